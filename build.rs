@@ -1,6 +1,27 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    prost_build::Config::new()
-        .out_dir("src/")
+    let mut config = prost_build::Config::new();
+    config.out_dir("src/");
+
+    // pbjson generates its serde impls from the descriptors of the messages
+    #[cfg(feature = "json")]
+    let descriptor_path =
+        std::path::PathBuf::from(std::env::var("OUT_DIR")?).join("plugin_descriptor.bin");
+    #[cfg(feature = "json")]
+    config.file_descriptor_set_path(&descriptor_path);
+
+    config.compile_protos(&["proto/codegen.proto"], &["proto/"])?;
+
+    #[cfg(feature = "json")]
+    pbjson_build::Builder::new()
+        .register_descriptors(&std::fs::read(&descriptor_path)?)?
+        .build(&[".plugin"])?;
+
+    // Only the `CodegenService` stubs are generated here; the messages are
+    // shared with the prost output above through `extern_path`.
+    #[cfg(feature = "grpc")]
+    tonic_prost_build::configure()
+        .extern_path(".plugin", "crate::plugin")
         .compile_protos(&["proto/codegen.proto"], &["proto/"])?;
+
     Ok(())
 }
@@ -0,0 +1,765 @@
+//! Inspect and convert the messages exchanged between sqlc and its plugins.
+//!
+//! ```text
+//! sqlc-gen-core json [--request|--response] <file>
+//! sqlc-gen-core tree [--request|--response] <file>
+//! sqlc-gen-core encode [--request|--response] [-o <output>] <file.json>
+//! sqlc-gen-core write [--dry-run|--check] [--delete-stale --marker <text>] [-o <dir>] [--base-dir <dir>] <request> -- <plugin> [<args>...]
+//! ```
+//!
+//! `json` decodes a protobuf file into pretty JSON, `tree` prints a summary of
+//! the catalog, queries or generated files and `encode` turns (edited) JSON
+//! back into protobuf. Files are read as a `GenerateRequest` unless their name
+//! ends with `.response`, like the response files written by
+//! `SQLC_GEN_CAPTURE`; `--request` and `--response` override the detection.
+//! A file named `-` is read from stdin.
+//!
+//! `write` runs the plugin command given after `--` against a request
+//! (protobuf, or JSON if the file name ends with `.json`) and writes the
+//! generated files under `codegen.out`, or the directory given with `-o`, like
+//! sqlc would. A relative `codegen.out` is resolved against the base directory
+//! the runtime resolves schema paths against: `--base-dir`, which is also
+//! passed to the plugin, or `SQLC_GEN_BASE_DIR`, falling back to the working
+//! directory. With `--dry-run` the changes are only listed, with `--check`
+//! the command fails if any file differs, and `--delete-stale` removes the
+//! generated files the plugin no longer produces. Those are recognized by the
+//! text given with `--marker`, which has to be specific to the plugin:
+//! sqlc's generic `Code generated by sqlc` header is shared with other
+//! generators.
+
+use prost::Message;
+use sqlc_gen_core::output::{self, Mode, WriteOptions};
+use sqlc_gen_core::plugin::{Column, GenerateRequest, GenerateResponse, Identifier};
+use sqlc_gen_core::runtime::{RunOptions, BASE_DIR_ENV};
+use std::error::Error;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{ExitCode, Stdio};
+
+const USAGE: &str = "\
+usage: sqlc-gen-core <command> [--request|--response] [-o <output>] <file>
+       sqlc-gen-core write [<write options>] <request> -- <plugin> [<args>...]
+
+commands:
+  json     decode a protobuf request or response and print it as JSON
+  tree     print a summary of a protobuf request or response
+  encode   encode a JSON request or response as protobuf
+  write    run a plugin and write the generated files under codegen.out
+
+write options:
+  --dry-run           list the changes without writing any file
+  --check             fail if the generated files differ from the files on disk
+  --delete-stale      delete generated files the plugin no longer produces
+  --marker <text>     text identifying the plugin's files, required by --delete-stale
+  -o <dir>            write under <dir> instead of codegen.out
+  --base-dir <dir>    directory of sqlc.yaml, defaults to SQLC_GEN_BASE_DIR
+
+The plugin command and its arguments follow `--`; it reads the request from
+stdin and runs with SQLC_GEN_BASE_DIR set to the base directory, if any.
+
+Files ending in `.response` are read as a GenerateResponse, other files as a
+GenerateRequest. Use `-` to read from stdin.";
+
+/// Command line arguments
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Args {
+    command: Command,
+    kind: Option<Kind>,
+    output: Option<PathBuf>,
+    input: String,
+    plugin: Vec<String>,
+    base_dir: Option<PathBuf>,
+    mode: Mode,
+    delete_stale: bool,
+    marker: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Json,
+    Tree,
+    Encode,
+    Write,
+}
+
+/// Message stored in the input file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Request,
+    Response,
+}
+
+fn main() -> ExitCode {
+    if matches!(
+        std::env::args().nth(1).as_deref(),
+        Some("-h" | "--help" | "help")
+    ) {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut args = args.into_iter();
+
+    let command = match args.next().as_deref() {
+        Some("json") => Command::Json,
+        Some("tree") => Command::Tree,
+        Some("encode") => Command::Encode,
+        Some("write") => Command::Write,
+        Some(other) => return Err(format!("unknown command `{other}`")),
+        None => return Err("missing command".to_string()),
+    };
+
+    let mut kind = None;
+    let mut output = None;
+    let mut input = None;
+    let mut plugin = Vec::new();
+    let mut base_dir = None;
+    let mut mode = Mode::Write;
+    let mut delete_stale = false;
+    let mut marker = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--request" => kind = Some(Kind::Request),
+            "--response" => kind = Some(Kind::Response),
+            "-o" | "--output" => {
+                let path = args.next().ok_or("missing value for `-o`")?;
+                output = Some(PathBuf::from(path));
+            }
+            "--base-dir" if command == Command::Write => {
+                let path = args.next().ok_or("missing value for `--base-dir`")?;
+                base_dir = Some(PathBuf::from(path));
+            }
+            "--dry-run" if command == Command::Write => mode = Mode::DryRun,
+            "--check" if command == Command::Write => mode = Mode::Check,
+            "--delete-stale" if command == Command::Write => delete_stale = true,
+            "--marker" if command == Command::Write => {
+                marker = Some(args.next().ok_or("missing value for `--marker`")?);
+            }
+            "--" if command == Command::Write => {
+                plugin.extend(args.by_ref());
+                break;
+            }
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown option `{flag}`"))
+            }
+            _ if input.is_some() => return Err(format!("unexpected argument `{arg}`")),
+            _ => input = Some(arg),
+        }
+    }
+
+    if command == Command::Write && plugin.is_empty() {
+        return Err("missing plugin command after `--`".to_string());
+    }
+    if delete_stale && marker.as_deref().is_none_or(str::is_empty) {
+        return Err("`--delete-stale` requires `--marker`".to_string());
+    }
+
+    Ok(Args {
+        command,
+        kind,
+        output,
+        input: input.ok_or("missing input file")?,
+        plugin,
+        base_dir,
+        mode,
+        delete_stale,
+        marker,
+    })
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let input = read_input(&args.input)?;
+    if args.command == Command::Write {
+        return write(args, &input);
+    }
+
+    let kind = args.kind.unwrap_or_else(|| detect_kind(&args.input));
+
+    let output = match (args.command, kind) {
+        (Command::Json, Kind::Request) => to_json(&GenerateRequest::decode(&input[..])?)?,
+        (Command::Json, Kind::Response) => to_json(&GenerateResponse::decode(&input[..])?)?,
+        (Command::Tree, Kind::Request) => request_tree(&GenerateRequest::decode(&input[..])?)
+            .render()
+            .into_bytes(),
+        (Command::Tree, Kind::Response) => response_tree(&GenerateResponse::decode(&input[..])?)
+            .render()
+            .into_bytes(),
+        (Command::Encode, Kind::Request) => {
+            serde_json::from_slice::<GenerateRequest>(&input)?.encode_to_vec()
+        }
+        (Command::Encode, Kind::Response) => {
+            serde_json::from_slice::<GenerateResponse>(&input)?.encode_to_vec()
+        }
+        (Command::Write, _) => unreachable!("handled above"),
+    };
+
+    match &args.output {
+        Some(path) => std::fs::write(path, output)
+            .map_err(|err| format!("failed to write {}: {err}", path.display()).into()),
+        None => Ok(std::io::stdout().write_all(&output)?),
+    }
+}
+
+/// Run the plugin against the request and write the generated files
+fn write(args: &Args, input: &[u8]) -> Result<(), Box<dyn Error>> {
+    let request = if args.input.ends_with(".json") {
+        serde_json::from_slice::<GenerateRequest>(input)?
+    } else {
+        GenerateRequest::decode(input)?
+    };
+
+    let base_dir = args
+        .base_dir
+        .clone()
+        .or_else(|| RunOptions::from_env().base_dir);
+    let out_dir = match &args.output {
+        Some(dir) => dir.clone(),
+        None => {
+            let out = output::out_dir(&request)
+                .ok_or("the request has no codegen.out, use `-o` to set the output directory")?;
+            match &base_dir {
+                Some(base_dir) => base_dir.join(out),
+                None => out,
+            }
+        }
+    };
+
+    let response = run_plugin(&args.plugin, args.base_dir.as_deref(), &request)?;
+
+    let mut options = WriteOptions::default()
+        .with_mode(args.mode)
+        .with_delete_stale(args.delete_stale);
+    if let Some(marker) = &args.marker {
+        options = options.with_marker(marker);
+    }
+    let report = output::write_files(&out_dir, &response.files, &options)?;
+    print!("{report}");
+
+    Ok(())
+}
+
+/// Run a plugin command, sending the request on stdin like sqlc does
+///
+/// `base_dir` is passed to the plugin as `SQLC_GEN_BASE_DIR`.
+fn run_plugin(
+    command: &[String],
+    base_dir: Option<&Path>,
+    request: &GenerateRequest,
+) -> Result<GenerateResponse, Box<dyn Error>> {
+    let (program, args) = command.split_first().ok_or("empty plugin command")?;
+
+    let mut plugin = std::process::Command::new(program);
+    plugin.args(args);
+    if let Some(base_dir) = base_dir {
+        plugin.env(BASE_DIR_ENV, base_dir);
+    }
+    let mut child = plugin
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("failed to run {program}: {err}"))?;
+
+    // Write from a separate thread so that a plugin writing before it has read
+    // the whole request cannot deadlock on a full pipe
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let bytes = request.encode_to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&bytes));
+
+    let result = child.wait_with_output()?;
+    // A plugin may exit without reading the request; its status is what matters
+    let _ = writer.join();
+
+    if !result.status.success() {
+        return Err(format!("{program} failed with {}", result.status).into());
+    }
+
+    Ok(GenerateResponse::decode(&result.stdout[..])?)
+}
+
+fn read_input(input: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if input == "-" {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    } else {
+        std::fs::read(input).map_err(|err| format!("failed to read {input}: {err}").into())
+    }
+}
+
+/// Guess the message kind from the file name
+fn detect_kind(input: &str) -> Kind {
+    let name = input.strip_suffix(".json").unwrap_or(input);
+    if name.ends_with(".response") {
+        Kind::Response
+    } else {
+        Kind::Request
+    }
+}
+
+fn to_json<T: serde::Serialize>(message: &T) -> Result<Vec<u8>, serde_json::Error> {
+    let mut json = serde_json::to_vec_pretty(message)?;
+    json.push(b'\n');
+    Ok(json)
+}
+
+/// A node of the tree summary
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Node {
+    label: String,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            children: Vec::new(),
+        }
+    }
+
+    fn with_children(mut self, children: Vec<Node>) -> Self {
+        self.children = children;
+        self
+    }
+
+    fn render(&self) -> String {
+        let mut output = format!("{}\n", self.label);
+        self.render_children("", &mut output);
+        output
+    }
+
+    fn render_children(&self, prefix: &str, output: &mut String) {
+        for (index, child) in self.children.iter().enumerate() {
+            let last = index + 1 == self.children.len();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+
+            output.push_str(&format!("{prefix}{branch}{}\n", child.label));
+            child.render_children(&format!("{prefix}{indent}"), output);
+        }
+    }
+}
+
+fn request_tree(request: &GenerateRequest) -> Node {
+    let mut header = format!("GenerateRequest (sqlc {}", request.sqlc_version);
+    if let Some(settings) = &request.settings {
+        header += &format!(", engine {}", settings.engine);
+    }
+    header += ")";
+
+    let mut children = Vec::new();
+
+    if let Some(catalog) = &request.catalog {
+        let schemas = catalog
+            .schemas
+            .iter()
+            .map(|schema| {
+                let name = if schema.name.is_empty() {
+                    "(default)"
+                } else {
+                    &schema.name
+                };
+
+                let mut items: Vec<Node> = schema
+                    .tables
+                    .iter()
+                    .map(|table| {
+                        let name = table.rel.as_ref().map(|r| r.name.as_str()).unwrap_or("?");
+                        Node::new(format!("table {name}"))
+                            .with_children(table.columns.iter().map(column_node).collect())
+                    })
+                    .collect();
+                items.extend(
+                    schema
+                        .enums
+                        .iter()
+                        .map(|e| Node::new(format!("enum {} ({})", e.name, e.vals.join(", ")))),
+                );
+                items.extend(
+                    schema
+                        .composite_types
+                        .iter()
+                        .map(|c| Node::new(format!("composite type {}", c.name))),
+                );
+
+                Node::new(format!("schema {name}")).with_children(items)
+            })
+            .collect();
+
+        children.push(Node::new("catalog").with_children(schemas));
+    }
+
+    let queries = request
+        .queries
+        .iter()
+        .map(|query| {
+            let mut label = format!("{} {}", query.name, query.cmd);
+            if !query.filename.is_empty() {
+                label += &format!(" ({})", query.filename);
+            }
+
+            let mut sections = Vec::new();
+            if !query.params.is_empty() {
+                sections.push(
+                    Node::new("params").with_children(
+                        query
+                            .params
+                            .iter()
+                            .map(|p| match &p.column {
+                                Some(column) => {
+                                    let node = column_node(column);
+                                    Node::new(format!("${} {}", p.number, node.label))
+                                }
+                                None => Node::new(format!("${}", p.number)),
+                            })
+                            .collect(),
+                    ),
+                );
+            }
+            if !query.columns.is_empty() {
+                sections.push(
+                    Node::new("columns")
+                        .with_children(query.columns.iter().map(column_node).collect()),
+                );
+            }
+
+            Node::new(label).with_children(sections)
+        })
+        .collect();
+    children.push(Node::new("queries").with_children(queries));
+
+    Node::new(header).with_children(children)
+}
+
+fn response_tree(response: &GenerateResponse) -> Node {
+    Node::new("GenerateResponse").with_children(
+        response
+            .files
+            .iter()
+            .map(|file| Node::new(format!("{} ({} bytes)", file.name, file.contents.len())))
+            .collect(),
+    )
+}
+
+fn column_node(column: &Column) -> Node {
+    let mut label = column.name.clone();
+    if let Some(ty) = &column.r#type {
+        label += &format!(" {}", type_name(ty));
+    }
+    if column.is_array {
+        label += "[]";
+    }
+    if column.not_null {
+        label += " not null";
+    }
+    Node::new(label)
+}
+
+fn type_name(ty: &Identifier) -> String {
+    if ty.schema.is_empty() {
+        ty.name.clone()
+    } else {
+        format!("{}.{}", ty.schema, ty.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlc_gen_core::plugin::{Catalog, Codegen, File, Query, Schema, Settings, Table};
+
+    fn args(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            args(&["encode", "--response", "-o", "out.bin", "in.json"]).unwrap(),
+            Args {
+                command: Command::Encode,
+                kind: Some(Kind::Response),
+                output: Some(PathBuf::from("out.bin")),
+                input: "in.json".to_string(),
+                plugin: Vec::new(),
+                base_dir: None,
+                mode: Mode::Write,
+                delete_stale: false,
+                marker: None,
+            }
+        );
+        assert_eq!(args(&["tree", "-"]).unwrap().input, "-");
+
+        assert!(args(&[]).is_err());
+        assert!(args(&["show", "file"]).is_err());
+        assert!(args(&["json"]).is_err());
+        assert!(args(&["json", "--verbose", "file"]).is_err());
+        assert!(args(&["json", "a", "b"]).is_err());
+        assert!(args(&["json", "--check", "file"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_write_args() {
+        let parsed = args(&[
+            "write",
+            "--check",
+            "--delete-stale",
+            "--marker",
+            "Code generated by sqlc-gen-foo",
+            "--base-dir",
+            "db",
+            "request.bin",
+            "--",
+            "target/my plugins/sqlc-gen-foo",
+            "--check",
+            "-o",
+        ])
+        .unwrap();
+        assert_eq!(parsed.command, Command::Write);
+        assert_eq!(
+            parsed.plugin,
+            ["target/my plugins/sqlc-gen-foo", "--check", "-o"]
+        );
+        assert_eq!(parsed.base_dir, Some(PathBuf::from("db")));
+        assert_eq!(parsed.input, "request.bin");
+        assert_eq!(parsed.mode, Mode::Check);
+        assert!(parsed.delete_stale);
+        assert_eq!(
+            parsed.marker.as_deref(),
+            Some("Code generated by sqlc-gen-foo")
+        );
+
+        assert!(args(&["write", "request.bin"]).is_err());
+        assert!(args(&["write", "request.bin", "--"]).is_err());
+        assert!(args(&["json", "file", "--", "p"]).is_err());
+        let err = args(&["write", "--delete-stale", "request.bin", "--", "p"]).unwrap_err();
+        assert!(err.contains("--marker"));
+    }
+
+    #[test]
+    fn test_detect_kind() {
+        assert_eq!(detect_kind("request.bin"), Kind::Request);
+        assert_eq!(detect_kind("request.bin.response"), Kind::Response);
+        assert_eq!(detect_kind("request.bin.response.json"), Kind::Response);
+    }
+
+    #[test]
+    fn test_request_tree() {
+        let request = GenerateRequest {
+            settings: Some(Settings {
+                engine: "postgresql".to_string(),
+                ..Default::default()
+            }),
+            catalog: Some(Catalog {
+                schemas: vec![Schema {
+                    name: "public".to_string(),
+                    tables: vec![Table {
+                        rel: Some(Identifier {
+                            name: "users".to_string(),
+                            ..Default::default()
+                        }),
+                        columns: vec![Column::builder("id").ty("int4").not_null().build()],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            queries: vec![Query::builder("GetUser", ":one")
+                .filename("query.sql")
+                .param(Column::builder("id").ty("int4").not_null())
+                .column(Column::builder("tags").ty("text").array())
+                .build()],
+            sqlc_version: "1.0.0".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            request_tree(&request).render(),
+            "\
+GenerateRequest (sqlc 1.0.0, engine postgresql)
+├── catalog
+│   └── schema public
+│       └── table users
+│           └── id int4 not null
+└── queries
+    └── GetUser :one (query.sql)
+        ├── params
+        │   └── $1 id int4 not null
+        └── columns
+            └── tags text[]
+"
+        );
+    }
+
+    #[test]
+    fn test_response_tree() {
+        let response = GenerateResponse {
+            files: vec![File {
+                name: "db/models.rs".to_string(),
+                contents: b"// models".to_vec(),
+            }],
+        };
+
+        assert_eq!(
+            response_tree(&response).render(),
+            "GenerateResponse\n└── db/models.rs (9 bytes)\n"
+        );
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("request.bin");
+        let json = dir.path().join("request.json");
+        let encoded = dir.path().join("encoded.bin");
+
+        let request = GenerateRequest::builder()
+            .engine("sqlite")
+            .query(Query::builder("ListUsers", ":many"))
+            .build();
+        std::fs::write(&binary, request.encode_to_vec()).unwrap();
+
+        let path = |p: &PathBuf| p.display().to_string();
+        run(&Args {
+            command: Command::Json,
+            kind: None,
+            output: Some(json.clone()),
+            input: path(&binary),
+            plugin: Vec::new(),
+            base_dir: None,
+            mode: Mode::Write,
+            delete_stale: false,
+            marker: None,
+        })
+        .unwrap();
+        run(&Args {
+            command: Command::Encode,
+            kind: None,
+            output: Some(encoded.clone()),
+            input: path(&json),
+            plugin: Vec::new(),
+            base_dir: None,
+            mode: Mode::Write,
+            delete_stale: false,
+            marker: None,
+        })
+        .unwrap();
+
+        let decoded = GenerateRequest::decode(&std::fs::read(&encoded).unwrap()[..]).unwrap();
+        assert_eq!(decoded, request);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_runs_plugin() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let request_path = dir.path().join("request.bin");
+        let response_path = dir.path().join("canned response.bin");
+
+        let request = GenerateRequest::builder().engine("sqlite").build();
+        std::fs::write(&request_path, request.encode_to_vec()).unwrap();
+
+        // `cat` ignores the request on stdin and replays a canned response
+        let response = GenerateResponse {
+            files: vec![File {
+                name: "models.rs".to_string(),
+                contents: b"// models".to_vec(),
+            }],
+        };
+        std::fs::write(&response_path, response.encode_to_vec()).unwrap();
+
+        let mut args = Args {
+            command: Command::Write,
+            kind: None,
+            output: Some(out.clone()),
+            input: request_path.display().to_string(),
+            plugin: vec!["cat".to_string(), response_path.display().to_string()],
+            base_dir: None,
+            mode: Mode::Check,
+            delete_stale: false,
+            marker: None,
+        };
+        let err = run(&args).unwrap_err();
+        assert!(err.to_string().contains("out of date"));
+
+        args.mode = Mode::Write;
+        run(&args).unwrap();
+        assert_eq!(std::fs::read(out.join("models.rs")).unwrap(), b"// models");
+
+        args.mode = Mode::Check;
+        run(&args).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_resolves_out_against_base_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_dir = dir.path().join("project");
+        std::fs::create_dir(&base_dir).unwrap();
+        let request_path = dir.path().join("request.bin");
+        let response_path = dir.path().join("response.bin");
+
+        // The working directory of the test is the crate root, not `base_dir`
+        let mut request = GenerateRequest::builder().engine("sqlite").build();
+        request.settings.as_mut().unwrap().codegen = Some(Codegen {
+            out: "gen-out-relative-to-base-dir".to_string(),
+            ..Default::default()
+        });
+        std::fs::write(&request_path, request.encode_to_vec()).unwrap();
+
+        let response = GenerateResponse {
+            files: vec![File {
+                name: "models.rs".to_string(),
+                contents: b"// models".to_vec(),
+            }],
+        };
+        std::fs::write(&response_path, response.encode_to_vec()).unwrap();
+
+        // The plugin sees the base directory through SQLC_GEN_BASE_DIR
+        let script = format!(
+            "cat > /dev/null; test \"${BASE_DIR_ENV}\" = '{}' && cat '{}'",
+            base_dir.display(),
+            response_path.display()
+        );
+        run(&Args {
+            command: Command::Write,
+            kind: None,
+            output: None,
+            input: request_path.display().to_string(),
+            plugin: vec!["sh".to_string(), "-c".to_string(), script],
+            base_dir: Some(base_dir.clone()),
+            mode: Mode::Write,
+            delete_stale: false,
+            marker: None,
+        })
+        .unwrap();
+
+        let written = base_dir.join("gen-out-relative-to-base-dir/models.rs");
+        assert_eq!(std::fs::read(written).unwrap(), b"// models");
+        assert!(!Path::new("gen-out-relative-to-base-dir").exists());
+    }
+}
@@ -0,0 +1,429 @@
+//! Fluent builders for plugin messages.
+//!
+//! The prost-generated messages have many fields, most of which keep their
+//! default value in practice. The builders in this module only require the
+//! identifying fields and let the rest be set by name, which keeps requests
+//! built in tests (of this crate and of plugins) short and readable.
+//!
+//! # Example
+//!
+//! ```
+//! use sqlc_gen_core::plugin::{Column, GenerateRequest, Query};
+//!
+//! let query = Query::builder("GetUser", ":one")
+//!     .text("SELECT id, name FROM users WHERE id = $1")
+//!     .column(Column::builder("id").ty("int4").not_null().table("public", "users"))
+//!     .column(Column::builder("name").ty("text").table("public", "users"))
+//!     .param(Column::builder("id").ty("int4").not_null())
+//!     .build();
+//!
+//! let request = GenerateRequest::builder()
+//!     .engine("postgresql")
+//!     .schema("schema.sql")
+//!     .query(query)
+//!     .build();
+//!
+//! assert_eq!(request.queries[0].params[0].number, 1);
+//! assert!(request.queries[0].columns[0].not_null);
+//! ```
+
+use crate::plugin::{Catalog, Column, GenerateRequest, Identifier, Parameter, Query, Settings};
+
+/// Identifier of a schema-qualified object
+fn identifier(schema: &str, name: &str) -> Identifier {
+    Identifier {
+        catalog: String::new(),
+        schema: schema.to_string(),
+        name: name.to_string(),
+    }
+}
+
+impl Column {
+    /// Start building a column named `name`
+    pub fn builder(name: impl Into<String>) -> ColumnBuilder {
+        ColumnBuilder {
+            column: Column {
+                name: name.into(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Builder for [`Column`], created with [`Column::builder`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnBuilder {
+    column: Column,
+}
+
+impl ColumnBuilder {
+    /// Set the type name (e.g. `int4`)
+    pub fn ty(mut self, name: impl AsRef<str>) -> Self {
+        self.column.r#type = Some(identifier("", name.as_ref()));
+        self
+    }
+
+    /// Set a schema-qualified type, such as a user-defined enum
+    pub fn ty_in(mut self, schema: impl AsRef<str>, name: impl AsRef<str>) -> Self {
+        self.column.r#type = Some(identifier(schema.as_ref(), name.as_ref()));
+        self
+    }
+
+    /// Mark the column as `NOT NULL`
+    pub fn not_null(mut self) -> Self {
+        self.column.not_null = true;
+        self
+    }
+
+    /// Mark the column as a one-dimensional array
+    pub fn array(self) -> Self {
+        self.array_dims(1)
+    }
+
+    /// Mark the column as an array with `dims` dimensions
+    pub fn array_dims(mut self, dims: i32) -> Self {
+        self.column.is_array = dims > 0;
+        self.column.array_dims = dims;
+        self
+    }
+
+    /// Mark the column as unsigned (MySQL)
+    pub fn unsigned(mut self) -> Self {
+        self.column.unsigned = true;
+        self
+    }
+
+    /// Set the column comment
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.column.comment = comment.into();
+        self
+    }
+
+    /// Set the length of the type (e.g. `255` for `varchar(255)`)
+    pub fn length(mut self, length: i32) -> Self {
+        self.column.length = length;
+        self
+    }
+
+    /// Set the table the column belongs to
+    pub fn table(mut self, schema: impl AsRef<str>, name: impl AsRef<str>) -> Self {
+        self.column.table = Some(identifier(schema.as_ref(), name.as_ref()));
+        self
+    }
+
+    /// Set the alias of the table in the query
+    pub fn table_alias(mut self, alias: impl Into<String>) -> Self {
+        self.column.table_alias = alias.into();
+        self
+    }
+
+    /// Set the name of the column in its table, when it differs from the output name
+    pub fn original_name(mut self, name: impl Into<String>) -> Self {
+        self.column.original_name = name.into();
+        self
+    }
+
+    /// Set the scope of the column
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.column.scope = scope.into();
+        self
+    }
+
+    /// Mark the column as a named parameter (`@name` or `sqlc.arg(name)`)
+    pub fn named_param(mut self) -> Self {
+        self.column.is_named_param = true;
+        self
+    }
+
+    /// Mark the column as the result of a function call
+    pub fn func_call(mut self) -> Self {
+        self.column.is_func_call = true;
+        self
+    }
+
+    /// Mark the column as a `sqlc.slice` parameter
+    pub fn sqlc_slice(mut self) -> Self {
+        self.column.is_sqlc_slice = true;
+        self
+    }
+
+    /// Mark the column as a `sqlc.embed` of the given table
+    pub fn embed_table(mut self, schema: impl AsRef<str>, name: impl AsRef<str>) -> Self {
+        self.column.embed_table = Some(identifier(schema.as_ref(), name.as_ref()));
+        self
+    }
+
+    /// Finish building the column
+    pub fn build(self) -> Column {
+        self.column
+    }
+}
+
+impl From<ColumnBuilder> for Column {
+    fn from(builder: ColumnBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl Parameter {
+    /// Start building the parameter at position `number` (starting at 1)
+    pub fn builder(number: i32) -> ParameterBuilder {
+        ParameterBuilder {
+            parameter: Parameter {
+                number,
+                column: None,
+            },
+        }
+    }
+}
+
+/// Builder for [`Parameter`], created with [`Parameter::builder`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterBuilder {
+    parameter: Parameter,
+}
+
+impl ParameterBuilder {
+    /// Set the column describing the parameter
+    pub fn column(mut self, column: impl Into<Column>) -> Self {
+        self.parameter.column = Some(column.into());
+        self
+    }
+
+    /// Finish building the parameter
+    pub fn build(self) -> Parameter {
+        self.parameter
+    }
+}
+
+impl From<ParameterBuilder> for Parameter {
+    fn from(builder: ParameterBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl Query {
+    /// Start building a query named `name` with the command `cmd` (e.g. `:one`)
+    pub fn builder(name: impl Into<String>, cmd: impl Into<String>) -> QueryBuilder {
+        QueryBuilder {
+            query: Query {
+                name: name.into(),
+                cmd: cmd.into(),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Builder for [`Query`], created with [`Query::builder`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryBuilder {
+    query: Query,
+}
+
+impl QueryBuilder {
+    /// Set the SQL text of the query
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.query.text = text.into();
+        self
+    }
+
+    /// Add an output column
+    pub fn column(mut self, column: impl Into<Column>) -> Self {
+        self.query.columns.push(column.into());
+        self
+    }
+
+    /// Add a parameter numbered after the parameters added so far
+    pub fn param(mut self, column: impl Into<Column>) -> Self {
+        let number = self.query.params.len() as i32 + 1;
+        self.query
+            .params
+            .push(Parameter::builder(number).column(column).build());
+        self
+    }
+
+    /// Add a parameter with an explicit number
+    pub fn parameter(mut self, parameter: impl Into<Parameter>) -> Self {
+        self.query.params.push(parameter.into());
+        self
+    }
+
+    /// Add a comment line
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.query.comments.push(comment.into());
+        self
+    }
+
+    /// Set the name of the file the query was read from
+    pub fn filename(mut self, filename: impl Into<String>) -> Self {
+        self.query.filename = filename.into();
+        self
+    }
+
+    /// Set the table an `INSERT` query writes to
+    pub fn insert_into_table(mut self, schema: impl AsRef<str>, name: impl AsRef<str>) -> Self {
+        self.query.insert_into_table = Some(identifier(schema.as_ref(), name.as_ref()));
+        self
+    }
+
+    /// Finish building the query
+    pub fn build(self) -> Query {
+        self.query
+    }
+}
+
+impl From<QueryBuilder> for Query {
+    fn from(builder: QueryBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl GenerateRequest {
+    /// Start building an empty request
+    pub fn builder() -> GenerateRequestBuilder {
+        GenerateRequestBuilder {
+            request: GenerateRequest::default(),
+        }
+    }
+}
+
+/// Builder for [`GenerateRequest`], created with [`GenerateRequest::builder`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerateRequestBuilder {
+    request: GenerateRequest,
+}
+
+impl GenerateRequestBuilder {
+    /// Settings of the request, created on first use
+    fn settings(&mut self) -> &mut Settings {
+        self.request.settings.get_or_insert_with(Settings::default)
+    }
+
+    /// Set the database engine (`postgresql`, `mysql` or `sqlite`)
+    pub fn engine(mut self, engine: impl Into<String>) -> Self {
+        self.settings().engine = engine.into();
+        self
+    }
+
+    /// Add a schema entry (file, directory or glob pattern)
+    pub fn schema(mut self, entry: impl Into<String>) -> Self {
+        self.settings().schema.push(entry.into());
+        self
+    }
+
+    /// Add a query file entry to the settings
+    pub fn queries_path(mut self, entry: impl Into<String>) -> Self {
+        self.settings().queries.push(entry.into());
+        self
+    }
+
+    /// Set the catalog sent by sqlc
+    pub fn catalog(mut self, catalog: Catalog) -> Self {
+        self.request.catalog = Some(catalog);
+        self
+    }
+
+    /// Add a query
+    pub fn query(mut self, query: impl Into<Query>) -> Self {
+        self.request.queries.push(query.into());
+        self
+    }
+
+    /// Set the sqlc version
+    pub fn sqlc_version(mut self, version: impl Into<String>) -> Self {
+        self.request.sqlc_version = version.into();
+        self
+    }
+
+    /// Set the plugin options (JSON)
+    pub fn plugin_options(mut self, options: impl Into<Vec<u8>>) -> Self {
+        self.request.plugin_options = options.into();
+        self
+    }
+
+    /// Set the global options (JSON)
+    pub fn global_options(mut self, options: impl Into<Vec<u8>>) -> Self {
+        self.request.global_options = options.into();
+        self
+    }
+
+    /// Finish building the request
+    pub fn build(self) -> GenerateRequest {
+        self.request
+    }
+}
+
+impl From<GenerateRequestBuilder> for GenerateRequest {
+    fn from(builder: GenerateRequestBuilder) -> Self {
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_builder() {
+        let column = Column::builder("id")
+            .ty("int4")
+            .not_null()
+            .table("public", "users")
+            .build();
+
+        assert_eq!(
+            column,
+            Column {
+                name: "id".to_string(),
+                not_null: true,
+                r#type: Some(identifier("", "int4")),
+                table: Some(identifier("public", "users")),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_column_builder_array() {
+        let column = Column::builder("tags").ty("text").array_dims(2).build();
+        assert!(column.is_array);
+        assert_eq!(column.array_dims, 2);
+
+        let column = Column::builder("tags").ty("text").array().build();
+        assert_eq!(column.array_dims, 1);
+    }
+
+    #[test]
+    fn test_query_builder_numbers_params() {
+        let query = Query::builder("UpdateUser", ":exec")
+            .text("UPDATE users SET name = $2 WHERE id = $1")
+            .param(Column::builder("id").ty("int4"))
+            .param(Column::builder("name").ty("text"))
+            .parameter(Parameter::builder(7).column(Column::builder("extra")))
+            .build();
+
+        let numbers: Vec<_> = query.params.iter().map(|p| p.number).collect();
+        assert_eq!(numbers, vec![1, 2, 7]);
+        assert_eq!(query.params[1].column.as_ref().unwrap().name, "name");
+        assert_eq!(query.cmd, ":exec");
+    }
+
+    #[test]
+    fn test_generate_request_builder() {
+        let request = GenerateRequest::builder()
+            .engine("mysql")
+            .schema("schema.sql")
+            .schema("migrations")
+            .query(Query::builder("ListUsers", ":many"))
+            .sqlc_version("1.0.0")
+            .plugin_options(r#"{"package":"db"}"#)
+            .build();
+
+        let settings = request.settings.as_ref().unwrap();
+        assert_eq!(settings.engine, "mysql");
+        assert_eq!(settings.schema, vec!["schema.sql", "migrations"]);
+        assert_eq!(request.queries[0].name, "ListUsers");
+        assert_eq!(request.plugin_options, br#"{"package":"db"}"#);
+    }
+}
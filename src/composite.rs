@@ -0,0 +1,242 @@
+//! Attributes of composite types.
+//!
+//! PostgreSQL composite types (`CREATE TYPE address AS (street text, city
+//! text)`) are listed in `Schema.composite_types`, but the plugin protocol only
+//! gives them a name and a comment. [`CompositeAttributes`] keeps their
+//! attributes alongside the catalog, as columns, so that a column of a
+//! composite type can be resolved back to its fields and generated as a nested
+//! row struct.
+//!
+//! [`CatalogBuilder`](crate::schema::CatalogBuilder) collects the attributes
+//! while parsing schema files. The runtime hands the attributes parsed from
+//! the schema files of a request to
+//! [`Plugin::prepare_composites`](crate::runtime::Plugin::prepare_composites),
+//! and [`Frontend::generate_with_composites`](crate::frontend::Frontend::generate_with_composites)
+//! returns them alongside the request it builds. While the runtime processes a
+//! request, they are also the *current* attributes of the thread, available
+//! with [`current`] to handlers that only receive the request.
+//!
+//! # Example
+//!
+//! ```
+//! use sqlc_gen_core::schema::CatalogBuilder;
+//!
+//! let mut builder = CatalogBuilder::new("postgresql");
+//! builder
+//!     .parse_sql(
+//!         "CREATE TYPE address AS (street text, city text);
+//!          CREATE TABLE users (id int NOT NULL, home address);",
+//!     )
+//!     .unwrap();
+//!
+//! let attributes = builder.composite_attributes();
+//! let catalog = builder.build();
+//!
+//! let home = &catalog.schemas[0].tables[0].columns[1];
+//! let fields: Vec<_> = attributes
+//!     .resolve(home)
+//!     .unwrap()
+//!     .iter()
+//!     .map(|field| field.name.as_str())
+//!     .collect();
+//! assert_eq!(fields, ["street", "city"]);
+//! ```
+
+use crate::plugin::Column;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+/// Attributes of the composite types of a catalog, by schema and type name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompositeAttributes {
+    /// Schema that unqualified type names and the `""` schema refer to
+    pub default_schema: String,
+
+    /// Attributes of each type, keyed by schema and type name
+    types: BTreeMap<(String, String), Vec<Column>>,
+}
+
+impl CompositeAttributes {
+    /// Create an empty set of attributes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the schema that unqualified type names refer to
+    pub fn with_default_schema(mut self, schema: impl Into<String>) -> Self {
+        self.default_schema = schema.into();
+        self
+    }
+
+    /// Set the attributes of a type, replacing any previous definition
+    pub fn insert(&mut self, schema: &str, name: &str, attributes: Vec<Column>) {
+        self.types
+            .insert((schema.to_string(), name.to_string()), attributes);
+    }
+
+    /// Attributes of the type `name` in `schema`
+    ///
+    /// The `""` schema and [`default_schema`](Self::default_schema) are
+    /// interchangeable.
+    pub fn get(&self, schema: &str, name: &str) -> Option<&[Column]> {
+        let lookup = |schema: &str| {
+            self.types
+                .get(&(schema.to_string(), name.to_string()))
+                .map(Vec::as_slice)
+        };
+
+        lookup(schema).or_else(|| {
+            if schema.is_empty() {
+                lookup(&self.default_schema)
+            } else if schema == self.default_schema {
+                lookup("")
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Attributes of the composite type of `column`
+    ///
+    /// Type names qualified in the name itself (`app.address`) are looked up
+    /// in their schema. Returns `None` when the column does not have a known
+    /// composite type.
+    pub fn resolve(&self, column: &Column) -> Option<&[Column]> {
+        let r#type = column.r#type.as_ref()?;
+        if !r#type.schema.is_empty() {
+            return self.get(&r#type.schema, &r#type.name);
+        }
+
+        match r#type.name.rsplit_once('.') {
+            Some((schema, name)) => self
+                .get(schema, name)
+                .or_else(|| self.get("", &r#type.name)),
+            None => self.get("", &r#type.name),
+        }
+    }
+
+    /// Iterate over the types as `(schema, name, attributes)`, by schema and name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, &[Column])> {
+        self.types.iter().map(|((schema, name), attributes)| {
+            (schema.as_str(), name.as_str(), attributes.as_slice())
+        })
+    }
+
+    /// Number of types
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    /// Check whether there are no types
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    /// Move the attributes of a type after `ALTER TYPE ... RENAME TO`
+    pub(crate) fn rename(&mut self, schema: &str, from: &str, to: &str) {
+        if let Some(attributes) = self.types.remove(&(schema.to_string(), from.to_string())) {
+            self.insert(schema, to, attributes);
+        }
+    }
+
+    /// Run `f` with these attributes as the current attributes of the thread
+    ///
+    /// The previous current attributes are restored afterwards, even if `f`
+    /// panics.
+    pub fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        struct Restore(Option<CompositeAttributes>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+            }
+        }
+
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        let _restore = Restore(previous);
+        f()
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<CompositeAttributes>> = const { RefCell::new(None) };
+}
+
+/// Current attributes of the thread, or empty attributes outside of a scope
+pub fn current() -> CompositeAttributes {
+    CURRENT
+        .with(|current| current.borrow().clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::Identifier;
+
+    fn column(name: &str, type_schema: &str, type_name: &str) -> Column {
+        Column {
+            name: name.to_string(),
+            r#type: Some(Identifier {
+                catalog: String::new(),
+                schema: type_schema.to_string(),
+                name: type_name.to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn attributes() -> CompositeAttributes {
+        let mut attributes = CompositeAttributes::new().with_default_schema("public");
+        attributes.insert("", "address", vec![column("city", "", "text")]);
+        attributes.insert("app", "point", vec![column("x", "", "int")]);
+        attributes
+    }
+
+    #[test]
+    fn test_get_default_schema() {
+        let attributes = attributes();
+
+        assert_eq!(attributes.get("", "address").unwrap()[0].name, "city");
+        assert_eq!(attributes.get("public", "address").unwrap()[0].name, "city");
+        assert!(attributes.get("app", "address").is_none());
+        assert_eq!(attributes.len(), 2);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let attributes = attributes();
+
+        assert!(attributes.resolve(&column("home", "", "address")).is_some());
+        assert!(attributes
+            .resolve(&column("home", "public", "address"))
+            .is_some());
+        assert!(attributes.resolve(&column("at", "", "app.point")).is_some());
+        assert!(attributes.resolve(&column("at", "app", "point")).is_some());
+        assert!(attributes.resolve(&column("id", "", "int")).is_none());
+        assert!(attributes.resolve(&Column::default()).is_none());
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut attributes = attributes();
+        attributes.rename("app", "point", "coordinates");
+
+        let names: Vec<_> = attributes
+            .iter()
+            .map(|(schema, name, _)| (schema, name))
+            .collect();
+        assert_eq!(names, [("", "address"), ("app", "coordinates")]);
+    }
+
+    #[test]
+    fn test_scope_sets_current_attributes() {
+        assert!(current().is_empty());
+        let value = attributes().scope(|| {
+            assert_eq!(current().len(), 2);
+            42
+        });
+        assert_eq!(value, 42);
+        assert!(current().is_empty());
+    }
+}
@@ -0,0 +1,1519 @@
+//! A small, offline stand-in for `sqlc generate`.
+//!
+//! [`Frontend`] reads schema files and `-- name: GetUser :one` annotated query
+//! files and produces the [`GenerateRequest`] sqlc would send to a plugin, so
+//! plugins can be exercised without installing sqlc:
+//!
+//! - The catalog is built from the schema files with [`CatalogBuilder`]
+//! - Each annotated query fills `Query.name`, `cmd`, `text`, `filename` and
+//!   `comments` (the `--` comment lines directly following the annotation)
+//! - For simple `SELECT`, `INSERT`, `UPDATE` and `DELETE` statements, `params`
+//!   and result `columns` are inferred from the catalog with sqlparser
+//!
+//! Inference covers the common cases (`*` and column references, comparisons
+//! and `IN`/`BETWEEN`/`= ANY(...)` against columns, `CASE`, `INSERT ...
+//! VALUES`, `UPDATE ... SET`, `LIMIT`/`OFFSET`, `RETURNING`). Subqueries,
+//! `JOIN ... ON`, `GROUP BY`, `HAVING` and `ORDER BY` are walked in the order
+//! they appear, so MySQL `?` placeholders keep their position. It does not aim
+//! to match sqlc exactly: placeholders it cannot type get the `any` type
+//! instead of being dropped.
+//!
+//! # Example
+//!
+//! ```
+//! use sqlc_gen_core::frontend::Frontend;
+//! use sqlc_gen_core::fs::MemoryFileSystem;
+//! use sqlc_gen_core::runtime::RunOptions;
+//!
+//! let fs = MemoryFileSystem::new()
+//!     .with_file("schema.sql", "CREATE TABLE users (id int NOT NULL, name text);")
+//!     .with_file(
+//!         "query.sql",
+//!         "-- name: GetUser :one\nSELECT id, name FROM users WHERE id = $1;\n",
+//!     );
+//!
+//! let request = Frontend::new("postgresql")
+//!     .schema("schema.sql")
+//!     .queries("query.sql")
+//!     .with_options(RunOptions::default().with_file_system(fs))
+//!     .generate()
+//!     .unwrap();
+//!
+//! let query = &request.queries[0];
+//! assert_eq!(query.name, "GetUser");
+//! assert_eq!(query.text, "SELECT id, name FROM users WHERE id = $1");
+//! assert_eq!(query.columns.len(), 2);
+//! assert_eq!(query.params[0].column.as_ref().unwrap().name, "id");
+//! ```
+
+use crate::composite::CompositeAttributes;
+use crate::loader::SchemaLoader;
+use crate::plugin::{
+    Catalog, Column, GenerateRequest, Identifier, Parameter, Query, Settings, Table,
+};
+use crate::runtime::RunOptions;
+use crate::schema::CatalogBuilder;
+use sqlparser::ast::{
+    visit_expressions, AssignmentTarget, Delete, Expr, FromTable, FunctionArg, FunctionArgExpr,
+    FunctionArguments, GroupByExpr, Ident, Insert, JoinConstraint, JoinOperator, LimitClause,
+    ObjectName, OrderBy, OrderByKind, Query as SqlQuery, Select, SelectItem,
+    SelectItemQualifiedWildcardKind, SetExpr, Statement, TableFactor, TableObject, TableWithJoins,
+    Value, Visit,
+};
+use sqlparser::dialect::dialect_from_str;
+use sqlparser::parser::Parser;
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+/// Commands accepted in `-- name:` annotations
+const COMMANDS: &[&str] = &[
+    ":one",
+    ":many",
+    ":exec",
+    ":execrows",
+    ":execresult",
+    ":execlastid",
+    ":copyfrom",
+    ":batchexec",
+    ":batchmany",
+    ":batchone",
+];
+
+/// Error produced while building a request from schema and query files.
+#[derive(Debug)]
+pub enum Error {
+    /// A schema file could not be loaded or parsed
+    Schema(crate::runtime::Error),
+
+    /// A query file could not be found or read
+    QueryRead {
+        /// Path of the query file or entry
+        path: PathBuf,
+        /// Underlying I/O error
+        source: io::Error,
+    },
+
+    /// A query file is not correctly annotated
+    Annotation {
+        /// Path of the query file
+        path: PathBuf,
+        /// Line of the problem, starting from 1
+        line: usize,
+        /// Description of the problem
+        message: String,
+    },
+
+    /// The SQL of an annotated query could not be parsed
+    QueryParse {
+        /// Path of the query file
+        path: PathBuf,
+        /// Name of the query
+        name: String,
+        /// Parse error, located within the query text
+        source: crate::schema::Error,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Schema(err) => write!(f, "{err}"),
+            Self::QueryRead { path, source } => {
+                write!(f, "failed to read query file {}: {source}", path.display())
+            }
+            Self::Annotation {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{line}: {message}", path.display()),
+            Self::QueryParse { path, name, source } => write!(
+                f,
+                "failed to parse query {name} in {}: {source}",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Schema(err) => Some(err),
+            Self::QueryRead { source, .. } => Some(source),
+            Self::Annotation { .. } => None,
+            Self::QueryParse { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Builds a [`GenerateRequest`] from schema and query files.
+#[derive(Debug, Clone, Default)]
+pub struct Frontend {
+    /// Database engine (`postgresql`, `mysql` or `sqlite`)
+    pub engine: String,
+
+    /// Schema entries (files, directories or glob patterns)
+    pub schema: Vec<String>,
+
+    /// Query entries (files, directories or glob patterns)
+    pub queries: Vec<String>,
+
+    /// Plugin options sent with the request
+    pub plugin_options: Vec<u8>,
+
+    /// Base directory and file system used to read schema and query files
+    pub options: RunOptions,
+}
+
+impl Frontend {
+    /// Create a frontend for `engine`
+    pub fn new(engine: impl Into<String>) -> Self {
+        Self {
+            engine: engine.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Add a schema entry
+    pub fn schema(mut self, entry: impl Into<String>) -> Self {
+        self.schema.push(entry.into());
+        self
+    }
+
+    /// Add a query entry
+    pub fn queries(mut self, entry: impl Into<String>) -> Self {
+        self.queries.push(entry.into());
+        self
+    }
+
+    /// Set the plugin options (JSON)
+    pub fn plugin_options(mut self, options: impl Into<Vec<u8>>) -> Self {
+        self.plugin_options = options.into();
+        self
+    }
+
+    /// Read files according to `options` (base directory and file system)
+    pub fn with_options(mut self, options: RunOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Read the schema and query files and build the request
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Schema`] if a schema file cannot be loaded or parsed,
+    /// [`Error::QueryRead`] if a query file cannot be read,
+    /// [`Error::Annotation`] for missing or invalid `-- name:` annotations and
+    /// [`Error::QueryParse`] if the SQL of a query cannot be parsed.
+    pub fn generate(&self) -> Result<GenerateRequest, Error> {
+        self.generate_with_composites()
+            .map(|(request, _attributes)| request)
+    }
+
+    /// Like [`generate`](Self::generate), also returning the attributes of the
+    /// composite types parsed from the schema files
+    ///
+    /// Hand the attributes to the plugin, or make them current with
+    /// [`CompositeAttributes::scope`], like the runtime does.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`generate`](Self::generate).
+    pub fn generate_with_composites(
+        &self,
+    ) -> Result<(GenerateRequest, CompositeAttributes), Error> {
+        let loader = SchemaLoader {
+            base_dir: self.options.base_dir.clone(),
+            fs: self.options.fs.clone(),
+        };
+
+        let mut builder = CatalogBuilder::new(&self.engine);
+        for file in loader.load(&self.schema).map_err(Error::Schema)? {
+            builder.parse_sql(&file.sql).map_err(|source| {
+                Error::Schema(crate::runtime::Error::SqlParse {
+                    path: file.path,
+                    source,
+                })
+            })?;
+        }
+        let attributes = builder.composite_attributes();
+        let catalog = builder.build();
+
+        let mut queries = Vec::new();
+        for file in loader.load(&self.queries).map_err(query_read_error)? {
+            for annotated in split_queries(&file.path, &file.sql)? {
+                queries.push(self.analyze(&catalog, &file.path, annotated)?);
+            }
+        }
+
+        let request = GenerateRequest {
+            settings: Some(Settings {
+                engine: self.engine.clone(),
+                schema: self.schema.clone(),
+                queries: self.queries.clone(),
+                ..Default::default()
+            }),
+            catalog: Some(catalog),
+            queries,
+            plugin_options: self.plugin_options.clone(),
+            ..Default::default()
+        };
+        Ok((request, attributes))
+    }
+
+    /// Parse an annotated query and infer its parameters and columns
+    fn analyze(
+        &self,
+        catalog: &Catalog,
+        path: &Path,
+        annotated: Annotated,
+    ) -> Result<Query, Error> {
+        let mut query = Query {
+            text: annotated.text,
+            name: annotated.name,
+            cmd: annotated.cmd,
+            comments: annotated.comments,
+            filename: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+
+        let dialect = dialect_from_str(&self.engine).ok_or_else(|| {
+            Error::Schema(crate::runtime::Error::SqlParse {
+                path: path.to_path_buf(),
+                source: crate::schema::Error::UnknownDialect(self.engine.clone()),
+            })
+        })?;
+        let statements =
+            Parser::parse_sql(dialect.as_ref(), &query.text).map_err(|err| Error::QueryParse {
+                path: path.to_path_buf(),
+                name: query.name.clone(),
+                source: crate::schema::Error::parse(&query.text, err),
+            })?;
+
+        if let Some(statement) = statements.first() {
+            Analyzer::new(catalog).statement(statement, &mut query);
+        }
+
+        Ok(query)
+    }
+}
+
+/// Report loader errors for query entries as query read errors
+fn query_read_error(err: crate::runtime::Error) -> Error {
+    match err {
+        crate::runtime::Error::SchemaRead { path, source, .. } => Error::QueryRead { path, source },
+        crate::runtime::Error::SchemaPath { path, base_dir } => Error::QueryRead {
+            source: io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("escapes base directory {}", base_dir.display()),
+            ),
+            path,
+        },
+        other => Error::Schema(other),
+    }
+}
+
+/// A query split out of a query file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Annotated {
+    name: String,
+    cmd: String,
+    text: String,
+    comments: Vec<String>,
+}
+
+/// Split a query file on its `-- name: <Name> <:cmd>` annotations
+fn split_queries(path: &Path, sql: &str) -> Result<Vec<Annotated>, Error> {
+    let error = |line: usize, message: String| Error::Annotation {
+        path: path.to_path_buf(),
+        line,
+        message,
+    };
+
+    let mut queries: Vec<(usize, Annotated)> = Vec::new();
+    let mut lines: Vec<&str> = Vec::new();
+
+    for (index, line) in sql.lines().enumerate() {
+        let number = index + 1;
+        let trimmed = line.trim();
+
+        if let Some(annotation) = trimmed
+            .strip_prefix("--")
+            .map(str::trim_start)
+            .and_then(|rest| rest.strip_prefix("name:"))
+        {
+            if let Some((_, query)) = queries.last_mut() {
+                query.text = query_text(&lines);
+            }
+            lines.clear();
+
+            let mut parts = annotation.split_whitespace();
+            let name = parts
+                .next()
+                .ok_or_else(|| error(number, "missing query name".to_string()))?;
+            let cmd = parts
+                .next()
+                .ok_or_else(|| error(number, format!("missing command for query {name}")))?;
+            if !COMMANDS.contains(&cmd) {
+                return Err(error(
+                    number,
+                    format!("unknown command `{cmd}` for query {name}"),
+                ));
+            }
+
+            queries.push((
+                number,
+                Annotated {
+                    name: name.to_string(),
+                    cmd: cmd.to_string(),
+                    text: String::new(),
+                    comments: Vec::new(),
+                },
+            ));
+            continue;
+        }
+
+        match queries.last_mut() {
+            // Comment lines between the annotation and the SQL describe the query
+            Some((_, query)) if lines.is_empty() && trimmed.starts_with("--") => {
+                query
+                    .comments
+                    .push(trimmed.strip_prefix("--").unwrap_or_default().to_string());
+            }
+            Some(_) => {
+                if !trimmed.is_empty() || !lines.is_empty() {
+                    lines.push(line);
+                }
+            }
+            None if trimmed.is_empty() || trimmed.starts_with("--") => {}
+            None => {
+                return Err(error(
+                    number,
+                    "statement without a `-- name:` annotation".to_string(),
+                ))
+            }
+        }
+    }
+
+    if let Some((_, query)) = queries.last_mut() {
+        query.text = query_text(&lines);
+    }
+
+    queries
+        .into_iter()
+        .map(|(line, query)| {
+            if query.text.is_empty() {
+                Err(error(line, format!("query {} has no SQL", query.name)))
+            } else {
+                Ok(query)
+            }
+        })
+        .collect()
+}
+
+/// Join the SQL lines of a query, without the trailing semicolon
+fn query_text(lines: &[&str]) -> String {
+    let text = lines.join("\n");
+    text.trim().trim_end_matches(';').trim_end().to_string()
+}
+
+/// A table referenced by a statement, with the alias it is referenced by
+struct Source<'a> {
+    alias: Option<String>,
+    table: &'a Table,
+}
+
+/// Infers the parameters and result columns of a statement
+///
+/// Clauses are walked in the order they appear in the text, so that MySQL `?`
+/// placeholders are numbered like sqlc numbers them.
+struct Analyzer<'a> {
+    catalog: &'a Catalog,
+    sources: Vec<Source<'a>>,
+    /// Sources of the enclosing queries, innermost last
+    scopes: Vec<Vec<Source<'a>>>,
+    params: BTreeMap<i32, Column>,
+    next_positional: i32,
+}
+
+impl<'a> Analyzer<'a> {
+    fn new(catalog: &'a Catalog) -> Self {
+        Self {
+            catalog,
+            sources: Vec::new(),
+            scopes: Vec::new(),
+            params: BTreeMap::new(),
+            next_positional: 1,
+        }
+    }
+
+    fn statement(mut self, statement: &Statement, query: &mut Query) {
+        match statement {
+            Statement::Query(select) => {
+                query.columns = self.query(select);
+            }
+            Statement::Insert(insert) => self.insert(insert, query),
+            Statement::Update {
+                table,
+                assignments,
+                selection,
+                returning,
+                limit,
+                ..
+            } => {
+                self.add_sources(std::slice::from_ref(table));
+                self.joins(std::slice::from_ref(table));
+                for assignment in assignments {
+                    let target = match &assignment.target {
+                        AssignmentTarget::ColumnName(name) => self.resolve_column(&idents(name)),
+                        AssignmentTarget::Tuple(_) => None,
+                    };
+                    self.expr(&assignment.value, target.as_ref());
+                }
+                if let Some(selection) = selection {
+                    self.expr(selection, None);
+                }
+                if let Some(limit) = limit {
+                    self.expr(limit, Some(&count_column("limit")));
+                }
+                if let Some(returning) = returning {
+                    query.columns = self.projection(returning);
+                }
+            }
+            Statement::Delete(Delete {
+                from,
+                using,
+                selection,
+                returning,
+                order_by,
+                limit,
+                ..
+            }) => {
+                let (FromTable::WithFromKeyword(tables) | FromTable::WithoutKeyword(tables)) = from;
+                self.add_sources(tables);
+                self.joins(tables);
+                if let Some(using) = using {
+                    self.add_sources(using);
+                    self.joins(using);
+                }
+                if let Some(selection) = selection {
+                    self.expr(selection, None);
+                }
+                for order in order_by {
+                    self.expr(&order.expr, None);
+                }
+                if let Some(limit) = limit {
+                    self.expr(limit, Some(&count_column("limit")));
+                }
+                if let Some(returning) = returning {
+                    query.columns = self.projection(returning);
+                }
+            }
+            _ => {}
+        }
+
+        query.params = self
+            .params
+            .into_iter()
+            .map(|(number, column)| Parameter {
+                number,
+                column: Some(column),
+            })
+            .collect();
+    }
+
+    /// Analyze a query, returning its result columns
+    fn query(&mut self, query: &SqlQuery) -> Vec<Column> {
+        if let Some(with) = &query.with {
+            for cte in &with.cte_tables {
+                self.subquery(&cte.query);
+            }
+        }
+
+        let columns = self.set_expr(&query.body);
+
+        if let Some(OrderBy {
+            kind: OrderByKind::Expressions(exprs),
+            ..
+        }) = &query.order_by
+        {
+            for order in exprs {
+                self.expr(&order.expr, None);
+            }
+        }
+
+        match &query.limit_clause {
+            Some(LimitClause::LimitOffset { limit, offset, .. }) => {
+                if let Some(limit) = limit {
+                    self.expr(limit, Some(&count_column("limit")));
+                }
+                if let Some(offset) = offset {
+                    self.expr(&offset.value, Some(&count_column("offset")));
+                }
+            }
+            Some(LimitClause::OffsetCommaLimit { offset, limit }) => {
+                self.expr(offset, Some(&count_column("offset")));
+                self.expr(limit, Some(&count_column("limit")));
+            }
+            None => {}
+        }
+
+        columns
+    }
+
+    /// Analyze a subquery in its own scope, returning its result columns
+    ///
+    /// The sources of the enclosing queries stay visible, for correlated
+    /// subqueries.
+    fn subquery(&mut self, query: &SqlQuery) -> Vec<Column> {
+        let outer = std::mem::take(&mut self.sources);
+        self.scopes.push(outer);
+        let columns = self.query(query);
+        self.sources = self.scopes.pop().unwrap_or_default();
+        columns
+    }
+
+    /// Analyze the body of a query, returning its result columns
+    fn set_expr(&mut self, body: &SetExpr) -> Vec<Column> {
+        match body {
+            SetExpr::Select(select) => self.select(select),
+            SetExpr::Query(query) => self.subquery(query),
+            SetExpr::SetOperation { left, right, .. } => {
+                let columns = self.scoped_set_expr(left);
+                self.scoped_set_expr(right);
+                columns
+            }
+            SetExpr::Values(values) => {
+                for row in &values.rows {
+                    for expr in row {
+                        self.expr(expr, None);
+                    }
+                }
+                Vec::new()
+            }
+            other => {
+                self.untyped(other);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Analyze one side of a set operation, without leaking its sources
+    fn scoped_set_expr(&mut self, body: &SetExpr) -> Vec<Column> {
+        let outer = std::mem::take(&mut self.sources);
+        self.scopes.push(outer);
+        let columns = self.set_expr(body);
+        self.sources = self.scopes.pop().unwrap_or_default();
+        columns
+    }
+
+    fn select(&mut self, select: &Select) -> Vec<Column> {
+        self.add_sources(&select.from);
+        let columns = self.projection(&select.projection);
+        self.joins(&select.from);
+        if let Some(selection) = &select.selection {
+            self.expr(selection, None);
+        }
+        if let GroupByExpr::Expressions(exprs, _) = &select.group_by {
+            for expr in exprs {
+                self.expr(expr, None);
+            }
+        }
+        if let Some(having) = &select.having {
+            self.expr(having, None);
+        }
+        if let Some(qualify) = &select.qualify {
+            self.expr(qualify, None);
+        }
+        columns
+    }
+
+    fn insert(&mut self, insert: &Insert, query: &mut Query) {
+        let TableObject::TableName(name) = &insert.table else {
+            return;
+        };
+        let Some(table) = self.resolve_table(name) else {
+            return;
+        };
+
+        query.insert_into_table = table.rel.clone();
+        self.sources.push(Source { alias: None, table });
+
+        let targets: Vec<Option<Column>> = if insert.columns.is_empty() {
+            table
+                .columns
+                .iter()
+                .map(|column| Some(table_column(table, column)))
+                .collect()
+        } else {
+            insert
+                .columns
+                .iter()
+                .map(|ident| self.resolve_column(std::slice::from_ref(ident)))
+                .collect()
+        };
+
+        if let Some(source) = &insert.source {
+            match source.body.as_ref() {
+                SetExpr::Values(values) => {
+                    for row in &values.rows {
+                        for (index, expr) in row.iter().enumerate() {
+                            self.expr(expr, targets.get(index).and_then(Option::as_ref));
+                        }
+                    }
+                }
+                _ => {
+                    self.subquery(source);
+                }
+            }
+        }
+
+        if let Some(returning) = &insert.returning {
+            query.columns = self.projection(returning);
+        }
+    }
+
+    /// Register the tables of a `FROM` clause
+    fn add_sources(&mut self, tables: &[TableWithJoins]) {
+        let factors = tables.iter().flat_map(|table| {
+            std::iter::once(&table.relation).chain(table.joins.iter().map(|join| &join.relation))
+        });
+
+        for factor in factors {
+            match factor {
+                TableFactor::Table { name, alias, .. } => {
+                    if let Some(table) = self.resolve_table(name) {
+                        self.sources.push(Source {
+                            alias: alias.as_ref().map(|alias| alias.name.value.clone()),
+                            table,
+                        });
+                    }
+                }
+                TableFactor::NestedJoin {
+                    table_with_joins, ..
+                } => self.add_sources(std::slice::from_ref(table_with_joins)),
+                _ => {}
+            }
+        }
+    }
+
+    /// Collect the placeholders of a `FROM` clause: derived tables and join
+    /// constraints
+    fn joins(&mut self, tables: &[TableWithJoins]) {
+        for table in tables {
+            self.table_factor(&table.relation);
+            for join in &table.joins {
+                self.table_factor(&join.relation);
+                match &join.join_operator {
+                    JoinOperator::AsOf {
+                        match_condition,
+                        constraint,
+                    } => {
+                        self.expr(match_condition, None);
+                        self.join_constraint(constraint);
+                    }
+                    JoinOperator::Join(constraint)
+                    | JoinOperator::Inner(constraint)
+                    | JoinOperator::Left(constraint)
+                    | JoinOperator::LeftOuter(constraint)
+                    | JoinOperator::Right(constraint)
+                    | JoinOperator::RightOuter(constraint)
+                    | JoinOperator::FullOuter(constraint)
+                    | JoinOperator::CrossJoin(constraint)
+                    | JoinOperator::Semi(constraint)
+                    | JoinOperator::LeftSemi(constraint)
+                    | JoinOperator::RightSemi(constraint)
+                    | JoinOperator::Anti(constraint)
+                    | JoinOperator::LeftAnti(constraint)
+                    | JoinOperator::RightAnti(constraint)
+                    | JoinOperator::StraightJoin(constraint) => self.join_constraint(constraint),
+                    JoinOperator::CrossApply | JoinOperator::OuterApply => {}
+                }
+            }
+        }
+    }
+
+    fn table_factor(&mut self, factor: &TableFactor) {
+        match factor {
+            TableFactor::Table { .. } => {}
+            TableFactor::Derived { subquery, .. } => {
+                self.subquery(subquery);
+            }
+            TableFactor::NestedJoin {
+                table_with_joins, ..
+            } => self.joins(std::slice::from_ref(table_with_joins)),
+            other => self.untyped(other),
+        }
+    }
+
+    fn join_constraint(&mut self, constraint: &JoinConstraint) {
+        if let JoinConstraint::On(expr) = constraint {
+            self.expr(expr, None);
+        }
+    }
+
+    /// Find a table of the catalog, preferring the default schema for unqualified names
+    fn resolve_table(&self, name: &ObjectName) -> Option<&'a Table> {
+        let parts = idents(name);
+        let (schema, table) = match parts.as_slice() {
+            [.., schema, table] => (Some(schema.value.as_str()), table.value.as_str()),
+            [table] => (None, table.value.as_str()),
+            [] => return None,
+        };
+
+        let catalog: &'a Catalog = self.catalog;
+        let mut candidates = catalog.schemas.iter().flat_map(|s| {
+            s.tables
+                .iter()
+                .filter(|t| {
+                    t.rel
+                        .as_ref()
+                        .is_some_and(|rel| rel.name.eq_ignore_ascii_case(table))
+                })
+                .map(move |t| (s.name.as_str(), t))
+        });
+
+        match schema {
+            Some(schema) => candidates
+                .find(|(name, _)| name.eq_ignore_ascii_case(schema))
+                .map(|(_, t)| t),
+            None => {
+                let candidates: Vec<_> = candidates.collect();
+                candidates
+                    .iter()
+                    .find(|(name, _)| *name == catalog.default_schema)
+                    .or_else(|| candidates.first())
+                    .map(|(_, t)| *t)
+            }
+        }
+    }
+
+    /// Find the column referenced by `parts` (`column` or `table.column`)
+    fn resolve_column(&self, parts: &[Ident]) -> Option<Column> {
+        let (qualifier, name) = match parts {
+            [.., qualifier, name] => (Some(qualifier.value.as_str()), name.value.as_str()),
+            [name] => (None, name.value.as_str()),
+            [] => return None,
+        };
+
+        let scopes = self.scopes.iter().rev().flatten();
+        self.sources
+            .iter()
+            .chain(scopes)
+            .filter(|source| qualifier.is_none_or(|q| source_named(source, q)))
+            .find_map(|source| {
+                let column = source
+                    .table
+                    .columns
+                    .iter()
+                    .find(|c| c.name.eq_ignore_ascii_case(name))?;
+                let mut column = table_column(source.table, column);
+                if let Some(alias) = &source.alias {
+                    column.table_alias = alias.clone();
+                }
+                Some(column)
+            })
+    }
+
+    /// Result columns of a projection or `RETURNING` list
+    fn projection(&mut self, items: &[SelectItem]) -> Vec<Column> {
+        let mut columns = Vec::new();
+
+        for item in items {
+            match item {
+                SelectItem::Wildcard(_) => {
+                    for source in &self.sources {
+                        columns.extend(source_columns(source));
+                    }
+                }
+                SelectItem::QualifiedWildcard(
+                    SelectItemQualifiedWildcardKind::ObjectName(name),
+                    _,
+                ) => {
+                    let qualifier = idents(name).last().map(|ident| ident.value.clone());
+                    if let Some(source) = self.sources.iter().find(|source| {
+                        qualifier
+                            .as_deref()
+                            .is_some_and(|q| source_named(source, q))
+                    }) {
+                        columns.extend(source_columns(source));
+                    }
+                }
+                SelectItem::QualifiedWildcard(SelectItemQualifiedWildcardKind::Expr(_), _) => {}
+                SelectItem::UnnamedExpr(expr) => {
+                    self.expr(expr, None);
+                    columns.push(self.expr_column(expr, None, columns.len() + 1));
+                }
+                SelectItem::ExprWithAlias { expr, alias } => {
+                    self.expr(expr, None);
+                    columns.push(self.expr_column(expr, Some(&alias.value), columns.len() + 1));
+                }
+            }
+        }
+
+        columns
+    }
+
+    /// Result column of a projected expression
+    fn expr_column(&self, expr: &Expr, alias: Option<&str>, position: usize) -> Column {
+        let mut column = match expr {
+            Expr::Identifier(ident) => self
+                .resolve_column(std::slice::from_ref(ident))
+                .unwrap_or_else(|| any_column(&ident.value)),
+            Expr::CompoundIdentifier(parts) => self.resolve_column(parts).unwrap_or_else(|| {
+                any_column(parts.last().map(|p| p.value.as_str()).unwrap_or_default())
+            }),
+            Expr::Nested(inner) => self.expr_column(inner, None, position),
+            Expr::Cast {
+                expr, data_type, ..
+            } => {
+                let mut column = self.expr_column(expr, None, position);
+                column.r#type = Some(type_identifier(&data_type.to_string()));
+                column
+            }
+            Expr::Function(function) => {
+                let name = idents(&function.name)
+                    .last()
+                    .map(|ident| ident.value.to_lowercase())
+                    .unwrap_or_default();
+                let mut column = if name == "count" {
+                    count_column("count")
+                } else {
+                    any_column(&name)
+                };
+                column.is_func_call = true;
+                column
+            }
+            _ => any_column(&format!("column_{position}")),
+        };
+
+        if let Some(alias) = alias {
+            column.name = alias.to_string();
+        }
+        column
+    }
+
+    /// Collect the placeholders of an expression
+    ///
+    /// `context` is the column a placeholder found directly in `expr` is
+    /// compared to or assigned to, and gives the parameter its type.
+    fn expr(&mut self, expr: &Expr, context: Option<&Column>) {
+        match expr {
+            Expr::Value(value) => {
+                if let Value::Placeholder(placeholder) = &value.value {
+                    self.placeholder(placeholder, context);
+                }
+            }
+            Expr::BinaryOp { left, right, .. }
+            | Expr::IsDistinctFrom(left, right)
+            | Expr::IsNotDistinctFrom(left, right) => self.compare(left, right, context),
+            Expr::AnyOp { left, right, .. } | Expr::AllOp { left, right, .. } => {
+                // `col = ANY($1)` compares to each element of an array
+                let element = self.column_of(left);
+                self.expr(left, None);
+                let array = element.map(|column| Column {
+                    is_array: true,
+                    array_dims: 1,
+                    ..column
+                });
+                self.expr(right, array.as_ref());
+            }
+            Expr::Nested(inner)
+            | Expr::UnaryOp { expr: inner, .. }
+            | Expr::Collate { expr: inner, .. } => self.expr(inner, context),
+            Expr::IsNull(inner)
+            | Expr::IsNotNull(inner)
+            | Expr::IsTrue(inner)
+            | Expr::IsNotTrue(inner)
+            | Expr::IsFalse(inner)
+            | Expr::IsNotFalse(inner)
+            | Expr::IsUnknown(inner)
+            | Expr::IsNotUnknown(inner) => self.expr(inner, None),
+            Expr::Cast {
+                expr, data_type, ..
+            } => {
+                let mut column = context.cloned().unwrap_or_else(|| any_column(""));
+                column.r#type = Some(type_identifier(&data_type.to_string()));
+                self.expr(expr, Some(&column));
+            }
+            Expr::InList { expr, list, .. } => {
+                let column = self.column_of(expr);
+                self.expr(expr, None);
+                for item in list {
+                    self.expr(item, column.as_ref());
+                }
+            }
+            Expr::Between {
+                expr, low, high, ..
+            } => {
+                let column = self.column_of(expr);
+                self.expr(expr, None);
+                self.expr(low, column.as_ref());
+                self.expr(high, column.as_ref());
+            }
+            Expr::Like { expr, pattern, .. }
+            | Expr::ILike { expr, pattern, .. }
+            | Expr::SimilarTo { expr, pattern, .. }
+            | Expr::RLike { expr, pattern, .. } => {
+                let column = self.column_of(expr);
+                self.expr(expr, None);
+                self.expr(pattern, column.as_ref());
+            }
+            Expr::InSubquery { expr, subquery, .. } => {
+                self.expr(expr, None);
+                self.subquery(subquery);
+            }
+            Expr::Exists { subquery, .. } | Expr::Subquery(subquery) => {
+                self.subquery(subquery);
+            }
+            Expr::Case {
+                operand,
+                conditions,
+                else_result,
+                ..
+            } => {
+                // `CASE col WHEN $1` compares the placeholder to `col`, and
+                // the results take the type the whole expression is used as
+                let operand_column = operand.as_deref().and_then(|op| self.column_of(op));
+                if let Some(operand) = operand {
+                    self.expr(operand, None);
+                }
+                for when in conditions {
+                    self.expr(&when.condition, operand_column.as_ref());
+                    self.expr(&when.result, context);
+                }
+                if let Some(else_result) = else_result {
+                    self.expr(else_result, context);
+                }
+            }
+            Expr::Tuple(items) => {
+                for item in items {
+                    self.expr(item, None);
+                }
+            }
+            Expr::Function(function) => {
+                self.untyped(&function.parameters);
+                match &function.args {
+                    FunctionArguments::List(list) => {
+                        for arg in &list.args {
+                            let (FunctionArg::Unnamed(arg)
+                            | FunctionArg::Named { arg, .. }
+                            | FunctionArg::ExprNamed { arg, .. }) = arg;
+                            match arg {
+                                FunctionArgExpr::Expr(arg) => self.expr(arg, None),
+                                other => self.untyped(other),
+                            }
+                        }
+                        self.untyped(&list.clauses);
+                    }
+                    FunctionArguments::Subquery(subquery) => {
+                        self.subquery(subquery);
+                    }
+                    FunctionArguments::None => {}
+                }
+                self.untyped(&function.within_group);
+                if let Some(filter) = &function.filter {
+                    self.expr(filter, None);
+                }
+                self.untyped(&function.over);
+            }
+            other => self.untyped(other),
+        }
+    }
+
+    /// Collect the placeholders of a comparison, typing each side after the
+    /// other
+    fn compare(&mut self, left: &Expr, right: &Expr, context: Option<&Column>) {
+        if let (Expr::Tuple(left), Expr::Tuple(right)) = (left, right) {
+            if left.len() == right.len() {
+                // `(a, b) = ($1, $2)` compares element by element
+                for (left, right) in left.iter().zip(right) {
+                    self.compare(left, right, None);
+                }
+                return;
+            }
+        }
+
+        let left_column = self.column_of(left);
+        let right_column = self.column_of(right);
+        self.expr(left, right_column.as_ref().or(context));
+        self.expr(right, left_column.as_ref().or(context));
+    }
+
+    /// Record the placeholders of a node the analyzer does not understand,
+    /// in order and without a type
+    fn untyped<V: Visit>(&mut self, node: &V) {
+        let _ = visit_expressions(node, |expr| {
+            if let Expr::Value(value) = expr {
+                if let Value::Placeholder(placeholder) = &value.value {
+                    self.placeholder(placeholder, None);
+                }
+            }
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    /// Record a parameter for a `$N` or `?` placeholder
+    fn placeholder(&mut self, placeholder: &str, context: Option<&Column>) {
+        let number = match placeholder.strip_prefix('$') {
+            Some(number) => match number.parse() {
+                Ok(number) => number,
+                Err(_) => return,
+            },
+            None if placeholder == "?" => {
+                let number = self.next_positional;
+                self.next_positional += 1;
+                number
+            }
+            None => return,
+        };
+
+        // A placeholder used several times keeps the first type it was given
+        let column = context.cloned().unwrap_or_else(|| any_column(""));
+        let replace = match self.params.get(&number) {
+            Some(existing) => is_any(existing) && !is_any(&column),
+            None => true,
+        };
+        if replace {
+            self.params.insert(number, column);
+        }
+    }
+
+    /// Column referenced by an expression, if it is a plain column reference
+    fn column_of(&self, expr: &Expr) -> Option<Column> {
+        match expr {
+            Expr::Identifier(ident) => self.resolve_column(std::slice::from_ref(ident)),
+            Expr::CompoundIdentifier(parts) => self.resolve_column(parts),
+            Expr::Nested(inner) => self.column_of(inner),
+            _ => None,
+        }
+    }
+}
+
+/// Identifier parts of an object name
+fn idents(name: &ObjectName) -> Vec<Ident> {
+    name.0
+        .iter()
+        .filter_map(|part| part.as_ident().cloned())
+        .collect()
+}
+
+/// Check whether a source is referenced as `qualifier`
+fn source_named(source: &Source, qualifier: &str) -> bool {
+    match &source.alias {
+        Some(alias) => alias.eq_ignore_ascii_case(qualifier),
+        None => source
+            .table
+            .rel
+            .as_ref()
+            .is_some_and(|rel| rel.name.eq_ignore_ascii_case(qualifier)),
+    }
+}
+
+/// All columns of a source, as result columns
+fn source_columns(source: &Source) -> Vec<Column> {
+    source
+        .table
+        .columns
+        .iter()
+        .map(|column| {
+            let mut column = table_column(source.table, column);
+            if let Some(alias) = &source.alias {
+                column.table_alias = alias.clone();
+            }
+            column
+        })
+        .collect()
+}
+
+/// A catalog column, annotated with the table it belongs to
+fn table_column(table: &Table, column: &Column) -> Column {
+    Column {
+        table: table.rel.clone(),
+        original_name: column.name.clone(),
+        ..column.clone()
+    }
+}
+
+/// Identifier of an unqualified type
+fn type_identifier(name: &str) -> Identifier {
+    Identifier {
+        name: name.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Check whether a column has an unknown type
+fn is_any(column: &Column) -> bool {
+    column.r#type.as_ref().is_none_or(|ty| ty.name == "any")
+}
+
+/// Column of unknown type
+fn any_column(name: &str) -> Column {
+    Column {
+        name: name.to_string(),
+        r#type: Some(type_identifier("any")),
+        ..Default::default()
+    }
+}
+
+/// Non-null integer column, as used for `COUNT(*)`, `LIMIT` and `OFFSET`
+fn count_column(name: &str) -> Column {
+    Column {
+        name: name.to_string(),
+        not_null: true,
+        r#type: Some(type_identifier("bigint")),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::MemoryFileSystem;
+
+    const SCHEMA: &str = "
+        CREATE TABLE users (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            email TEXT
+        );
+        CREATE TABLE posts (
+            id INTEGER PRIMARY KEY,
+            user_id INTEGER NOT NULL REFERENCES users(id),
+            title TEXT NOT NULL
+        );
+    ";
+
+    fn generate(engine: &str, queries: &str) -> Result<GenerateRequest, Error> {
+        let fs = MemoryFileSystem::new()
+            .with_file("schema.sql", SCHEMA)
+            .with_file("query.sql", queries);
+
+        Frontend::new(engine)
+            .schema("schema.sql")
+            .queries("query.sql")
+            .with_options(RunOptions::default().with_file_system(fs))
+            .generate()
+    }
+
+    fn query(engine: &str, sql: &str) -> Query {
+        generate(engine, sql).unwrap().queries.remove(0)
+    }
+
+    fn names(columns: &[Column]) -> Vec<&str> {
+        columns.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    fn param_types(query: &Query) -> Vec<(i32, String, String)> {
+        query
+            .params
+            .iter()
+            .map(|p| {
+                let column = p.column.as_ref().unwrap();
+                (
+                    p.number,
+                    column.name.clone(),
+                    column.r#type.as_ref().unwrap().name.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_queries() {
+        let sql = "\
+-- Leading comments are ignored
+-- name: GetUser :one
+-- Get a user by id
+SELECT * FROM users
+WHERE id = $1;
+
+-- name: DeleteUser :exec
+DELETE FROM users WHERE id = $1;
+";
+        let queries = split_queries(Path::new("query.sql"), sql).unwrap();
+
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].name, "GetUser");
+        assert_eq!(queries[0].cmd, ":one");
+        assert_eq!(queries[0].comments, vec![" Get a user by id"]);
+        assert_eq!(queries[0].text, "SELECT * FROM users\nWHERE id = $1");
+        assert_eq!(queries[1].text, "DELETE FROM users WHERE id = $1");
+    }
+
+    #[test]
+    fn test_split_queries_errors() {
+        let path = Path::new("query.sql");
+
+        let err = split_queries(path, "SELECT 1;").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "query.sql:1: statement without a `-- name:` annotation"
+        );
+
+        let err = split_queries(path, "\n-- name: GetUser :first\nSELECT 1;").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "query.sql:2: unknown command `:first` for query GetUser"
+        );
+
+        let err = split_queries(path, "-- name: GetUser\nSELECT 1;").unwrap_err();
+        assert!(err.to_string().contains("missing command"));
+
+        let err = split_queries(
+            path,
+            "-- name: Empty :exec\n-- name: Other :exec\nSELECT 1;",
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "query.sql:1: query Empty has no SQL");
+    }
+
+    #[test]
+    fn test_generate_fills_request() {
+        let request = generate(
+            "postgresql",
+            "-- name: ListUsers :many\nSELECT * FROM users;",
+        )
+        .unwrap();
+
+        let settings = request.settings.as_ref().unwrap();
+        assert_eq!(settings.engine, "postgresql");
+        assert_eq!(settings.queries, vec!["query.sql"]);
+        assert_eq!(request.catalog.as_ref().unwrap().schemas[0].tables.len(), 2);
+
+        let query = &request.queries[0];
+        assert_eq!(query.filename, "query.sql");
+        assert_eq!(names(&query.columns), vec!["id", "name", "email"]);
+
+        let id = &query.columns[0];
+        assert!(id.not_null);
+        assert_eq!(id.table.as_ref().unwrap().name, "users");
+        assert!(query.params.is_empty());
+    }
+
+    #[test]
+    fn test_select_columns_and_params() {
+        let query = query(
+            "postgresql",
+            "-- name: ListPosts :many
+             SELECT p.title, u.name AS author, COUNT(*) AS total
+             FROM posts p JOIN users u ON u.id = p.user_id
+             WHERE u.email LIKE $1 AND p.id IN ($2, $3)
+             LIMIT $4 OFFSET $5;",
+        );
+
+        assert_eq!(names(&query.columns), vec!["title", "author", "total"]);
+        assert_eq!(query.columns[1].original_name, "name");
+        assert_eq!(query.columns[1].table_alias, "u");
+        assert!(query.columns[2].is_func_call);
+
+        assert_eq!(
+            param_types(&query),
+            vec![
+                (1, "email".to_string(), "TEXT".to_string()),
+                (2, "id".to_string(), "INTEGER".to_string()),
+                (3, "id".to_string(), "INTEGER".to_string()),
+                (4, "limit".to_string(), "bigint".to_string()),
+                (5, "offset".to_string(), "bigint".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repeated_placeholder() {
+        let query = query(
+            "postgresql",
+            "-- name: Search :many\nSELECT id FROM users WHERE name = $1 OR email = $1;",
+        );
+
+        assert_eq!(
+            param_types(&query),
+            vec![(1, "name".to_string(), "TEXT".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_insert_returning() {
+        let query = query(
+            "postgresql",
+            "-- name: CreatePost :one\nINSERT INTO posts (user_id, title) VALUES ($1, $2) RETURNING *;",
+        );
+
+        assert_eq!(query.insert_into_table.as_ref().unwrap().name, "posts");
+        assert_eq!(
+            param_types(&query),
+            vec![
+                (1, "user_id".to_string(), "INTEGER".to_string()),
+                (2, "title".to_string(), "TEXT".to_string()),
+            ]
+        );
+        assert_eq!(names(&query.columns), vec!["id", "user_id", "title"]);
+    }
+
+    #[test]
+    fn test_update_and_delete_mysql_placeholders() {
+        let request = generate(
+            "mysql",
+            "-- name: RenameUser :exec\nUPDATE users SET name = ? WHERE id = ?;\n\
+             -- name: DeletePost :execrows\nDELETE FROM posts WHERE user_id = ? AND title = ?;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            param_types(&request.queries[0]),
+            vec![
+                (1, "name".to_string(), "TEXT".to_string()),
+                (2, "id".to_string(), "INTEGER".to_string()),
+            ]
+        );
+        assert_eq!(
+            param_types(&request.queries[1]),
+            vec![
+                (1, "user_id".to_string(), "INTEGER".to_string()),
+                (2, "title".to_string(), "TEXT".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_join_on_mysql_placeholders() {
+        let query = query(
+            "mysql",
+            "-- name: UserPosts :many\n\
+             SELECT p.title FROM users u JOIN posts p ON p.user_id = u.id AND p.title = ? \
+             WHERE u.id = ? GROUP BY p.title HAVING count(*) > ? ORDER BY p.title LIMIT ?;",
+        );
+
+        assert_eq!(
+            param_types(&query),
+            vec![
+                (1, "title".to_string(), "TEXT".to_string()),
+                (2, "id".to_string(), "INTEGER".to_string()),
+                (3, String::new(), "any".to_string()),
+                (4, "limit".to_string(), "bigint".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_any_and_subquery_params() {
+        let query = query(
+            "postgresql",
+            "-- name: ListUsers :many\n\
+             SELECT id FROM users WHERE id = ANY($1) \
+             AND id IN (SELECT user_id FROM posts WHERE title = $2) \
+             AND EXISTS (SELECT 1 FROM posts p WHERE p.user_id = users.id AND p.id > $3) \
+             AND email = (SELECT email FROM users WHERE name = $4);",
+        );
+
+        assert_eq!(
+            param_types(&query),
+            vec![
+                (1, "id".to_string(), "INTEGER".to_string()),
+                (2, "title".to_string(), "TEXT".to_string()),
+                (3, "id".to_string(), "INTEGER".to_string()),
+                (4, "name".to_string(), "TEXT".to_string()),
+            ]
+        );
+        let first = query.params[0].column.as_ref().unwrap();
+        assert!(first.is_array);
+        assert_eq!(first.array_dims, 1);
+        assert_eq!(names(&query.columns), vec!["id"]);
+    }
+
+    #[test]
+    fn test_case_and_fallback_params() {
+        let query = query(
+            "mysql",
+            "-- name: Labels :many\n\
+             SELECT CASE name WHEN ? THEN ? ELSE email END AS label, substring(name, ?) \
+             FROM users WHERE (id, name) = (?, ?);",
+        );
+
+        assert_eq!(
+            param_types(&query),
+            vec![
+                (1, "name".to_string(), "TEXT".to_string()),
+                (2, String::new(), "any".to_string()),
+                (3, String::new(), "any".to_string()),
+                (4, "id".to_string(), "INTEGER".to_string()),
+                (5, "name".to_string(), "TEXT".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_untyped_expressions() {
+        let query = query(
+            "postgresql",
+            "-- name: Misc :one\nSELECT lower(name), 1, $1::text AS label FROM users;",
+        );
+
+        assert_eq!(names(&query.columns), vec!["lower", "column_2", "label"]);
+        assert_eq!(query.columns[0].r#type, Some(type_identifier("any")));
+        assert_eq!(query.columns[2].r#type, Some(type_identifier("TEXT")));
+        assert_eq!(
+            param_types(&query),
+            vec![(1, String::new(), "TEXT".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_generate_with_composites() {
+        let fs = MemoryFileSystem::new()
+            .with_file(
+                "schema.sql",
+                "CREATE TYPE address AS (street text, city text);
+                 CREATE TABLE users (id int NOT NULL, home address);",
+            )
+            .with_file(
+                "query.sql",
+                "-- name: ListUsers :many\nSELECT home FROM users;",
+            );
+
+        let (request, attributes) = Frontend::new("postgresql")
+            .schema("schema.sql")
+            .queries("query.sql")
+            .with_options(RunOptions::default().with_file_system(fs))
+            .generate_with_composites()
+            .unwrap();
+
+        let home = &request.queries[0].columns[0];
+        let fields = attributes.resolve(home).unwrap();
+        assert_eq!(names(fields), ["street", "city"]);
+    }
+
+    #[test]
+    fn test_query_parse_error() {
+        let err = generate(
+            "postgresql",
+            "-- name: Broken :one\nSELECT id FROM users WHERE;",
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, Error::QueryParse { ref name, .. } if name == "Broken"));
+        assert!(err
+            .to_string()
+            .starts_with("failed to parse query Broken in query.sql"));
+    }
+
+    #[test]
+    fn test_missing_query_file() {
+        let err = Frontend::new("postgresql")
+            .queries("missing.sql")
+            .with_options(RunOptions::default().with_file_system(MemoryFileSystem::new()))
+            .generate()
+            .unwrap_err();
+
+        assert!(matches!(err, Error::QueryRead { .. }));
+    }
+}
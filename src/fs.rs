@@ -0,0 +1,348 @@
+//! File system access used to load schema files.
+//!
+//! The runtime reads `Settings.schema` entries through the [`FileSystem`]
+//! trait instead of calling `std::fs` directly, so plugins can run where file
+//! access is restricted (such as sqlc's WASM plugins) and can be tested with
+//! in-memory schemas.
+//!
+//! Three implementations are provided:
+//!
+//! - [`StdFileSystem`]: the host file system through `std::fs` (the default)
+//! - [`MemoryFileSystem`]: a fixed set of files kept in memory
+//! - [`DisabledFileSystem`]: refuses every access, for plugins that must not
+//!   touch the file system and only rely on the catalog sent by sqlc (the
+//!   runtime then skips `Settings.schema` entries instead of reading them)
+//!
+//! # Example
+//!
+//! ```
+//! use sqlc_gen_core::fs::MemoryFileSystem;
+//! use sqlc_gen_core::plugin::{GenerateRequest, GenerateResponse, Settings};
+//! use sqlc_gen_core::runtime::{run_with_options, RunOptions};
+//! use prost::Message;
+//!
+//! let fs = MemoryFileSystem::new().with_file("schema.sql", "CREATE TABLE users (id int);");
+//! let options = RunOptions::default().with_file_system(fs);
+//!
+//! let request = GenerateRequest {
+//!     settings: Some(Settings {
+//!         engine: "postgresql".to_string(),
+//!         schema: vec!["schema.sql".to_string()],
+//!         ..Default::default()
+//!     }),
+//!     ..Default::default()
+//! };
+//!
+//! let mut output = Vec::new();
+//! run_with_options(&request.encode_to_vec()[..], &mut output, &options, |request| {
+//!     assert_eq!(request.catalog.unwrap().schemas[0].tables.len(), 1);
+//!     Ok(GenerateResponse { files: vec![] })
+//! })
+//! .unwrap();
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Read-only file system operations needed to load schema files.
+///
+/// Errors are reported as [`io::Error`]s; a missing path must be reported
+/// with [`io::ErrorKind::NotFound`].
+pub trait FileSystem: fmt::Debug + Send + Sync {
+    /// Read the contents of a file as UTF-8
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Check whether `path` is a directory
+    ///
+    /// Returns `Ok(false)` for files and an error if the path does not exist.
+    fn is_dir(&self, path: &Path) -> io::Result<bool>;
+
+    /// List the files (not subdirectories) directly inside a directory
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// List the files matching a glob pattern
+    ///
+    /// Returns an [`io::ErrorKind::InvalidInput`] error if the pattern is
+    /// invalid. Matching no file is not an error.
+    fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>>;
+
+    /// Resolve `path` to the location it actually refers to
+    ///
+    /// File systems with symbolic links resolve them, so that the loader can
+    /// check that a schema file really lies inside its base directory. The
+    /// default returns the path unchanged, for file systems without links.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+
+    /// Check whether files can be read at all
+    ///
+    /// When `false`, the runtime passes the catalog sent by sqlc through
+    /// unchanged instead of loading `Settings.schema` entries.
+    fn is_enabled(&self) -> bool {
+        true
+    }
+}
+
+/// The host file system, accessed through `std::fs`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StdFileSystem;
+
+impl FileSystem for StdFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> io::Result<bool> {
+        Ok(std::fs::metadata(path)?.is_dir())
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push(entry.path());
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>> {
+        let paths = glob::glob(pattern)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+
+        let mut files = Vec::new();
+        for path in paths {
+            let path = path.map_err(|err| {
+                let message = format!("{}: {}", err.path().display(), err.error());
+                io::Error::new(err.error().kind(), message)
+            })?;
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        std::fs::canonicalize(path)
+    }
+}
+
+/// A file system holding a fixed set of files in memory.
+///
+/// Directories are implied by the paths of the files they contain. Paths are
+/// compared as given, so a loader with a base directory needs files inserted
+/// under that directory.
+///
+/// # Example
+///
+/// ```
+/// use sqlc_gen_core::fs::{FileSystem, MemoryFileSystem};
+/// use std::path::Path;
+///
+/// let fs = MemoryFileSystem::new()
+///     .with_file("migrations/1_users.sql", "CREATE TABLE users (id int);")
+///     .with_file("migrations/2_posts.sql", "CREATE TABLE posts (id int);");
+///
+/// assert!(fs.is_dir(Path::new("migrations")).unwrap());
+/// assert_eq!(fs.glob("migrations/*.sql").unwrap().len(), 2);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryFileSystem {
+    /// File contents by path
+    pub files: BTreeMap<PathBuf, String>,
+}
+
+impl MemoryFileSystem {
+    /// Create an empty file system
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file, replacing any previous contents
+    pub fn with_file(mut self, path: impl Into<PathBuf>, contents: impl Into<String>) -> Self {
+        self.insert(path, contents);
+        self
+    }
+
+    /// Add a file, replacing any previous contents
+    pub fn insert(&mut self, path: impl Into<PathBuf>, contents: impl Into<String>) {
+        self.files.insert(path.into(), contents.into());
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.files.get(path).cloned().ok_or_else(|| not_found(path))
+    }
+
+    fn is_dir(&self, path: &Path) -> io::Result<bool> {
+        if self.files.contains_key(path) {
+            Ok(false)
+        } else if self.files.keys().any(|file| file.starts_with(path)) {
+            Ok(true)
+        } else {
+            Err(not_found(path))
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        if !self.is_dir(path)? {
+            return Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("{} is not a directory", path.display()),
+            ));
+        }
+
+        Ok(self
+            .files
+            .keys()
+            .filter(|file| file.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+
+    fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>> {
+        let pattern = glob::Pattern::new(pattern)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+
+        // Match like `glob::glob`, where wildcards never cross a separator
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..glob::MatchOptions::new()
+        };
+
+        Ok(self
+            .files
+            .keys()
+            .filter(|file| pattern.matches_path_with(file, options))
+            .cloned()
+            .collect())
+    }
+}
+
+/// A file system refusing every access.
+///
+/// The runtime does not load `Settings.schema` entries through a disabled file
+/// system and keeps the catalog sent by sqlc. Direct reads, such as with a
+/// [`SchemaLoader`](crate::loader::SchemaLoader), fail with an
+/// [`io::ErrorKind::Unsupported`] error instead of reading the host file system.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DisabledFileSystem;
+
+impl DisabledFileSystem {
+    fn refuse(path: &Path) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("file system access is disabled: {}", path.display()),
+        )
+    }
+}
+
+impl FileSystem for DisabledFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        Err(Self::refuse(path))
+    }
+
+    fn is_dir(&self, path: &Path) -> io::Result<bool> {
+        Err(Self::refuse(path))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        Err(Self::refuse(path))
+    }
+
+    fn glob(&self, pattern: &str) -> io::Result<Vec<PathBuf>> {
+        Err(Self::refuse(Path::new(pattern)))
+    }
+
+    fn is_enabled(&self) -> bool {
+        false
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} not found", path.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> MemoryFileSystem {
+        MemoryFileSystem::new()
+            .with_file("schema.sql", "CREATE TABLE a (id int);")
+            .with_file("db/1_users.sql", "CREATE TABLE users (id int);")
+            .with_file("db/nested/2_posts.sql", "CREATE TABLE posts (id int);")
+    }
+
+    #[test]
+    fn test_memory_read_to_string() {
+        let fs = sample();
+        assert_eq!(
+            fs.read_to_string(Path::new("schema.sql")).unwrap(),
+            "CREATE TABLE a (id int);"
+        );
+
+        let err = fs.read_to_string(Path::new("missing.sql")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_memory_is_dir() {
+        let fs = sample();
+        assert!(fs.is_dir(Path::new("db")).unwrap());
+        assert!(fs.is_dir(Path::new("db/nested")).unwrap());
+        assert!(!fs.is_dir(Path::new("schema.sql")).unwrap());
+        assert_eq!(
+            fs.is_dir(Path::new("missing")).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_memory_read_dir_lists_direct_files() {
+        let fs = sample();
+        assert_eq!(
+            fs.read_dir(Path::new("db")).unwrap(),
+            vec![PathBuf::from("db/1_users.sql")]
+        );
+
+        let err = fs.read_dir(Path::new("schema.sql")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotADirectory);
+    }
+
+    #[test]
+    fn test_memory_glob_does_not_cross_separators() {
+        let fs = sample();
+        assert_eq!(
+            fs.glob("db/*.sql").unwrap(),
+            vec![PathBuf::from("db/1_users.sql")]
+        );
+        assert_eq!(fs.glob("db/*/*.sql").unwrap().len(), 1);
+        assert!(fs.glob("other/*.sql").unwrap().is_empty());
+
+        let err = fs.glob("db/[.sql").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_disabled_refuses_access() {
+        let fs = DisabledFileSystem;
+        let err = fs.read_to_string(Path::new("schema.sql")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(
+            fs.glob("*.sql").unwrap_err().kind(),
+            io::ErrorKind::Unsupported
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! gRPC server mode for sqlc plugins.
+//!
+//! `proto/codegen.proto` declares a `CodegenService` with a single `Generate`
+//! RPC taking the same [`GenerateRequest`] sqlc writes to a process plugin's
+//! stdin. With the `grpc` feature enabled, this module serves a plugin handler
+//! over that service on a local TCP or Unix socket, so a long-running plugin
+//! can keep warm caches between generations and other tools (editors, test
+//! harnesses) can call it directly.
+//!
+//! Requests go through the same schema enrichment as [`crate::runtime::run_with_io`]
+//! before reaching the handler, configured by a [`RunOptions`].
+//!
+//! Any local client can call the service, so schema entries are always
+//! resolved against a base directory (the current directory unless
+//! [`RunOptions::base_dir`] is set) and entries leaving it, including absolute
+//! paths, are refused. Error statuses never include the contents of schema
+//! files.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sqlc_gen_core::grpc::{serve_tcp, Service};
+//! use sqlc_gen_core::plugin::GenerateResponse;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let service = Service::new(|request| {
+//!     // Your code generation logic here
+//!     Ok(GenerateResponse { files: vec![] })
+//! });
+//!
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:50051").await?;
+//! serve_tcp(listener, service).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::plugin::{GenerateRequest, GenerateResponse};
+use crate::runtime::{finish_response, prepare_request, Error, RunOptions};
+use std::error::Error as StdError;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+
+/// Service stubs generated from `proto/codegen.proto` by `build.rs`
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/plugin.rs"));
+}
+
+pub use proto::codegen_service_client::CodegenServiceClient;
+pub use proto::codegen_service_server::{CodegenService, CodegenServiceServer};
+
+/// Processing function shared between concurrent `Generate` calls
+type Handler = dyn Fn(GenerateRequest) -> Result<GenerateResponse, Box<dyn StdError>> + Send + Sync;
+
+/// `CodegenService` implementation backed by a plugin handler.
+///
+/// The handler is called on tokio's blocking thread pool, so it may perform
+/// synchronous work without stalling the server. Calls can run concurrently;
+/// state kept between generations must be synchronized by the handler.
+#[derive(Clone)]
+pub struct Service {
+    handler: Arc<Handler>,
+    options: RunOptions,
+}
+
+impl Service {
+    /// Create a service calling `handler` for every `Generate` request
+    ///
+    /// Schema entries are resolved against the current directory.
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: Fn(GenerateRequest) -> Result<GenerateResponse, Box<dyn StdError>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        Self {
+            handler: Arc::new(handler),
+            options: RunOptions::default().with_base_dir(current_dir()),
+        }
+    }
+
+    /// Load schema files according to `options`
+    ///
+    /// Without a [`base_dir`](RunOptions::base_dir), schema entries are
+    /// resolved against the current directory.
+    pub fn with_options(mut self, mut options: RunOptions) -> Self {
+        if options.base_dir.is_none() {
+            options.base_dir = Some(current_dir());
+        }
+        self.options = options;
+        self
+    }
+
+    /// Wrap the service into a tonic server, e.g. for use with a custom router
+    pub fn into_server(self) -> CodegenServiceServer<Self> {
+        CodegenServiceServer::new(self)
+    }
+}
+
+#[tonic::async_trait]
+impl CodegenService for Service {
+    async fn generate(
+        &self,
+        request: Request<GenerateRequest>,
+    ) -> Result<Response<GenerateResponse>, Status> {
+        let handler = Arc::clone(&self.handler);
+        let options = self.options.clone();
+        let request = request.into_inner();
+        check_schema_entries(&request)?;
+
+        let response = tokio::task::spawn_blocking(move || {
+            options.log.scope(|| {
+                let (request, attributes) =
+                    prepare_request(request, &options).map_err(to_status)?;
+                let response = attributes
+                    .scope(|| handler(request))
+                    .map_err(|err| to_status(Error::Processor(err)))?;
+                finish_response(response, &options).map_err(to_status)
+            })
+        })
+        .await
+        .map_err(|err| Status::internal(format!("plugin handler failed: {err}")))??;
+
+        Ok(Response::new(response))
+    }
+}
+
+/// Refuse absolute schema entries, which sqlc never sends
+fn check_schema_entries(request: &GenerateRequest) -> Result<(), Status> {
+    let mut entries = request.settings.iter().flat_map(|s| &s.schema);
+    match entries.find(|entry| Path::new(entry).is_absolute()) {
+        Some(entry) => Err(Status::invalid_argument(format!(
+            "schema entry {entry} must be relative to the base directory"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Directory schema entries are resolved against by default
+fn current_dir() -> PathBuf {
+    std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+}
+
+/// Map a runtime error to the gRPC status reported to the caller
+///
+/// Parse errors only report the location of the failure: the statement text
+/// and the parser message both quote the schema file.
+fn to_status(err: Error) -> Status {
+    let message = match &err {
+        Error::SqlParse {
+            path,
+            source: crate::schema::Error::Parse { line, column, .. },
+        } => format!(
+            "failed to parse schema file {} at line {line}, column {column}",
+            path.display()
+        ),
+        Error::SqlParse { path, .. } => {
+            format!("failed to parse schema file {}", path.display())
+        }
+        _ => err.to_string(),
+    };
+    match err {
+        Error::Decode(_)
+        | Error::SchemaRead { .. }
+        | Error::SchemaPath { .. }
+        | Error::SqlParse { .. }
+        | Error::Options(_) => Status::invalid_argument(message),
+        Error::Io(_)
+        | Error::Processor(_)
+        | Error::InvalidPaths(_)
+        | Error::Encode(_)
+        | Error::Capture { .. }
+        | Error::Panic { .. } => Status::internal(message),
+    }
+}
+
+/// Serve `service` on a bound TCP listener until the server fails.
+///
+/// # Errors
+///
+/// Returns an error if the underlying transport fails.
+pub async fn serve_tcp(
+    listener: tokio::net::TcpListener,
+    service: Service,
+) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(service.into_server())
+        .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
+        .await
+}
+
+/// Serve `service` on a bound Unix domain socket until the server fails.
+///
+/// # Errors
+///
+/// Returns an error if the underlying transport fails.
+#[cfg(unix)]
+pub async fn serve_unix(
+    listener: tokio::net::UnixListener,
+    service: Service,
+) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(service.into_server())
+        .serve_with_incoming(tokio_stream::wrappers::UnixListenerStream::new(listener))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::{File, Settings};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    /// Start `service` on a random local port and return a connected client
+    async fn start(service: Service) -> CodegenServiceClient<tonic::transport::Channel> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_tcp(listener, service));
+
+        CodegenServiceClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_generate_over_tcp() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+
+        let service = Service::new(move |request| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(GenerateResponse {
+                files: vec![File {
+                    name: "version.txt".to_string(),
+                    contents: request.sqlc_version.into_bytes(),
+                }],
+            })
+        });
+
+        runtime().block_on(async {
+            let mut client = start(service).await;
+
+            for version in ["1.0.0", "1.1.0"] {
+                let response = client
+                    .generate(GenerateRequest {
+                        sqlc_version: version.to_string(),
+                        ..Default::default()
+                    })
+                    .await
+                    .unwrap()
+                    .into_inner();
+
+                assert_eq!(response.files[0].contents, version.as_bytes());
+            }
+        });
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_generate_enriches_catalog() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("schema.sql"),
+            "CREATE TABLE users (id int);",
+        )
+        .unwrap();
+
+        let service = Service::new(|request| {
+            let catalog = request.catalog.ok_or("missing catalog")?;
+            let tables = catalog
+                .schemas
+                .iter()
+                .map(|s| s.tables.len())
+                .sum::<usize>();
+            Ok(GenerateResponse {
+                files: vec![File {
+                    name: "tables.txt".to_string(),
+                    contents: tables.to_string().into_bytes(),
+                }],
+            })
+        })
+        .with_options(RunOptions::default().with_base_dir(dir.path()));
+
+        runtime().block_on(async {
+            let mut client = start(service).await;
+            let response = client
+                .generate(GenerateRequest {
+                    settings: Some(Settings {
+                        engine: "postgresql".to_string(),
+                        schema: vec!["schema.sql".to_string()],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+                .into_inner();
+
+            assert_eq!(response.files[0].contents, b"1");
+        });
+    }
+
+    #[test]
+    fn test_generate_reports_errors() {
+        let service = Service::new(|_request| Err("generation failed".into()));
+
+        runtime().block_on(async {
+            let mut client = start(service).await;
+
+            let status = client
+                .generate(GenerateRequest::default())
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::Internal);
+            assert_eq!(status.message(), "generation failed");
+
+            let status = client
+                .generate(GenerateRequest {
+                    settings: Some(Settings {
+                        engine: "postgresql".to_string(),
+                        schema: vec!["../outside.sql".to_string()],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                })
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        });
+    }
+
+    #[test]
+    fn test_generate_refuses_files_outside_base_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_dir = dir.path().join("project");
+        std::fs::create_dir(&base_dir).unwrap();
+        let secret = dir.path().join("secret.sql");
+        std::fs::write(&secret, "password hunter2").unwrap();
+        std::fs::write(base_dir.join("broken.sql"), "CREATE TABLE hunter2 (").unwrap();
+
+        let service = Service::new(|_request| Ok(GenerateResponse { files: vec![] }))
+            .with_options(RunOptions::default().with_base_dir(&base_dir));
+
+        runtime().block_on(async {
+            let mut client = start(service).await;
+
+            for entry in [
+                "../secret.sql".to_string(),
+                secret.display().to_string(),
+                "broken.sql".to_string(),
+            ] {
+                let status = client
+                    .generate(GenerateRequest {
+                        settings: Some(Settings {
+                            engine: "postgresql".to_string(),
+                            schema: vec![entry.clone()],
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    })
+                    .await
+                    .unwrap_err();
+                assert_eq!(status.code(), tonic::Code::InvalidArgument, "{entry}");
+                assert!(
+                    !status.message().contains("hunter2"),
+                    "{}",
+                    status.message()
+                );
+            }
+        });
+    }
+}
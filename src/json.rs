@@ -0,0 +1,122 @@
+//! JSON encoding of the plugin messages.
+//!
+//! With the `json` feature enabled, every message of [`crate::plugin`]
+//! implements `serde::Serialize` and `serde::Deserialize` following the proto3
+//! JSON mapping, as generated by `pbjson-build`:
+//!
+//! - Keys use the `json_name` of each field (e.g. `Query.params` is written as
+//!   `parameters`, `Column.not_null` as `notNull`), while both the `json_name`
+//!   and the original field name are accepted when decoding
+//! - `bytes` fields are base64 encoded
+//! - Fields holding their default value are omitted when encoding and may be
+//!   left out when decoding
+//!
+//! This makes it possible to read the request written by sqlc's built-in JSON
+//! plugin, to keep human-readable fixtures and to diff requests in code review.
+//!
+//! # Example
+//!
+//! ```
+//! use sqlc_gen_core::plugin::{File, GenerateResponse};
+//!
+//! let response = GenerateResponse {
+//!     files: vec![File {
+//!         name: "query.rs".to_string(),
+//!         contents: b"// Generated code".to_vec(),
+//!     }],
+//! };
+//!
+//! let json = serde_json::to_string(&response).unwrap();
+//! assert_eq!(
+//!     json,
+//!     r#"{"files":[{"name":"query.rs","contents":"Ly8gR2VuZXJhdGVkIGNvZGU="}]}"#
+//! );
+//!
+//! let decoded: GenerateResponse = serde_json::from_str(&json).unwrap();
+//! assert_eq!(decoded, response);
+//! ```
+
+#[allow(clippy::all)]
+mod generated {
+    use crate::plugin::*;
+
+    include!(concat!(env!("OUT_DIR"), "/plugin.serde.rs"));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plugin::{Column, GenerateRequest, Identifier, Parameter, Query, Settings};
+    use serde_json::json;
+
+    #[test]
+    fn test_json_name_keys() {
+        let query = Query {
+            name: "GetUser".to_string(),
+            cmd: ":one".to_string(),
+            params: vec![Parameter {
+                number: 1,
+                column: Some(Column {
+                    name: "id".to_string(),
+                    not_null: true,
+                    ..Default::default()
+                }),
+            }],
+            ..Default::default()
+        };
+
+        let value = serde_json::to_value(&query).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "name": "GetUser",
+                "cmd": ":one",
+                "parameters": [{"number": 1, "column": {"name": "id", "notNull": true}}],
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_accepts_proto_field_names() {
+        let column: Column = serde_json::from_value(json!({
+            "name": "id",
+            "not_null": true,
+            "type": {"name": "int4"},
+        }))
+        .unwrap();
+
+        assert!(column.not_null);
+        assert_eq!(
+            column.r#type,
+            Some(Identifier {
+                name: "int4".to_string(),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_request_roundtrip_with_bytes() {
+        let request = GenerateRequest {
+            settings: Some(Settings {
+                engine: "postgresql".to_string(),
+                schema: vec!["schema.sql".to_string()],
+                ..Default::default()
+            }),
+            sqlc_version: "1.0.0".to_string(),
+            plugin_options: br#"{"package":"db"}"#.to_vec(),
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["plugin_options"], "eyJwYWNrYWdlIjoiZGIifQ==");
+
+        let decoded: GenerateRequest = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, request);
+    }
+
+    #[test]
+    fn test_decode_rejects_unknown_fields() {
+        let err = serde_json::from_value::<Column>(json!({"nmae": "id"})).unwrap_err();
+        assert!(err.to_string().contains("nmae"));
+    }
+}
@@ -1,13 +1,40 @@
 //! sqlc.dev gen core library.
 //!
 //! Provides:
+//! - `builder`: fluent builders for requests, queries, parameters and columns
+//! - `composite`: attributes of composite types, kept alongside the catalog
+//! - `frontend`: building requests from schema and annotated query files
+//! - `fs`: file system abstraction used to load schema files
+//! - `grpc`: serving plugins over the `CodegenService` gRPC service (`grpc` feature)
+//! - `json`: proto3 JSON encoding of the plugin messages (`json` feature)
+//! - `loader`: discovery and migration-aware loading of schema files
+//! - `log`: opt-in diagnostics written to stderr
+//! - `merge`: merging catalogs with per-kind conflict policies
+//! - `options`: typed decoding of plugin and global options
+//! - `output`: writing generated files to disk, with dry-run and check modes
 //! - `plugin`: generated proto definitions
 //! - `runtime`: helper functions for running sqlc.dev plugins
 //! - `schema`: SQL schema parsing and constraint extraction
+//! - `testing`: golden-file fixtures for plugin tests (`testing` feature)
 
+pub mod builder;
+pub mod composite;
+pub mod frontend;
+pub mod fs;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod loader;
+pub mod log;
+pub mod merge;
+pub mod options;
+pub mod output;
 pub mod plugin;
 pub mod runtime;
 pub mod schema;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 pub mod prelude {
     pub use crate::plugin::{File, GenerateRequest, GenerateResponse};
@@ -0,0 +1,728 @@
+//! Schema file discovery and loading.
+//!
+//! The `schema` entries of `sqlc.yaml` may name single files, directories of
+//! migration files, or glob patterns. This module expands them into the list
+//! of SQL files that should be fed to [`crate::schema::CatalogBuilder`], in
+//! the same order sqlc itself reads them:
+//!
+//! - A file is used as-is
+//! - A directory contributes its `.sql` files (not recursively), ordered by
+//!   migration version
+//! - A glob pattern (containing `*`, `?` or `[`) contributes every matching
+//!   `.sql` file, ordered by migration version
+//!
+//! Hidden files (starting with `.`) and golang-migrate `.down.sql` files found
+//! in directories or by patterns are skipped, and a file reached through
+//! several entries is only read once. Relative entries are resolved against
+//! the loader's base directory, see [`SchemaLoader`]. Files are accessed
+//! through the loader's [`FileSystem`], the host file system by default.
+//!
+//! # Migrations
+//!
+//! [`SchemaLoader::load`] also understands the annotations of common migration tools
+//! and keeps only the statements that migrate the schema up:
+//!
+//! | Tool           | Up marker          | Down marker          |
+//! |----------------|--------------------|----------------------|
+//! | goose          | `-- +goose Up`     | `-- +goose Down`     |
+//! | sql-migrate    | `-- +migrate Up`   | `-- +migrate Down`   |
+//! | dbmate         | `-- migrate:up`    | `-- migrate:down`    |
+//! | golang-migrate | `*.up.sql` files   | `*.down.sql` files   |
+//!
+//! Files are ordered by the numeric version prefix used by all of these tools
+//! (`1_init.up.sql` before `10_users.up.sql`), falling back to the file name.
+
+use crate::fs::{FileSystem, StdFileSystem};
+use crate::runtime::Error;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// A schema file read from disk, ready to be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaFile {
+    /// Path the file was read from
+    pub path: PathBuf,
+
+    /// SQL contents with migration down sections removed
+    pub sql: String,
+}
+
+/// Expands and reads the `Settings.schema` entries of a request.
+///
+/// By default relative entries are resolved against the current working
+/// directory, like `std::fs` does. sqlc resolves them against the directory of
+/// its configuration file, so plugins should set [`base_dir`](Self::base_dir)
+/// to that directory when it is known. With a base directory, entries that
+/// would resolve outside of it (through `..` or an absolute path) are refused,
+/// and so are files reached through a symbolic link or a glob match that lead
+/// outside of it.
+///
+/// # Example
+///
+/// ```no_run
+/// use sqlc_gen_core::loader::SchemaLoader;
+///
+/// let loader = SchemaLoader::new().with_base_dir("/project");
+/// for file in loader.load(&["db/migrations"]).unwrap() {
+///     println!("{}: {} bytes", file.path.display(), file.sql.len());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SchemaLoader {
+    /// Directory schema entries are resolved against
+    ///
+    /// When `None`, entries are used as given. Entries are first checked
+    /// lexically, then every file they expand to is resolved with
+    /// [`FileSystem::canonicalize`], following symbolic links, and checked
+    /// against the resolved base directory.
+    pub base_dir: Option<PathBuf>,
+
+    /// File system schema files are read from
+    pub fs: Arc<dyn FileSystem>,
+}
+
+impl Default for SchemaLoader {
+    fn default() -> Self {
+        Self {
+            base_dir: None,
+            fs: Arc::new(StdFileSystem),
+        }
+    }
+}
+
+impl SchemaLoader {
+    /// Create a loader resolving paths against the current working directory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolve schema entries against `base_dir` and refuse paths outside of it
+    pub fn with_base_dir(mut self, base_dir: impl Into<PathBuf>) -> Self {
+        self.base_dir = Some(base_dir.into());
+        self
+    }
+
+    /// Read schema files from `fs` instead of the host file system
+    pub fn with_file_system(mut self, fs: impl FileSystem + 'static) -> Self {
+        self.fs = Arc::new(fs);
+        self
+    }
+
+    /// Expand, read and clean up schema entries.
+    ///
+    /// Entries are expanded with [`expand`](Self::expand) and migration down
+    /// sections are removed with [`up_migration`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if [`expand`](Self::expand) fails, or
+    /// [`Error::SchemaRead`] if a file cannot be read.
+    pub fn load<S: AsRef<str>>(&self, entries: &[S]) -> Result<Vec<SchemaFile>, Error> {
+        self.expand(entries)?
+            .into_iter()
+            .map(|path| {
+                let contents = self
+                    .fs
+                    .read_to_string(&path)
+                    .map_err(|source| self.read_error(&path, source))?;
+
+                Ok(SchemaFile {
+                    sql: up_migration(&contents),
+                    path,
+                })
+            })
+            .collect()
+    }
+
+    /// Expand schema entries into the SQL files to read.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SchemaPath`] if an entry, or a file it expands to,
+    /// resolves outside of the base directory, or [`Error::SchemaRead`] if an
+    /// entry does not exist, a directory cannot be listed, a glob pattern is
+    /// invalid or matches no files.
+    pub fn expand<S: AsRef<str>>(&self, entries: &[S]) -> Result<Vec<PathBuf>, Error> {
+        let mut files = Vec::new();
+        let mut real_base_dir = None;
+
+        for entry in entries {
+            let path = self.resolve(entry.as_ref())?;
+
+            let expanded = if is_glob(entry.as_ref()) {
+                self.expand_glob(&path)?
+            } else {
+                let is_dir = self
+                    .fs
+                    .is_dir(&path)
+                    .map_err(|source| self.read_error(&path, source))?;
+
+                if is_dir {
+                    self.expand_dir(&path)?
+                } else {
+                    vec![path]
+                }
+            };
+
+            for file in expanded {
+                if let Some(base_dir) = &self.base_dir {
+                    let real_base_dir = match &real_base_dir {
+                        Some(real_base_dir) => real_base_dir,
+                        None => real_base_dir.insert(self.real_path(&normalize(base_dir))?),
+                    };
+                    if !self.real_path(&file)?.starts_with(real_base_dir) {
+                        return Err(Error::SchemaPath {
+                            path: file,
+                            base_dir: normalize(base_dir),
+                        });
+                    }
+                }
+
+                if !files.contains(&file) {
+                    files.push(file);
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Resolve symbolic links in `path` through the file system
+    fn real_path(&self, path: &Path) -> Result<PathBuf, Error> {
+        self.fs
+            .canonicalize(path)
+            .map_err(|source| self.read_error(path, source))
+    }
+
+    /// Error for a schema path that cannot be read
+    fn read_error(&self, path: &Path, source: io::Error) -> Error {
+        Error::SchemaRead {
+            path: path.to_path_buf(),
+            base_dir: self.base_dir.clone(),
+            source,
+        }
+    }
+
+    /// List the SQL files of a directory, ordered by migration version
+    fn expand_dir(&self, dir: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut files = self
+            .fs
+            .read_dir(dir)
+            .map_err(|source| self.read_error(dir, source))?;
+        files.retain(|path| is_schema_file(path));
+
+        sort_by_version(&mut files);
+        Ok(files)
+    }
+
+    /// List the SQL files matching a glob pattern, ordered by migration version
+    fn expand_glob(&self, pattern: &Path) -> Result<Vec<PathBuf>, Error> {
+        let pattern_error = |message: String| {
+            self.read_error(
+                pattern,
+                io::Error::new(io::ErrorKind::InvalidInput, message),
+            )
+        };
+
+        let text = pattern
+            .to_str()
+            .ok_or_else(|| pattern_error("pattern is not valid UTF-8".to_string()))?;
+        let mut files = self
+            .fs
+            .glob(text)
+            .map_err(|source| self.read_error(pattern, source))?;
+        files.retain(|path| is_schema_file(path));
+
+        if files.is_empty() {
+            return Err(self.read_error(
+                pattern,
+                io::Error::new(io::ErrorKind::NotFound, "pattern matched no schema files"),
+            ));
+        }
+
+        sort_by_version(&mut files);
+        Ok(files)
+    }
+
+    /// Resolve a schema entry against the base directory
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::SchemaPath`] if the entry resolves outside of the base
+    /// directory.
+    pub fn resolve(&self, entry: &str) -> Result<PathBuf, Error> {
+        let Some(base_dir) = &self.base_dir else {
+            return Ok(PathBuf::from(entry));
+        };
+
+        let base_dir = normalize(base_dir);
+        let path = normalize(&base_dir.join(entry));
+
+        if path.starts_with(&base_dir) {
+            Ok(path)
+        } else {
+            Err(Error::SchemaPath {
+                path: PathBuf::from(entry),
+                base_dir,
+            })
+        }
+    }
+}
+
+/// Lexically normalize a path, removing `.` and resolving `..` components
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            _ => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+/// Remove the down sections of a goose, sql-migrate or dbmate migration.
+///
+/// Lines outside of any annotated section are kept, so plain SQL files pass
+/// through unchanged. Removed lines are replaced by empty lines so that line
+/// numbers in parse errors still match the original file.
+///
+/// # Example
+///
+/// ```
+/// use sqlc_gen_core::loader::up_migration;
+///
+/// let sql = "-- +goose Up\nCREATE TABLE users (id int);\n-- +goose Down\nDROP TABLE users;\n";
+/// assert_eq!(
+///     up_migration(sql),
+///     "-- +goose Up\nCREATE TABLE users (id int);\n\n\n"
+/// );
+/// ```
+pub fn up_migration(sql: &str) -> String {
+    let mut keep = true;
+
+    sql.split_inclusive('\n')
+        .map(|line| {
+            match migration_marker(line) {
+                Some(Direction::Up) => keep = true,
+                Some(Direction::Down) => keep = false,
+                None => {}
+            }
+
+            if keep {
+                line
+            } else if line.ends_with('\n') {
+                "\n"
+            } else {
+                ""
+            }
+        })
+        .collect()
+}
+
+/// Direction of a migration section
+enum Direction {
+    Up,
+    Down,
+}
+
+/// Recognize the section markers of goose, sql-migrate and dbmate
+fn migration_marker(line: &str) -> Option<Direction> {
+    let line = line.trim();
+    let annotation = line.strip_prefix("--")?.trim_start();
+
+    match annotation.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["+goose", "Up", ..] | ["+migrate", "Up", ..] | ["migrate:up", ..] => Some(Direction::Up),
+        ["+goose", "Down", ..] | ["+migrate", "Down", ..] | ["migrate:down", ..] => {
+            Some(Direction::Down)
+        }
+        _ => None,
+    }
+}
+
+/// Numeric version prefix of a migration file name (e.g. `0042_users.sql` -> 42)
+pub fn migration_version(path: &Path) -> Option<u128> {
+    let name = path.file_name()?.to_str()?;
+    let digits = &name[..name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(name.len())];
+
+    digits.parse().ok()
+}
+
+/// Order migration files by version, then by path
+fn sort_by_version(files: &mut [PathBuf]) {
+    files.sort_by(|a, b| {
+        migration_version(a)
+            .cmp(&migration_version(b))
+            .then_with(|| a.cmp(b))
+    });
+}
+
+/// Check whether a schema entry is a glob pattern rather than a path
+fn is_glob(entry: &str) -> bool {
+    entry.contains(['*', '?', '['])
+}
+
+/// Check whether a file found in a directory or by a pattern should be read
+fn is_schema_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+
+    !name.starts_with('.') && name.ends_with(".sql") && !name.ends_with(".down.sql")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "").unwrap();
+        path
+    }
+
+    fn display(paths: &[PathBuf], root: &Path) -> Vec<String> {
+        paths
+            .iter()
+            .map(|p| p.strip_prefix(root).unwrap().display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_expand_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = touch(dir.path(), "schema.txt");
+
+        let files = SchemaLoader::new()
+            .expand(&[file.display().to_string()])
+            .unwrap();
+        assert_eq!(files, vec![file]);
+    }
+
+    #[test]
+    fn test_expand_directory_sorted() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "002_posts.sql");
+        touch(dir.path(), "001_users.sql");
+        touch(dir.path(), "010_comments.sql");
+        touch(dir.path(), "README.md");
+        touch(dir.path(), ".hidden.sql");
+        touch(dir.path(), "nested/003_ignored.sql");
+
+        let files = SchemaLoader::new()
+            .expand(&[dir.path().display().to_string()])
+            .unwrap();
+        assert_eq!(
+            display(&files, dir.path()),
+            vec!["001_users.sql", "002_posts.sql", "010_comments.sql"]
+        );
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "a/schema.sql");
+        touch(dir.path(), "b/schema.sql");
+        touch(dir.path(), "b/notes.txt");
+
+        let pattern = format!("{}/*/*", dir.path().display());
+        let files = SchemaLoader::new().expand(&[pattern]).unwrap();
+        assert_eq!(
+            display(&files, dir.path()),
+            vec!["a/schema.sql", "b/schema.sql"]
+        );
+    }
+
+    #[test]
+    fn test_expand_glob_no_match() {
+        let dir = tempfile::tempdir().unwrap();
+        let pattern = format!("{}/*.sql", dir.path().display());
+
+        let err = SchemaLoader::new().expand(&[pattern]).unwrap_err();
+        match err {
+            Error::SchemaRead { source, .. } => {
+                assert_eq!(source.kind(), io::ErrorKind::NotFound)
+            }
+            other => panic!("expected schema read error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_expand_invalid_glob() {
+        let err = SchemaLoader::new().expand(&["schema/[.sql"]).unwrap_err();
+        match err {
+            Error::SchemaRead { source, .. } => {
+                assert_eq!(source.kind(), io::ErrorKind::InvalidInput)
+            }
+            other => panic!("expected schema read error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_expand_missing_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.sql");
+
+        let err = SchemaLoader::new()
+            .expand(&[missing.display().to_string()])
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::SchemaRead { ref path, base_dir: None, .. } if *path == missing)
+        );
+
+        let err = SchemaLoader::new()
+            .with_base_dir(dir.path())
+            .expand(&["missing.sql"])
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::SchemaRead { base_dir: Some(ref base_dir), .. } if base_dir == dir.path()
+        ));
+    }
+
+    #[test]
+    fn test_expand_deduplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let users = touch(dir.path(), "users.sql");
+        touch(dir.path(), "posts.sql");
+
+        let files = SchemaLoader::new()
+            .expand(&[
+                users.display().to_string(),
+                dir.path().display().to_string(),
+            ])
+            .unwrap();
+        assert_eq!(display(&files, dir.path()), vec!["users.sql", "posts.sql"]);
+    }
+
+    #[test]
+    fn test_expand_directory_version_order() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "10_comments.up.sql");
+        touch(dir.path(), "2_posts.up.sql");
+        touch(dir.path(), "2_posts.down.sql");
+        touch(dir.path(), "1_users.up.sql");
+        touch(dir.path(), "1_users.down.sql");
+
+        let files = SchemaLoader::new()
+            .expand(&[dir.path().display().to_string()])
+            .unwrap();
+        assert_eq!(
+            display(&files, dir.path()),
+            vec!["1_users.up.sql", "2_posts.up.sql", "10_comments.up.sql"]
+        );
+    }
+
+    #[test]
+    fn test_migration_version() {
+        assert_eq!(migration_version(Path::new("0042_users.sql")), Some(42));
+        assert_eq!(
+            migration_version(Path::new("db/20240101120000_init.sql")),
+            Some(20240101120000)
+        );
+        assert_eq!(migration_version(Path::new("schema.sql")), None);
+    }
+
+    #[test]
+    fn test_up_migration_plain_sql() {
+        let sql = "CREATE TABLE users (id int);\n";
+        assert_eq!(up_migration(sql), sql);
+    }
+
+    #[test]
+    fn test_up_migration_goose() {
+        let sql = "\
+-- +goose Up
+-- +goose StatementBegin
+CREATE TABLE users (id int);
+-- +goose StatementEnd
+
+-- +goose Down
+DROP TABLE users;";
+
+        let up = up_migration(sql);
+        assert!(up.contains("CREATE TABLE users"));
+        assert!(!up.contains("DROP TABLE"));
+        assert_eq!(up.matches('\n').count(), sql.matches('\n').count());
+    }
+
+    #[test]
+    fn test_up_migration_sql_migrate() {
+        let sql = "-- +migrate Up\nCREATE TABLE a (id int);\n-- +migrate Down\nDROP TABLE a;\n";
+        assert!(!up_migration(sql).contains("DROP"));
+    }
+
+    #[test]
+    fn test_up_migration_dbmate_down_first() {
+        let sql = "\
+-- migrate:down
+DROP TABLE users;
+-- migrate:up
+CREATE TABLE users (id int);
+";
+
+        let up = up_migration(sql);
+        assert!(!up.contains("DROP TABLE"));
+        assert!(up.contains("CREATE TABLE users"));
+    }
+
+    #[test]
+    fn test_load_schema_strips_down_sections() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("20240101_users.sql"),
+            "-- +goose Up\nCREATE TABLE users (id int);\n-- +goose Down\nDROP TABLE users;\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("20240102_posts.sql"),
+            "-- migrate:up\nCREATE TABLE posts (id int);\n-- migrate:down\nDROP TABLE posts;\n",
+        )
+        .unwrap();
+
+        let files = SchemaLoader::new()
+            .load(&[dir.path().display().to_string()])
+            .unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files[0].path.ends_with("20240101_users.sql"));
+        assert!(files[0].sql.contains("CREATE TABLE users"));
+        assert!(files.iter().all(|f| !f.sql.contains("DROP TABLE")));
+    }
+
+    #[test]
+    fn test_resolve_without_base_dir() {
+        let loader = SchemaLoader::new();
+        assert_eq!(
+            loader.resolve("../schema.sql").unwrap(),
+            PathBuf::from("../schema.sql")
+        );
+    }
+
+    #[test]
+    fn test_resolve_relative_to_base_dir() {
+        let loader = SchemaLoader::new().with_base_dir("/project/config");
+        assert_eq!(
+            loader.resolve("./db/../schema.sql").unwrap(),
+            PathBuf::from("/project/config/schema.sql")
+        );
+        assert_eq!(
+            loader.resolve("/project/config/db/schema.sql").unwrap(),
+            PathBuf::from("/project/config/db/schema.sql")
+        );
+    }
+
+    #[test]
+    fn test_resolve_refuses_escaping_paths() {
+        let loader = SchemaLoader::new().with_base_dir("/project/config");
+
+        for entry in ["../schema.sql", "db/../../other/schema.sql", "/etc/passwd"] {
+            let err = loader.resolve(entry).unwrap_err();
+            match err {
+                Error::SchemaPath { path, base_dir } => {
+                    assert_eq!(path, PathBuf::from(entry));
+                    assert_eq!(base_dir, PathBuf::from("/project/config"));
+                }
+                other => panic!("expected schema path error, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_expand_with_base_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "db/schema/001_users.sql");
+        touch(dir.path(), "db/schema/002_posts.sql");
+        touch(dir.path(), "db/extra/003_tags.sql");
+
+        let loader = SchemaLoader::new().with_base_dir(dir.path().join("db"));
+        let files = loader.expand(&["schema", "extra/*.sql"]).unwrap();
+        assert_eq!(
+            display(&files, dir.path()),
+            vec![
+                "db/schema/001_users.sql",
+                "db/schema/002_posts.sql",
+                "db/extra/003_tags.sql"
+            ]
+        );
+
+        let err = loader.expand(&["../other"]).unwrap_err();
+        assert!(matches!(err, Error::SchemaPath { .. }));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_expand_refuses_links_outside_base_dir() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "secret/passwords.sql");
+        touch(dir.path(), "project/db/001_users.sql");
+        symlink(
+            dir.path().join("secret/passwords.sql"),
+            dir.path().join("project/link.sql"),
+        )
+        .unwrap();
+        symlink(dir.path().join("secret"), dir.path().join("project/shared")).unwrap();
+        symlink(
+            dir.path().join("project/db/001_users.sql"),
+            dir.path().join("project/users.sql"),
+        )
+        .unwrap();
+
+        let loader = SchemaLoader::new().with_base_dir(dir.path().join("project"));
+        for entry in ["link.sql", "shared", "shared/*.sql", "*.sql"] {
+            let err = loader.expand(&[entry]).unwrap_err();
+            assert!(
+                matches!(err, Error::SchemaPath { .. }),
+                "{entry}: unexpected error {err:?}"
+            );
+        }
+
+        let files = loader.expand(&["users.sql", "db"]).unwrap();
+        assert_eq!(
+            display(&files, dir.path()),
+            vec!["project/users.sql", "project/db/001_users.sql"]
+        );
+    }
+
+    #[test]
+    fn test_load_from_memory_file_system() {
+        let fs = crate::fs::MemoryFileSystem::new()
+            .with_file("db/10_posts.sql", "CREATE TABLE posts (id int);")
+            .with_file(
+                "db/2_users.sql",
+                "-- +goose Up\nCREATE TABLE users (id int);\n-- +goose Down\nDROP TABLE users;\n",
+            )
+            .with_file("db/2_users.down.sql", "DROP TABLE users;")
+            .with_file("db/nested/3_ignored.sql", "");
+
+        let loader = SchemaLoader::new().with_file_system(fs);
+
+        let files = loader.load(&["db"]).unwrap();
+        assert_eq!(
+            files.iter().map(|f| f.path.clone()).collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("db/2_users.sql"),
+                PathBuf::from("db/10_posts.sql")
+            ]
+        );
+        assert!(!files[0].sql.contains("DROP"));
+
+        let files = loader.expand(&["db/*.sql"]).unwrap();
+        assert_eq!(files.len(), 2);
+
+        let err = loader.load(&["missing.sql"]).unwrap_err();
+        assert!(matches!(err, Error::SchemaRead { .. }));
+    }
+}
@@ -0,0 +1,374 @@
+//! Opt-in diagnostics written to stderr.
+//!
+//! sqlc only shows the stderr of a plugin when it fails, and plugins have no
+//! other channel to report what they do. With a [`Logger`] enabled (for
+//! example by setting `SQLC_GEN_LOG=debug`, see
+//! [`crate::runtime::RunOptions::from_env`]), the runtime logs each stage, the
+//! schema files it reads, the statements it parses or skips and how long each
+//! stage takes. Every event is written as a single line:
+//!
+//! ```text
+//! sqlc-gen debug [schema] read file path=db/schema.sql bytes=1204
+//! sqlc-gen debug [schema] skipped statement sql="CREATE VIEW active_users"
+//! sqlc-gen debug [generate] done files=3 elapsed=1.42ms
+//! ```
+//!
+//! While the runtime processes a request, its logger is the *current* logger
+//! of the thread, so plugins can emit their own events through the same
+//! channel with [`event`] without having the logger passed around. Outside of
+//! the runtime, events are discarded.
+//!
+//! # Example
+//!
+//! ```
+//! use sqlc_gen_core::log::{self, Level};
+//!
+//! log::event(Level::Info, "models", "generated struct")
+//!     .field("name", "User")
+//!     .field("fields", 4)
+//!     .emit();
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Severity of an event, from the most to the least important.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// Failures
+    Error,
+    /// Suspicious input that did not stop generation
+    Warn,
+    /// High-level progress
+    Info,
+    /// Stages, files, statements and durations
+    Debug,
+    /// Everything else
+    Trace,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+            Self::Trace => "trace",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "error" => Ok(Self::Error),
+            "warn" | "warning" => Ok(Self::Warn),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            "trace" => Ok(Self::Trace),
+            other => Err(format!("unknown log level `{other}`")),
+        }
+    }
+}
+
+/// Destination of the events of a [`Logger`]
+type Sink = Arc<Mutex<dyn Write + Send>>;
+
+/// Writes events up to a maximum [`Level`] to stderr or another writer.
+///
+/// The default logger is disabled. Clones share the same destination.
+#[derive(Clone)]
+pub struct Logger {
+    /// Most verbose level written, `None` when disabled
+    pub level: Option<Level>,
+
+    /// Destination of the events
+    sink: Sink,
+}
+
+impl fmt::Debug for Logger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Logger")
+            .field("level", &self.level)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self {
+            level: None,
+            sink: Arc::new(Mutex::new(std::io::stderr())),
+        }
+    }
+}
+
+impl Logger {
+    /// Logger writing events up to `level` to stderr
+    pub fn stderr(level: Level) -> Self {
+        Self {
+            level: Some(level),
+            ..Self::default()
+        }
+    }
+
+    /// Logger writing events up to `level` to `writer`
+    pub fn to_writer(level: Level, writer: impl Write + Send + 'static) -> Self {
+        Self {
+            level: Some(level),
+            sink: Arc::new(Mutex::new(writer)),
+        }
+    }
+
+    /// Copy of this logger writing at least the events up to `level`
+    ///
+    /// The copy shares the destination, which is stderr for the default
+    /// logger. Used for warnings that must be seen even when logging is
+    /// disabled.
+    pub fn at_least(&self, level: Level) -> Self {
+        Self {
+            level: Some(self.level.map_or(level, |max| max.max(level))),
+            sink: Arc::clone(&self.sink),
+        }
+    }
+
+    /// Check whether events of `level` are written
+    pub fn enabled(&self, level: Level) -> bool {
+        self.level.is_some_and(|max| level <= max)
+    }
+
+    /// Start an event logged to this logger
+    pub fn event(&self, level: Level, stage: &str, message: impl Into<String>) -> Event {
+        Event {
+            logger: self.enabled(level).then(|| self.clone()),
+            level,
+            stage: stage.to_string(),
+            message: message.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Run `f` with this logger as the current logger of the thread
+    ///
+    /// The previous current logger is restored afterwards, even if `f` panics.
+    pub fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        struct Restore(Option<Logger>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+            }
+        }
+
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        let _restore = Restore(previous);
+        f()
+    }
+
+    /// Write a rendered line, ignoring I/O errors
+    fn write(&self, line: &str) {
+        let mut sink = self.sink.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ = sink.write_all(line.as_bytes());
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Logger>> = const { RefCell::new(None) };
+}
+
+/// Current logger of the thread, or a disabled logger outside of a scope
+pub fn current() -> Logger {
+    CURRENT
+        .with(|current| current.borrow().clone())
+        .unwrap_or_default()
+}
+
+/// Check whether the current logger writes events of `level`
+pub fn enabled(level: Level) -> bool {
+    CURRENT.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .is_some_and(|logger| logger.enabled(level))
+    })
+}
+
+/// Start an event logged to the current logger
+///
+/// `stage` groups related events, such as `schema` or the name of a plugin
+/// component.
+pub fn event(level: Level, stage: &str, message: impl Into<String>) -> Event {
+    current().event(level, stage, message)
+}
+
+/// Run `f` and log how long it took at [`Level::Debug`] under `stage`
+pub fn timed<T>(stage: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    event(Level::Debug, stage, "done")
+        .elapsed(start.elapsed())
+        .emit();
+    result
+}
+
+/// An event being built, written by [`Event::emit`].
+///
+/// Fields are only formatted when the event is enabled.
+#[derive(Debug)]
+#[must_use = "events are only written by `emit`"]
+pub struct Event {
+    logger: Option<Logger>,
+    level: Level,
+    stage: String,
+    message: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Event {
+    /// Add a `key=value` field
+    pub fn field(mut self, key: &str, value: impl fmt::Display) -> Self {
+        if self.logger.is_some() {
+            self.fields.push((key.to_string(), value.to_string()));
+        }
+        self
+    }
+
+    /// Add an `elapsed` field with a duration
+    pub fn elapsed(self, duration: Duration) -> Self {
+        let millis = duration.as_secs_f64() * 1000.0;
+        self.field("elapsed", format_args!("{millis:.2}ms"))
+    }
+
+    /// Write the event, if its level is enabled
+    pub fn emit(self) {
+        if let Some(logger) = &self.logger {
+            logger.write(&self.render());
+        }
+    }
+
+    /// Render the event as a single line
+    fn render(&self) -> String {
+        let mut line = format!("sqlc-gen {} [{}] {}", self.level, self.stage, self.message);
+        for (key, value) in &self.fields {
+            if value.is_empty()
+                || value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=')
+            {
+                line += &format!(" {key}={value:?}");
+            } else {
+                line += &format!(" {key}={value}");
+            }
+        }
+        line.push('\n');
+        line
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Writer collecting the lines of a test logger
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct Buffer(pub Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        pub(crate) fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_level_from_str() {
+        assert_eq!("debug".parse::<Level>(), Ok(Level::Debug));
+        assert_eq!(" WARNING ".parse::<Level>(), Ok(Level::Warn));
+        assert!("verbose".parse::<Level>().is_err());
+        assert!(Level::Error < Level::Trace);
+    }
+
+    #[test]
+    fn test_event_render() {
+        let buffer = Buffer::default();
+        let logger = Logger::to_writer(Level::Debug, buffer.clone());
+
+        logger
+            .event(Level::Debug, "schema", "read file")
+            .field("path", "db/schema.sql")
+            .field("sql", "CREATE VIEW v")
+            .field("empty", "")
+            .emit();
+        logger.event(Level::Trace, "schema", "hidden").emit();
+
+        assert_eq!(
+            buffer.contents(),
+            "sqlc-gen debug [schema] read file path=db/schema.sql sql=\"CREATE VIEW v\" empty=\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_scope_sets_current_logger() {
+        let buffer = Buffer::default();
+        let logger = Logger::to_writer(Level::Info, buffer.clone());
+
+        event(Level::Info, "plugin", "outside").emit();
+        assert!(!enabled(Level::Info));
+
+        logger.scope(|| {
+            assert!(enabled(Level::Info));
+            assert!(!enabled(Level::Debug));
+            event(Level::Info, "plugin", "inside").field("n", 1).emit();
+        });
+        event(Level::Info, "plugin", "after").emit();
+
+        assert_eq!(buffer.contents(), "sqlc-gen info [plugin] inside n=1\n");
+    }
+
+    #[test]
+    fn test_at_least() {
+        let buffer = Buffer::default();
+        let mut logger = Logger::to_writer(Level::Error, buffer.clone());
+        logger.level = None;
+
+        assert!(logger.at_least(Level::Warn).enabled(Level::Warn));
+        assert!(!logger.at_least(Level::Warn).enabled(Level::Info));
+        assert_eq!(
+            Logger::stderr(Level::Debug).at_least(Level::Warn).level,
+            Some(Level::Debug)
+        );
+
+        logger
+            .at_least(Level::Warn)
+            .event(Level::Warn, "capture", "failed")
+            .emit();
+        assert_eq!(buffer.contents(), "sqlc-gen warn [capture] failed\n");
+    }
+
+    #[test]
+    fn test_timed() {
+        let buffer = Buffer::default();
+        let logger = Logger::to_writer(Level::Debug, buffer.clone());
+
+        let value = logger.scope(|| timed("generate", || 42));
+        assert_eq!(value, 42);
+        assert!(buffer
+            .contents()
+            .starts_with("sqlc-gen debug [generate] done elapsed="));
+    }
+}
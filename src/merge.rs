@@ -0,0 +1,850 @@
+//! Merging catalogs from several sources.
+//!
+//! A [`CatalogBuilder`] can receive objects from several places: schema files
+//! parsed with [`CatalogBuilder::parse_sql`], the catalog sent by sqlc, or
+//! catalogs built by other means. [`CatalogBuilder::merge_catalog_with`] merges
+//! an incoming catalog into the builder. Schemas and objects are matched by
+//! qualified name, as normalized by the [`schema`](crate::schema) module (so
+//! `Users` matches sqlc's `users` but `"Users"` does not), and unqualified
+//! objects (in the `""` schema) are matched against the default schema of the
+//! incoming catalog. When an object exists on both sides and the two versions
+//! differ, the [`MergePolicy`] set for its [`ObjectKind`] decides what happens:
+//!
+//! - [`MergePolicy::KeepExisting`] keeps the builder's version
+//! - [`MergePolicy::Replace`] keeps the incoming version
+//! - [`MergePolicy::DeepMerge`] merges the two field by field, see below
+//! - [`MergePolicy::Error`] stops with a [`MergeError`]
+//!
+//! Identical objects are never conflicts. Every other clash is listed in the
+//! returned [`MergeReport`], together with how it was resolved.
+//!
+//! The builder also takes the name, comment and default schema of the
+//! incoming catalog when it does not have its own.
+//!
+//! # Deep merge
+//!
+//! sqlc's catalog knows the precise type of every column (`pg_catalog.int4`,
+//! array dimensions, lengths, comments), while the catalog parsed from schema
+//! files knows the constraints and indexes sqlc does not send. Deep merging is
+//! designed for that case, with the incoming catalog being sqlc's:
+//!
+//! - Tables keep the incoming columns and comment, and gain the primary key,
+//!   foreign keys and indexes of the existing table that the incoming one does
+//!   not define. Columns present on one side only, differing nullability and
+//!   indexes defined differently are reported, keeping the incoming version.
+//! - Enums keep the incoming values followed by the existing values missing
+//!   from them.
+//! - Composite types keep the incoming version.
+//!
+//! [`CatalogBuilder::deep_merge_catalog`] applies this policy to every kind.
+//!
+//! # Example
+//!
+//! ```
+//! use sqlc_gen_core::merge::{MergePolicies, MergePolicy, Resolution};
+//! use sqlc_gen_core::schema::CatalogBuilder;
+//!
+//! let mut builder = CatalogBuilder::new("postgresql");
+//! builder.parse_sql("CREATE TABLE users (id int)").unwrap();
+//!
+//! let mut other = CatalogBuilder::new("postgresql");
+//! other.parse_sql("CREATE TABLE users (id int, name text)").unwrap();
+//!
+//! let policies = MergePolicies::default().with_tables(MergePolicy::Replace);
+//! let report = builder.merge_catalog_with(other.build(), &policies).unwrap();
+//!
+//! assert_eq!(report.conflicts[0].object, "public.users");
+//! assert_eq!(report.conflicts[0].resolution, Resolution::Replaced);
+//! assert_eq!(builder.schemas["public"].tables[0].columns.len(), 2);
+//!
+//! let policies = MergePolicies::all(MergePolicy::Error);
+//! let mut other = CatalogBuilder::new("postgresql");
+//! other.parse_sql("CREATE TABLE users (id bigint)").unwrap();
+//! assert!(builder.merge_catalog_with(other.build(), &policies).is_err());
+//! ```
+
+use crate::plugin::{Catalog, CompositeType, Enum, Schema, Table};
+use crate::schema::CatalogBuilder;
+use std::error::Error as StdError;
+use std::fmt;
+
+/// Kind of catalog object a [`MergePolicy`] applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectKind {
+    /// Tables, including their columns, constraints and indexes
+    Table,
+    /// Enum types
+    Enum,
+    /// Composite types
+    CompositeType,
+}
+
+impl fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Table => write!(f, "table"),
+            Self::Enum => write!(f, "enum"),
+            Self::CompositeType => write!(f, "composite type"),
+        }
+    }
+}
+
+/// What to do when an object exists in both catalogs with different contents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keep the builder's version and drop the incoming one
+    #[default]
+    KeepExisting,
+    /// Replace the builder's version with the incoming one
+    Replace,
+    /// Merge both versions field by field, see the [module docs](self)
+    DeepMerge,
+    /// Fail with a [`MergeError`]
+    Error,
+}
+
+/// [`MergePolicy`] for each [`ObjectKind`].
+///
+/// The default keeps existing objects for every kind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MergePolicies {
+    /// Policy for tables
+    pub tables: MergePolicy,
+    /// Policy for enums
+    pub enums: MergePolicy,
+    /// Policy for composite types
+    pub composite_types: MergePolicy,
+}
+
+impl MergePolicies {
+    /// Use `policy` for every kind
+    pub fn all(policy: MergePolicy) -> Self {
+        Self {
+            tables: policy,
+            enums: policy,
+            composite_types: policy,
+        }
+    }
+
+    /// Set the policy for tables
+    pub fn with_tables(mut self, policy: MergePolicy) -> Self {
+        self.tables = policy;
+        self
+    }
+
+    /// Set the policy for enums
+    pub fn with_enums(mut self, policy: MergePolicy) -> Self {
+        self.enums = policy;
+        self
+    }
+
+    /// Set the policy for composite types
+    pub fn with_composite_types(mut self, policy: MergePolicy) -> Self {
+        self.composite_types = policy;
+        self
+    }
+
+    /// Policy for objects of `kind`
+    pub fn get(&self, kind: ObjectKind) -> MergePolicy {
+        match kind {
+            ObjectKind::Table => self.tables,
+            ObjectKind::Enum => self.enums,
+            ObjectKind::CompositeType => self.composite_types,
+        }
+    }
+}
+
+/// How a [`Conflict`] was resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// The builder's version was kept
+    KeptExisting,
+    /// The incoming version replaced the builder's
+    Replaced,
+    /// Both versions were merged, the incoming one winning on this difference
+    Merged,
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KeptExisting => write!(f, "kept existing"),
+            Self::Replaced => write!(f, "replaced"),
+            Self::Merged => write!(f, "merged"),
+        }
+    }
+}
+
+/// A difference between the builder's catalog and the incoming catalog.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    /// Kind of the conflicting object
+    pub kind: ObjectKind,
+    /// Qualified name of the object or of the part that differs, such as
+    /// `public.users` or `public.users.email`
+    pub object: String,
+    /// Description of the difference
+    pub message: String,
+    /// How the conflict was resolved
+    pub resolution: Resolution,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.object, self.message, self.resolution)
+    }
+}
+
+/// Outcome of a merge.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// Conflicts found while merging, in catalog order
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Error returned when an object with [`MergePolicy::Error`] conflicts.
+///
+/// The builder is left unchanged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeError {
+    /// Kind of the conflicting object
+    pub kind: ObjectKind,
+    /// Qualified name of the object
+    pub object: String,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} is defined differently in both catalogs",
+            self.kind, self.object
+        )
+    }
+}
+
+impl StdError for MergeError {}
+
+impl CatalogBuilder {
+    /// Merge `other` into the builder, resolving conflicts with `policies`.
+    ///
+    /// See the [module docs](crate::merge) for how objects are matched and
+    /// how each policy behaves.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`MergeError`] for the first object that differs on both
+    /// sides when its kind uses [`MergePolicy::Error`]. The builder is left
+    /// unchanged in that case.
+    pub fn merge_catalog_with(
+        &mut self,
+        other: Catalog,
+        policies: &MergePolicies,
+    ) -> Result<MergeReport, MergeError> {
+        let mut merger = Merger {
+            policies,
+            report: MergeReport::default(),
+        };
+        let mut schemas = self.schemas.clone();
+        let mut renamed = false;
+        let mut added = Vec::new();
+
+        if !other.default_schema.is_empty() {
+            if let Some(mut unqualified) = schemas.remove("") {
+                unqualified.name = other.default_schema.clone();
+                for table in &mut unqualified.tables {
+                    if let Some(rel) = &mut table.rel {
+                        rel.schema = other.default_schema.clone();
+                    }
+                }
+
+                let schema = match schemas.remove(&other.default_schema) {
+                    Some(mut existing) => {
+                        merger.schema(&mut existing, unqualified)?;
+                        existing
+                    }
+                    None => unqualified,
+                };
+                schemas.insert(other.default_schema.clone(), schema);
+                renamed = true;
+            }
+        }
+
+        for schema in other.schemas {
+            match schemas.get_mut(&schema.name) {
+                Some(existing) => merger.schema(existing, schema)?,
+                None => {
+                    added.push(schema.name.clone());
+                    schemas.insert(schema.name.clone(), schema);
+                }
+            }
+        }
+
+        self.schemas = schemas;
+        if renamed {
+            self.rename_declared_schema("", &other.default_schema);
+        }
+        for name in &added {
+            self.declare_schema(name);
+        }
+        if self.default_schema.is_empty() {
+            self.default_schema = other.default_schema;
+        }
+        if self.name.is_empty() {
+            self.name = other.name;
+        }
+        if self.comment.is_empty() {
+            self.comment = other.comment;
+        }
+
+        Ok(merger.report)
+    }
+
+    /// Merge the catalog sent by sqlc into the parsed schemas, field by field.
+    ///
+    /// This is [`merge_catalog_with`](Self::merge_catalog_with) with
+    /// [`MergePolicy::DeepMerge`] for every kind: sqlc's columns are kept and
+    /// the parsed constraints and indexes are layered on top.
+    pub fn deep_merge_catalog(&mut self, sqlc: Catalog) -> MergeReport {
+        self.merge_catalog_with(sqlc, &MergePolicies::all(MergePolicy::DeepMerge))
+            .expect("deep merging never fails")
+    }
+}
+
+/// State of an ongoing merge
+struct Merger<'a> {
+    policies: &'a MergePolicies,
+    report: MergeReport,
+}
+
+impl Merger<'_> {
+    /// Record a conflict
+    fn conflict(
+        &mut self,
+        kind: ObjectKind,
+        object: impl Into<String>,
+        message: impl Into<String>,
+        resolution: Resolution,
+    ) {
+        self.report.conflicts.push(Conflict {
+            kind,
+            object: object.into(),
+            message: message.into(),
+            resolution,
+        });
+    }
+
+    /// Apply the policy of `kind` to an object existing on both sides
+    ///
+    /// Returns the policy to apply once the conflict has been recorded, or an
+    /// error for [`MergePolicy::Error`].
+    fn clash(&mut self, kind: ObjectKind, object: &str) -> Result<MergePolicy, MergeError> {
+        let policy = self.policies.get(kind);
+        let message = format!("{kind} exists in both catalogs");
+        match policy {
+            MergePolicy::KeepExisting => {
+                self.conflict(kind, object, message, Resolution::KeptExisting)
+            }
+            MergePolicy::Replace => self.conflict(kind, object, message, Resolution::Replaced),
+            MergePolicy::DeepMerge => {}
+            MergePolicy::Error => {
+                return Err(MergeError {
+                    kind,
+                    object: object.to_string(),
+                })
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Merge the `incoming` schema into the `existing` one
+    fn schema(&mut self, existing: &mut Schema, incoming: Schema) -> Result<(), MergeError> {
+        if existing.comment.is_empty() {
+            existing.comment = incoming.comment;
+        }
+
+        for table in incoming.tables {
+            let Some(rel) = &table.rel else { continue };
+            let name = rel.name.clone();
+            match existing
+                .tables
+                .iter_mut()
+                .find(|t| t.rel.as_ref().is_some_and(|r| r.name == name))
+            {
+                Some(current) if *current == table => {}
+                Some(current) => {
+                    let object = qualified(&existing.name, &name);
+                    match self.clash(ObjectKind::Table, &object)? {
+                        MergePolicy::Replace => *current = table,
+                        MergePolicy::DeepMerge => self.table(&object, current, table),
+                        _ => {}
+                    }
+                }
+                None => existing.tables.push(table),
+            }
+        }
+
+        for r#enum in incoming.enums {
+            match existing.enums.iter_mut().find(|e| e.name == r#enum.name) {
+                Some(current) if *current == r#enum => {}
+                Some(current) => {
+                    let object = qualified(&existing.name, &r#enum.name);
+                    match self.clash(ObjectKind::Enum, &object)? {
+                        MergePolicy::Replace => *current = r#enum,
+                        MergePolicy::DeepMerge => self.r#enum(&object, current, r#enum),
+                        _ => {}
+                    }
+                }
+                None => existing.enums.push(r#enum),
+            }
+        }
+
+        for composite in incoming.composite_types {
+            match existing
+                .composite_types
+                .iter_mut()
+                .find(|c| c.name == composite.name)
+            {
+                Some(current) if *current == composite => {}
+                Some(current) => {
+                    let object = qualified(&existing.name, &composite.name);
+                    match self.clash(ObjectKind::CompositeType, &object)? {
+                        MergePolicy::Replace => *current = composite,
+                        MergePolicy::DeepMerge => self.composite(&object, current, composite),
+                        _ => {}
+                    }
+                }
+                None => existing.composite_types.push(composite),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keep the incoming columns and layer the existing constraints on top
+    fn table(&mut self, object: &str, existing: &mut Table, incoming: Table) {
+        let kind = ObjectKind::Table;
+
+        for column in &existing.columns {
+            match incoming.columns.iter().find(|c| c.name == column.name) {
+                Some(other) if other.not_null != column.not_null => self.conflict(
+                    kind,
+                    format!("{object}.{}", column.name),
+                    format!(
+                        "nullability differs (existing: {}, incoming: {})",
+                        nullability(column.not_null),
+                        nullability(other.not_null)
+                    ),
+                    Resolution::Merged,
+                ),
+                Some(_) => {}
+                None => self.conflict(
+                    kind,
+                    format!("{object}.{}", column.name),
+                    "column is missing from the incoming catalog",
+                    Resolution::Merged,
+                ),
+            }
+        }
+        for column in &incoming.columns {
+            if !existing.columns.iter().any(|c| c.name == column.name) {
+                self.conflict(
+                    kind,
+                    format!("{object}.{}", column.name),
+                    "column is missing from the existing catalog",
+                    Resolution::Merged,
+                );
+            }
+        }
+
+        let parsed = std::mem::replace(existing, incoming);
+        if existing.comment.is_empty() {
+            existing.comment = parsed.comment;
+        }
+
+        match (&existing.primary_key, parsed.primary_key) {
+            (None, primary_key) => existing.primary_key = primary_key,
+            (Some(current), Some(other)) if current.columns != other.columns => {
+                let message = format!(
+                    "primary key differs (existing: {}, incoming: {})",
+                    other.columns.join(", "),
+                    current.columns.join(", ")
+                );
+                self.conflict(kind, object, message, Resolution::Merged);
+            }
+            _ => {}
+        }
+
+        for foreign_key in parsed.foreign_keys {
+            if !existing.foreign_keys.contains(&foreign_key) {
+                existing.foreign_keys.push(foreign_key);
+            }
+        }
+
+        for index in parsed.indexes {
+            match existing.indexes.iter().find(|i| i.name == index.name) {
+                Some(current) if *current != index => self.conflict(
+                    kind,
+                    format!("{object}.{}", index.name),
+                    "index is defined differently in both catalogs",
+                    Resolution::Merged,
+                ),
+                Some(_) => {}
+                None => existing.indexes.push(index),
+            }
+        }
+    }
+
+    /// Keep the incoming values followed by the existing values missing from them
+    fn r#enum(&mut self, object: &str, existing: &mut Enum, incoming: Enum) {
+        if existing.vals != incoming.vals {
+            let message = format!(
+                "enum values differ (existing: {}, incoming: {})",
+                existing.vals.join(", "),
+                incoming.vals.join(", ")
+            );
+            self.conflict(ObjectKind::Enum, object, message, Resolution::Merged);
+        }
+
+        let mut vals = incoming.vals;
+        for val in &existing.vals {
+            if !vals.contains(val) {
+                vals.push(val.clone());
+            }
+        }
+        existing.vals = vals;
+        if !incoming.comment.is_empty() {
+            existing.comment = incoming.comment;
+        }
+    }
+
+    /// Keep the incoming composite type, with the existing comment as fallback
+    fn composite(&mut self, object: &str, existing: &mut CompositeType, incoming: CompositeType) {
+        let message = "composite type differs";
+        self.conflict(
+            ObjectKind::CompositeType,
+            object,
+            message,
+            Resolution::Merged,
+        );
+        if !incoming.comment.is_empty() {
+            existing.comment = incoming.comment;
+        }
+    }
+}
+
+/// Name of an object qualified by its schema, when there is one
+fn qualified(schema: &str, name: &str) -> String {
+    if schema.is_empty() {
+        name.to_string()
+    } else {
+        format!("{schema}.{name}")
+    }
+}
+
+fn nullability(not_null: bool) -> &'static str {
+    if not_null {
+        "not null"
+    } else {
+        "nullable"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::{Column, Identifier, Index};
+
+    fn sqlc_table(schema: &str, name: &str, columns: Vec<Column>) -> Table {
+        Table {
+            rel: Some(Identifier {
+                schema: schema.to_string(),
+                name: name.to_string(),
+                ..Default::default()
+            }),
+            columns,
+            ..Default::default()
+        }
+    }
+
+    fn sqlc_catalog(tables: Vec<Table>) -> Catalog {
+        Catalog {
+            default_schema: "public".to_string(),
+            schemas: vec![Schema {
+                name: "public".to_string(),
+                tables,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn parsed(sql: &str) -> CatalogBuilder {
+        let mut builder = CatalogBuilder::new("postgresql");
+        builder.parse_sql(sql).unwrap();
+        builder
+    }
+
+    #[test]
+    fn test_merge_takes_catalog_name_and_comment() {
+        let mut builder = parsed("CREATE TABLE users (id INTEGER)");
+        builder.merge_catalog(Catalog {
+            name: "sqlc".to_string(),
+            comment: "from sqlc".to_string(),
+            ..sqlc_catalog(vec![])
+        });
+        let catalog = builder.build();
+        assert_eq!(catalog.name, "sqlc");
+        assert_eq!(catalog.comment, "from sqlc");
+
+        let mut builder = parsed("CREATE TABLE users (id INTEGER)").with_name("app");
+        builder.merge_catalog(Catalog {
+            name: "sqlc".to_string(),
+            ..sqlc_catalog(vec![])
+        });
+        assert_eq!(builder.build().name, "app");
+    }
+
+    #[test]
+    fn test_deep_merge_keeps_sqlc_columns() {
+        let mut builder = parsed(
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, tags TEXT[] NOT NULL);
+             CREATE INDEX users_tags_idx ON users (tags);",
+        );
+        let report = builder.deep_merge_catalog(sqlc_catalog(vec![sqlc_table(
+            "public",
+            "users",
+            vec![
+                Column::builder("id")
+                    .ty_in("pg_catalog", "int4")
+                    .not_null()
+                    .build(),
+                Column::builder("tags")
+                    .ty("text")
+                    .array()
+                    .not_null()
+                    .comment("labels")
+                    .build(),
+            ],
+        )]));
+        assert_eq!(report, MergeReport::default());
+
+        let catalog = builder.build();
+        assert_eq!(catalog.default_schema, "public");
+        assert_eq!(catalog.schemas.len(), 1);
+
+        let users = &catalog.schemas[0].tables[0];
+        assert_eq!(users.rel.as_ref().unwrap().schema, "public");
+        assert_eq!(
+            users.columns[0].r#type.as_ref().unwrap().schema,
+            "pg_catalog"
+        );
+        assert!(users.columns[1].is_array);
+        assert_eq!(users.columns[1].comment, "labels");
+        assert_eq!(users.primary_key.as_ref().unwrap().columns, vec!["id"]);
+        assert_eq!(users.indexes[0].name, "users_tags_idx");
+    }
+
+    #[test]
+    fn test_deep_merge_adds_tables_from_both_sides() {
+        let mut builder =
+            parsed("CREATE TABLE public.posts (id int); CREATE TABLE auth.accounts (id int)");
+        builder.deep_merge_catalog(sqlc_catalog(vec![sqlc_table(
+            "public",
+            "users",
+            vec![Column::builder("id").build()],
+        )]));
+
+        let catalog = builder.build();
+        let mut names: Vec<_> = catalog
+            .schemas
+            .iter()
+            .flat_map(|s| {
+                s.tables
+                    .iter()
+                    .map(move |t| qualified(&s.name, &t.rel.as_ref().unwrap().name))
+            })
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["auth.accounts", "public.posts", "public.users"]);
+    }
+
+    #[test]
+    fn test_deep_merge_matches_normalized_names() {
+        let mut builder = parsed(
+            r#"CREATE TABLE Public.Users (Id int, "Email" text, CONSTRAINT Users_PK PRIMARY KEY (Id));
+               CREATE TABLE "Posts" (id int);"#,
+        );
+        let report = builder.deep_merge_catalog(sqlc_catalog(vec![
+            sqlc_table(
+                "public",
+                "users",
+                vec![
+                    Column::builder("id").build(),
+                    Column::builder("Email").ty("text").build(),
+                ],
+            ),
+            sqlc_table("public", "Posts", vec![Column::builder("id").build()]),
+        ]));
+        assert_eq!(report, MergeReport::default());
+
+        let catalog = builder.build();
+        let tables = &catalog.schemas[0].tables;
+        let names: Vec<_> = tables
+            .iter()
+            .map(|t| t.rel.as_ref().unwrap().name.as_str())
+            .collect();
+        assert_eq!(names, ["users", "Posts"]);
+
+        let primary_key = tables[0].primary_key.as_ref().unwrap();
+        assert_eq!(primary_key.name, "users_pk");
+        assert_eq!(primary_key.columns, ["id"]);
+    }
+
+    #[test]
+    fn test_deep_merge_reports_conflicts() {
+        let mut builder = parsed(
+            "CREATE TABLE users (id int NOT NULL, legacy text);
+             CREATE UNIQUE INDEX users_email_idx ON users (id);",
+        );
+        let mut table = sqlc_table(
+            "public",
+            "users",
+            vec![
+                Column::builder("id").build(),
+                Column::builder("email").ty("text").build(),
+            ],
+        );
+        table.indexes.push(Index {
+            name: "users_email_idx".to_string(),
+            columns: vec!["email".to_string()],
+            unique: true,
+        });
+
+        let report = builder.deep_merge_catalog(sqlc_catalog(vec![table]));
+        let conflicts: Vec<_> = report.conflicts.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            conflicts,
+            vec![
+                "public.users.id: nullability differs (existing: not null, incoming: nullable) (merged)",
+                "public.users.legacy: column is missing from the incoming catalog (merged)",
+                "public.users.email: column is missing from the existing catalog (merged)",
+                "public.users.users_email_idx: index is defined differently in both catalogs (merged)",
+            ]
+        );
+
+        let catalog = builder.build();
+        let users = &catalog.schemas[0].tables[0];
+        assert_eq!(users.columns.len(), 2);
+        assert_eq!(users.indexes[0].columns, vec!["email"]);
+    }
+
+    #[test]
+    fn test_deep_merge_enums() {
+        let mut builder = CatalogBuilder::new("postgresql");
+        builder.schemas.insert(
+            "public".to_string(),
+            Schema {
+                name: "public".to_string(),
+                enums: vec![Enum {
+                    name: "mood".to_string(),
+                    vals: vec!["sad".to_string()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        );
+
+        let mut catalog = sqlc_catalog(vec![]);
+        catalog.schemas[0].enums.push(Enum {
+            name: "mood".to_string(),
+            vals: vec!["sad".to_string(), "happy".to_string()],
+            ..Default::default()
+        });
+
+        let report = builder.deep_merge_catalog(catalog);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].object, "public.mood");
+        assert_eq!(
+            builder.schemas["public"].enums[0].vals,
+            vec!["sad", "happy"]
+        );
+    }
+
+    fn enum_catalog(vals: &[&str]) -> Catalog {
+        Catalog {
+            default_schema: "public".to_string(),
+            schemas: vec![Schema {
+                name: "public".to_string(),
+                enums: vec![Enum {
+                    name: "mood".to_string(),
+                    vals: vals.iter().map(|v| v.to_string()).collect(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_policies_per_kind() {
+        let mut builder = parsed("CREATE TABLE users (id int)");
+        builder
+            .merge_catalog_with(enum_catalog(&["sad"]), &MergePolicies::default())
+            .unwrap();
+
+        let mut incoming = parsed("CREATE TABLE users (id int, name text)").build();
+        incoming.schemas[0].enums = enum_catalog(&["happy"]).schemas[0].enums.clone();
+
+        let policies = MergePolicies::default().with_enums(MergePolicy::Replace);
+        let report = builder.merge_catalog_with(incoming, &policies).unwrap();
+
+        let conflicts: Vec<_> = report.conflicts.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            conflicts,
+            vec![
+                "public.users: table exists in both catalogs (kept existing)",
+                "public.mood: enum exists in both catalogs (replaced)",
+            ]
+        );
+        assert_eq!(report.conflicts[1].kind, ObjectKind::Enum);
+
+        let schema = &builder.schemas["public"];
+        assert_eq!(schema.tables[0].columns.len(), 1);
+        assert_eq!(schema.enums[0].vals, vec!["happy"]);
+    }
+
+    #[test]
+    fn test_merge_policy_error_leaves_builder_unchanged() {
+        let mut builder = parsed("CREATE TABLE users (id int)");
+        builder
+            .merge_catalog_with(enum_catalog(&["sad"]), &MergePolicies::default())
+            .unwrap();
+        let before = builder.clone();
+
+        let mut incoming = parsed("CREATE TABLE posts (id int)").build();
+        incoming.schemas[0].enums = enum_catalog(&["happy"]).schemas[0].enums.clone();
+
+        let policies = MergePolicies::all(MergePolicy::Error);
+        let err = builder.merge_catalog_with(incoming, &policies).unwrap_err();
+        assert_eq!(
+            err,
+            MergeError {
+                kind: ObjectKind::Enum,
+                object: "public.mood".to_string(),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "enum public.mood is defined differently in both catalogs"
+        );
+        assert_eq!(builder, before);
+
+        // Identical objects are not conflicts, even with the error policy
+        let report = builder
+            .merge_catalog_with(enum_catalog(&["sad"]), &policies)
+            .unwrap();
+        assert!(report.conflicts.is_empty());
+    }
+}
@@ -0,0 +1,270 @@
+//! Typed decoding of plugin options.
+//!
+//! sqlc forwards the JSON value of a plugin's `codegen.options` key as raw
+//! bytes in [`GenerateRequest::plugin_options`], and the top-level `options`
+//! key as [`GenerateRequest::global_options`]. This module decodes those bytes
+//! into user-defined types with serde.
+//!
+//! Decoding is strict:
+//! - Empty options are treated as `{}`, so `#[serde(default)]` fields are filled in
+//! - Keys that the target type does not know about are rejected
+//! - Errors report the JSON path of the offending value (e.g. `emit.derive[1]`)
+//!
+//! # Example
+//!
+//! ```
+//! use serde::Deserialize;
+//! use sqlc_gen_core::options::decode_options;
+//!
+//! #[derive(Debug, Deserialize, Default)]
+//! #[serde(default)]
+//! struct Opts {
+//!     package: String,
+//!     emit_json_tags: bool,
+//! }
+//!
+//! let opts: Opts = decode_options("plugin_options", br#"{"package": "db"}"#).unwrap();
+//! assert_eq!(opts.package, "db");
+//! assert!(!opts.emit_json_tags);
+//!
+//! let err = decode_options::<Opts>("plugin_options", br#"{"pakage": "db"}"#).unwrap_err();
+//! assert_eq!(err.path, "pakage");
+//! ```
+
+use crate::plugin::GenerateRequest;
+use serde::de::DeserializeOwned;
+use std::error::Error;
+use std::fmt;
+
+/// Error produced when plugin or global options cannot be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionsError {
+    /// Request field the options were read from (`plugin_options` or `global_options`)
+    pub field: &'static str,
+
+    /// JSON path of the offending value
+    ///
+    /// Empty when the error applies to the document as a whole, for example
+    /// when the bytes are not valid JSON.
+    pub path: String,
+
+    /// Human readable description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "invalid {}: {}", self.field, self.message)
+        } else {
+            write!(
+                f,
+                "invalid {} at `{}`: {}",
+                self.field, self.path, self.message
+            )
+        }
+    }
+}
+
+impl Error for OptionsError {}
+
+/// Plugin options paired with the global options of the sqlc configuration.
+///
+/// Use this as the options type of [`crate::runtime::run_typed`] when a plugin
+/// needs both `codegen.options` and the top-level `options` of `sqlc.yaml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options<P, G> {
+    /// Options decoded from [`GenerateRequest::plugin_options`]
+    pub plugin: P,
+
+    /// Options decoded from [`GenerateRequest::global_options`]
+    pub global: G,
+}
+
+/// Types that can be decoded from the options carried by a [`GenerateRequest`].
+///
+/// Every `serde` deserializable type decodes from `plugin_options`, while
+/// [`Options`] decodes both `plugin_options` and `global_options`.
+pub trait DecodeOptions: Sized {
+    /// Decode the options from the request
+    fn decode_options(request: &GenerateRequest) -> Result<Self, OptionsError>;
+}
+
+impl<T: DeserializeOwned> DecodeOptions for T {
+    fn decode_options(request: &GenerateRequest) -> Result<Self, OptionsError> {
+        decode_options("plugin_options", &request.plugin_options)
+    }
+}
+
+impl<P: DeserializeOwned, G: DeserializeOwned> DecodeOptions for Options<P, G> {
+    fn decode_options(request: &GenerateRequest) -> Result<Self, OptionsError> {
+        Ok(Self {
+            plugin: decode_options("plugin_options", &request.plugin_options)?,
+            global: decode_options("global_options", &request.global_options)?,
+        })
+    }
+}
+
+/// Decode JSON option bytes into `T`, rejecting unknown keys.
+///
+/// `field` names the request field the bytes came from and is only used for
+/// error reporting. Empty (or whitespace only) input is decoded as `{}`.
+pub fn decode_options<T: DeserializeOwned>(
+    field: &'static str,
+    bytes: &[u8],
+) -> Result<T, OptionsError> {
+    let bytes = if bytes.iter().all(u8::is_ascii_whitespace) {
+        b"{}".as_slice()
+    } else {
+        bytes
+    };
+
+    let mut unknown = Vec::new();
+    let mut track_unknown = |path: serde_ignored::Path| unknown.push(path.to_string());
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let ignored = serde_ignored::Deserializer::new(&mut deserializer, &mut track_unknown);
+
+    let value: T = serde_path_to_error::deserialize(ignored).map_err(|err| OptionsError {
+        field,
+        path: normalize_path(err.path().to_string()),
+        message: err.into_inner().to_string(),
+    })?;
+
+    deserializer.end().map_err(|err| OptionsError {
+        field,
+        path: String::new(),
+        message: err.to_string(),
+    })?;
+
+    if let Some(path) = unknown.into_iter().next() {
+        return Err(OptionsError {
+            field,
+            message: format!("unknown key `{}`", last_segment(&path)),
+            path,
+        });
+    }
+
+    Ok(value)
+}
+
+/// `serde_path_to_error` renders the root path as `.`
+fn normalize_path(path: String) -> String {
+    if path == "." {
+        String::new()
+    } else {
+        path
+    }
+}
+
+/// Return the key name at the end of a JSON path (e.g. `emit.derive` -> `derive`)
+fn last_segment(path: &str) -> &str {
+    path.rsplit('.').next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Default, Deserialize, PartialEq)]
+    #[serde(default)]
+    struct Emit {
+        json_tags: bool,
+        derive: Vec<String>,
+    }
+
+    #[derive(Debug, Default, Deserialize, PartialEq)]
+    #[serde(default)]
+    struct PluginOpts {
+        package: String,
+        emit: Emit,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Required {
+        package: String,
+    }
+
+    #[test]
+    fn test_decode_options_empty_uses_defaults() {
+        let opts: PluginOpts = decode_options("plugin_options", b"").unwrap();
+        assert_eq!(opts, PluginOpts::default());
+
+        let opts: PluginOpts = decode_options("plugin_options", b"  \n").unwrap();
+        assert_eq!(opts, PluginOpts::default());
+    }
+
+    #[test]
+    fn test_decode_options_nested() {
+        let json = br#"{"package": "db", "emit": {"json_tags": true, "derive": ["Debug"]}}"#;
+        let opts: PluginOpts = decode_options("plugin_options", json).unwrap();
+        assert_eq!(opts.package, "db");
+        assert!(opts.emit.json_tags);
+        assert_eq!(opts.emit.derive, vec!["Debug"]);
+    }
+
+    #[test]
+    fn test_decode_options_unknown_key() {
+        let json = br#"{"emit": {"json_tag": true}}"#;
+        let err = decode_options::<PluginOpts>("plugin_options", json).unwrap_err();
+        assert_eq!(err.field, "plugin_options");
+        assert_eq!(err.path, "emit.json_tag");
+        assert_eq!(
+            err.to_string(),
+            "invalid plugin_options at `emit.json_tag`: unknown key `json_tag`"
+        );
+    }
+
+    #[test]
+    fn test_decode_options_type_error_path() {
+        let json = br#"{"emit": {"derive": ["Debug", 1]}}"#;
+        let err = decode_options::<PluginOpts>("global_options", json).unwrap_err();
+        assert_eq!(err.field, "global_options");
+        assert_eq!(err.path, "emit.derive[1]");
+        assert!(err.message.contains("invalid type"));
+    }
+
+    #[test]
+    fn test_decode_options_missing_required_field() {
+        let err = decode_options::<Required>("plugin_options", b"").unwrap_err();
+        assert!(err.path.is_empty());
+        assert!(err.message.contains("missing field `package`"));
+    }
+
+    #[test]
+    fn test_decode_options_invalid_json() {
+        let err = decode_options::<PluginOpts>("plugin_options", b"{not json").unwrap_err();
+        assert!(err.to_string().starts_with("invalid plugin_options"));
+    }
+
+    #[test]
+    fn test_decode_options_trailing_characters() {
+        let err = decode_options::<PluginOpts>("plugin_options", b"{} {}").unwrap_err();
+        assert!(err.message.contains("trailing characters"));
+    }
+
+    #[test]
+    fn test_options_decodes_plugin_and_global() {
+        let request = GenerateRequest {
+            plugin_options: br#"{"package": "db"}"#.to_vec(),
+            global_options: br#"{"package": "shared"}"#.to_vec(),
+            ..Default::default()
+        };
+
+        let opts = Options::<PluginOpts, Required>::decode_options(&request).unwrap();
+        assert_eq!(opts.plugin.package, "db");
+        assert_eq!(opts.global.package, "shared");
+    }
+
+    #[test]
+    fn test_options_reports_global_field() {
+        let request = GenerateRequest {
+            global_options: br#"{"unknown": 1}"#.to_vec(),
+            ..Default::default()
+        };
+
+        let err = Options::<PluginOpts, PluginOpts>::decode_options(&request).unwrap_err();
+        assert_eq!(err.field, "global_options");
+        assert_eq!(err.path, "unknown");
+    }
+}
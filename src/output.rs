@@ -0,0 +1,715 @@
+//! Writing generated files to disk outside of sqlc.
+//!
+//! sqlc normally writes the [`File`]s returned by a plugin under the
+//! `codegen.out` directory of its configuration. When iterating on a plugin
+//! without sqlc, [`write_files`] does the same, with two extra modes:
+//!
+//! - [`Mode::DryRun`] only reports what would be created, updated or deleted
+//! - [`Mode::Check`] fails with [`Error::Outdated`] if the files on disk differ
+//!   from the generated ones, which makes it usable in CI
+//!
+//! With [`WriteOptions::delete_stale`], files under the output directory that
+//! were not generated this time but carry [`WriteOptions::marker`] are
+//! deleted. Files without the marker are never touched. There is no default
+//! marker: sqlc's [`GENERATED_MARKER`] is shared by every generator, so the
+//! marker has to be one only this plugin writes, such as
+//! `Code generated by sqlc-gen-rust`.
+//!
+//! File names are checked with [`normalize_paths`] first, so a plugin bug
+//! cannot write outside of the output directory. The runtime applies the same
+//! check to every response before encoding it.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sqlc_gen_core::output::{write_files, Mode, WriteOptions};
+//! use sqlc_gen_core::plugin::File;
+//!
+//! let files = vec![File {
+//!     name: "models.rs".to_string(),
+//!     contents: b"// Code generated by sqlc. DO NOT EDIT.\n".to_vec(),
+//! }];
+//!
+//! let options = WriteOptions::default().with_mode(Mode::DryRun);
+//! let report = write_files("src/db".as_ref(), &files, &options).unwrap();
+//! print!("{report}");
+//! ```
+
+use crate::plugin::{File, GenerateRequest};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Marker identifying generated files, following the Go convention sqlc uses
+///
+/// Every sqlc generator writes it, so it does not tell which plugin generated
+/// a file and is not suitable as [`WriteOptions::marker`] when other generators
+/// share the output directory.
+pub const GENERATED_MARKER: &str = "Code generated by sqlc. DO NOT EDIT.";
+
+/// Number of bytes at the start of a file searched for the generated-file marker
+const MARKER_WINDOW: usize = 1024;
+
+/// What [`write_files`] does with the generated files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Write the files to disk
+    #[default]
+    Write,
+
+    /// Report the changes without touching the disk
+    DryRun,
+
+    /// Report the changes and fail if there are any
+    Check,
+}
+
+/// Options controlling [`write_files`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Whether to write, only report or check the files
+    pub mode: Mode,
+
+    /// Delete files carrying [`marker`](Self::marker) that were not generated
+    pub delete_stale: bool,
+
+    /// Text identifying the files of this plugin, searched in the first
+    /// kilobyte of a file
+    ///
+    /// Required by [`delete_stale`](Self::delete_stale).
+    pub marker: Option<String>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Write,
+            delete_stale: false,
+            marker: None,
+        }
+    }
+}
+
+impl WriteOptions {
+    /// Set the mode
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Delete stale generated files
+    pub fn with_delete_stale(mut self, delete_stale: bool) -> Self {
+        self.delete_stale = delete_stale;
+        self
+    }
+
+    /// Identify the files of this plugin by `marker`
+    pub fn with_marker(mut self, marker: impl Into<String>) -> Self {
+        self.marker = Some(marker.into());
+        self
+    }
+}
+
+/// Kind of change made (or needed) to a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChangeKind {
+    /// The file does not exist yet
+    Create,
+    /// The file exists with different contents
+    Update,
+    /// The file is a stale generated file
+    Delete,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Create => write!(f, "create"),
+            Self::Update => write!(f, "update"),
+            Self::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+/// A change made (or needed) to a file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Change {
+    /// Kind of change
+    pub kind: ChangeKind,
+    /// Path of the file, including the output directory
+    pub path: PathBuf,
+}
+
+/// Changes made (or needed) by [`write_files`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Created, updated and deleted files, in path order
+    pub changes: Vec<Change>,
+
+    /// Number of generated files already up to date
+    pub unchanged: usize,
+}
+
+impl Report {
+    /// Check whether the files on disk already match the generated files
+    pub fn is_up_to_date(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{} {}", change.kind, change.path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Error produced while writing generated files.
+#[derive(Debug)]
+pub enum Error {
+    /// A file or directory could not be read, written or deleted
+    Io {
+        /// Path of the file or directory
+        path: PathBuf,
+        /// Underlying I/O error
+        source: io::Error,
+    },
+
+    /// In [`Mode::Check`], the files on disk differ from the generated files
+    Outdated(Report),
+
+    /// Some generated file names are not safe to write
+    InvalidPaths(InvalidPaths),
+
+    /// [`WriteOptions::delete_stale`] is set without a [`WriteOptions::marker`]
+    MissingMarker,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Outdated(report) => {
+                write!(f, "generated files are out of date:\n{report}")
+            }
+            Self::InvalidPaths(err) => write!(f, "{err}"),
+            Self::MissingMarker => {
+                write!(f, "deleting stale files requires a marker identifying them")
+            }
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Outdated(_) | Self::MissingMarker => None,
+            Self::InvalidPaths(err) => Some(err),
+        }
+    }
+}
+
+/// Problem found in a generated file name by [`normalize_paths`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathIssue {
+    /// The name is empty or only made of separators and `.` segments
+    Empty,
+    /// The name is an absolute path or starts with a drive letter
+    Absolute,
+    /// The name contains a `..` segment
+    ParentSegment,
+    /// The name contains a NUL or another control character
+    ControlCharacter,
+    /// Another file normalizes to the same name
+    Duplicate,
+}
+
+impl fmt::Display for PathIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty file name"),
+            Self::Absolute => write!(f, "absolute path"),
+            Self::ParentSegment => write!(f, "`..` escapes the output directory"),
+            Self::ControlCharacter => write!(f, "control character in file name"),
+            Self::Duplicate => write!(f, "duplicate file name"),
+        }
+    }
+}
+
+/// A generated file name rejected by [`normalize_paths`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPath {
+    /// File name as returned by the plugin
+    pub name: String,
+    /// Why the name was rejected
+    pub issue: PathIssue,
+}
+
+/// Error listing every file name rejected by [`normalize_paths`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidPaths(pub Vec<InvalidPath>);
+
+impl fmt::Display for InvalidPaths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid generated file paths:")?;
+        for path in &self.0 {
+            write!(f, "\n  {:?}: {}", path.name, path.issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl StdError for InvalidPaths {}
+
+/// Check and normalize the names of generated files.
+///
+/// sqlc joins each name to the output directory, so names must be relative
+/// paths staying below it. Backslashes are turned into `/`, and empty and `.`
+/// segments are removed, so `./db\\models.rs` becomes `db/models.rs`.
+///
+/// # Errors
+///
+/// Returns every offending name if any of them is empty, absolute, contains
+/// a `..` segment or a control character, or normalizes to the same name as
+/// another file. The files are left unchanged in that case.
+///
+/// # Example
+///
+/// ```
+/// use sqlc_gen_core::output::{normalize_paths, PathIssue};
+/// use sqlc_gen_core::plugin::File;
+///
+/// let file = |name: &str| File { name: name.to_string(), contents: vec![] };
+///
+/// let mut files = vec![file("./db\\models.rs")];
+/// normalize_paths(&mut files).unwrap();
+/// assert_eq!(files[0].name, "db/models.rs");
+///
+/// let mut files = vec![file("../models.rs")];
+/// let err = normalize_paths(&mut files).unwrap_err();
+/// assert_eq!(err.0[0].issue, PathIssue::ParentSegment);
+/// ```
+pub fn normalize_paths(files: &mut [File]) -> Result<(), InvalidPaths> {
+    let names = check_paths(files)?;
+    for (file, name) in files.iter_mut().zip(names) {
+        file.name = name;
+    }
+    Ok(())
+}
+
+/// Normalized names of `files`, in the same order
+fn check_paths(files: &[File]) -> Result<Vec<String>, InvalidPaths> {
+    let mut invalid = Vec::new();
+    let mut names = Vec::with_capacity(files.len());
+    let mut originals: BTreeMap<String, Vec<&String>> = BTreeMap::new();
+
+    for file in files {
+        match normalize_path(&file.name) {
+            Ok(name) => {
+                originals.entry(name.clone()).or_default().push(&file.name);
+                names.push(name);
+            }
+            Err(issue) => invalid.push(InvalidPath {
+                name: file.name.clone(),
+                issue,
+            }),
+        }
+    }
+
+    for duplicates in originals.into_values().filter(|names| names.len() > 1) {
+        invalid.extend(duplicates.into_iter().map(|name| InvalidPath {
+            name: name.clone(),
+            issue: PathIssue::Duplicate,
+        }));
+    }
+
+    if invalid.is_empty() {
+        Ok(names)
+    } else {
+        Err(InvalidPaths(invalid))
+    }
+}
+
+/// Normalize a single file name
+fn normalize_path(name: &str) -> Result<String, PathIssue> {
+    if name.chars().any(char::is_control) {
+        return Err(PathIssue::ControlCharacter);
+    }
+
+    let name = name.replace('\\', "/");
+    let bytes = name.as_bytes();
+    if name.starts_with('/')
+        || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+    {
+        return Err(PathIssue::Absolute);
+    }
+
+    let mut segments = Vec::new();
+    for segment in name.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return Err(PathIssue::ParentSegment),
+            segment => segments.push(segment),
+        }
+    }
+
+    if segments.is_empty() {
+        return Err(PathIssue::Empty);
+    }
+    Ok(segments.join("/"))
+}
+
+/// Output directory configured for the plugin (`Settings.codegen.out`)
+///
+/// Returns `None` when the request does not configure one.
+pub fn out_dir(request: &GenerateRequest) -> Option<PathBuf> {
+    request
+        .settings
+        .as_ref()?
+        .codegen
+        .as_ref()
+        .map(|codegen| codegen.out.as_str())
+        .filter(|out| !out.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Write generated files under `out_dir`, or report the changes it would make.
+///
+/// Files whose contents already match are left untouched, so their
+/// modification time is preserved.
+///
+/// # Errors
+///
+/// Returns [`Error::MissingMarker`] if stale files are to be deleted without a
+/// marker, [`Error::InvalidPaths`] if a file name is rejected by
+/// [`normalize_paths`], [`Error::Io`] if a file cannot be read, written or
+/// deleted, and [`Error::Outdated`] in [`Mode::Check`] if any change is needed.
+pub fn write_files(
+    out_dir: &Path,
+    files: &[File],
+    options: &WriteOptions,
+) -> Result<Report, Error> {
+    let stale_marker = match &options.marker {
+        Some(marker) if options.delete_stale && !marker.is_empty() => Some(marker.as_str()),
+        _ if options.delete_stale => return Err(Error::MissingMarker),
+        _ => None,
+    };
+
+    let mut report = Report::default();
+    let mut generated = BTreeSet::new();
+    let names = check_paths(files).map_err(Error::InvalidPaths)?;
+
+    for (file, name) in files.iter().zip(names) {
+        let path = out_dir.join(name);
+        generated.insert(path.clone());
+
+        let kind = match std::fs::read(&path) {
+            Ok(existing) if existing == file.contents => {
+                report.unchanged += 1;
+                continue;
+            }
+            Ok(_) => ChangeKind::Update,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => ChangeKind::Create,
+            Err(source) => return Err(Error::Io { path, source }),
+        };
+
+        if options.mode == Mode::Write {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|source| Error::Io {
+                    path: parent.to_path_buf(),
+                    source,
+                })?;
+            }
+            std::fs::write(&path, &file.contents).map_err(|source| Error::Io {
+                path: path.clone(),
+                source,
+            })?;
+        }
+
+        report.changes.push(Change { kind, path });
+    }
+
+    if let Some(marker) = stale_marker {
+        for path in list_files(out_dir)? {
+            if generated.contains(&path) || !has_marker(&path, marker)? {
+                continue;
+            }
+
+            if options.mode == Mode::Write {
+                std::fs::remove_file(&path).map_err(|source| Error::Io {
+                    path: path.clone(),
+                    source,
+                })?;
+            }
+
+            report.changes.push(Change {
+                kind: ChangeKind::Delete,
+                path,
+            });
+        }
+    }
+
+    report.changes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    if options.mode == Mode::Check && !report.is_up_to_date() {
+        return Err(Error::Outdated(report));
+    }
+
+    Ok(report)
+}
+
+/// List the files below `dir`, recursively
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(source) => return Err(Error::Io { path: dir, source }),
+        };
+
+        for entry in entries {
+            let entry = entry.map_err(|source| Error::Io {
+                path: dir.clone(),
+                source,
+            })?;
+            let file_type = entry.file_type().map_err(|source| Error::Io {
+                path: entry.path(),
+                source,
+            })?;
+
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Check whether the start of a file contains the generated-file marker
+fn has_marker(path: &Path, marker: &str) -> Result<bool, Error> {
+    use std::io::Read;
+
+    let mut head = Vec::with_capacity(MARKER_WINDOW);
+    std::fs::File::open(path)
+        .and_then(|file| file.take(MARKER_WINDOW as u64).read_to_end(&mut head))
+        .map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+    Ok(String::from_utf8_lossy(&head).contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::{Codegen, Settings};
+
+    fn file(name: &str, contents: &str) -> File {
+        File {
+            name: name.to_string(),
+            contents: contents.as_bytes().to_vec(),
+        }
+    }
+
+    /// Marker of the files of the plugin under test
+    const MARKER: &str = "Code generated by sqlc-gen-test. DO NOT EDIT.";
+
+    fn generated(body: &str) -> String {
+        format!("// {MARKER}\n{body}")
+    }
+
+    fn changes(report: &Report, root: &Path) -> Vec<String> {
+        report
+            .changes
+            .iter()
+            .map(|c| {
+                format!(
+                    "{} {}",
+                    c.kind,
+                    c.path.strip_prefix(root).unwrap().display()
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_write_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("same.rs"), generated("same")).unwrap();
+        std::fs::write(dir.path().join("old.rs"), generated("old")).unwrap();
+
+        let files = vec![
+            file("same.rs", &generated("same")),
+            file("old.rs", &generated("new")),
+            file("nested/new.rs", &generated("new")),
+        ];
+
+        let report = write_files(dir.path(), &files, &WriteOptions::default()).unwrap();
+        assert_eq!(
+            changes(&report, dir.path()),
+            vec!["create nested/new.rs", "update old.rs"]
+        );
+        assert_eq!(report.unchanged, 1);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("old.rs")).unwrap(),
+            generated("new")
+        );
+
+        let report = write_files(dir.path(), &files, &WriteOptions::default()).unwrap();
+        assert!(report.is_up_to_date());
+    }
+
+    #[test]
+    fn test_dry_run_leaves_disk_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("stale.rs"), generated("stale")).unwrap();
+
+        let options = WriteOptions::default()
+            .with_mode(Mode::DryRun)
+            .with_delete_stale(true)
+            .with_marker(MARKER);
+        let report = write_files(dir.path(), &[file("new.rs", "new")], &options).unwrap();
+
+        assert_eq!(
+            changes(&report, dir.path()),
+            vec!["create new.rs", "delete stale.rs"]
+        );
+        assert!(!dir.path().join("new.rs").exists());
+        assert!(dir.path().join("stale.rs").exists());
+        assert!(report.to_string().contains("delete "));
+    }
+
+    #[test]
+    fn test_check_fails_when_outdated() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("models.rs"), "edited by hand").unwrap();
+
+        let options = WriteOptions::default().with_mode(Mode::Check);
+        let err = write_files(dir.path(), &[file("models.rs", "generated")], &options).unwrap_err();
+
+        let Error::Outdated(report) = &err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(changes(report, dir.path()), vec!["update models.rs"]);
+        assert!(err
+            .to_string()
+            .starts_with("generated files are out of date"));
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("models.rs")).unwrap(),
+            "edited by hand"
+        );
+
+        std::fs::write(dir.path().join("models.rs"), "generated").unwrap();
+        write_files(dir.path(), &[file("models.rs", "generated")], &options).unwrap();
+    }
+
+    #[test]
+    fn test_delete_stale_requires_marker() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/stale.rs"), generated("stale")).unwrap();
+        std::fs::write(dir.path().join("handwritten.rs"), "fn main() {}").unwrap();
+        std::fs::write(
+            dir.path().join("other.go"),
+            format!("// {GENERATED_MARKER}\npackage db"),
+        )
+        .unwrap();
+
+        let options = WriteOptions::default().with_delete_stale(true);
+        let err = write_files(dir.path(), &[], &options).unwrap_err();
+        assert!(matches!(err, Error::MissingMarker));
+        assert!(dir.path().join("sub/stale.rs").exists());
+
+        let options = options.with_marker(MARKER);
+        let report = write_files(dir.path(), &[], &options).unwrap();
+        assert_eq!(changes(&report, dir.path()), vec!["delete sub/stale.rs"]);
+        assert!(!dir.path().join("sub/stale.rs").exists());
+        assert!(dir.path().join("handwritten.rs").exists());
+        assert!(dir.path().join("other.go").exists());
+    }
+
+    #[test]
+    fn test_normalize_paths() {
+        let mut files = vec![
+            file("models.rs", ""),
+            file("./db//queries.rs", ""),
+            file("db\\nested\\types.rs", ""),
+        ];
+        normalize_paths(&mut files).unwrap();
+
+        let names: Vec<_> = files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["models.rs", "db/queries.rs", "db/nested/types.rs"]
+        );
+    }
+
+    #[test]
+    fn test_normalize_paths_rejects_invalid_names() {
+        let mut files = vec![
+            file("ok.rs", ""),
+            file("/etc/passwd", ""),
+            file("C:\\models.rs", ""),
+            file("db/../../models.rs", ""),
+            file("models\0.rs", ""),
+            file("./", ""),
+            file("db/a.rs", ""),
+            file("db\\a.rs", ""),
+        ];
+        let err = normalize_paths(&mut files).unwrap_err();
+
+        let issues: Vec<_> = err.0.iter().map(|p| (p.name.as_str(), p.issue)).collect();
+        assert_eq!(
+            issues,
+            vec![
+                ("/etc/passwd", PathIssue::Absolute),
+                ("C:\\models.rs", PathIssue::Absolute),
+                ("db/../../models.rs", PathIssue::ParentSegment),
+                ("models\0.rs", PathIssue::ControlCharacter),
+                ("./", PathIssue::Empty),
+                ("db/a.rs", PathIssue::Duplicate),
+                ("db\\a.rs", PathIssue::Duplicate),
+            ]
+        );
+        assert_eq!(files[7].name, "db\\a.rs");
+        assert!(err.to_string().contains("\"/etc/passwd\": absolute path"));
+    }
+
+    #[test]
+    fn test_write_files_rejects_invalid_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+
+        let err =
+            write_files(&out, &[file("../escape.rs", "")], &WriteOptions::default()).unwrap_err();
+        assert!(matches!(err, Error::InvalidPaths(_)));
+        assert!(!dir.path().join("escape.rs").exists());
+    }
+
+    #[test]
+    fn test_out_dir() {
+        let mut request = GenerateRequest::default();
+        assert_eq!(out_dir(&request), None);
+
+        request.settings = Some(Settings {
+            codegen: Some(Codegen {
+                out: "src/db".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert_eq!(out_dir(&request), Some(PathBuf::from("src/db")));
+    }
+}
@@ -10,17 +10,19 @@
 //! The runtime handles:
 //! - Reading and decoding protobuf messages from stdin
 //! - Invoking user-defined code generation logic
+//! - Checking and normalizing the file names of responses
 //! - Encoding and writing responses back to stdout
-//! - Error propagation and handling
+//! - Error propagation and handling through the staged [`Error`] type
 //!
 //! # Example
 //!
 //! ```no_run
 //! use sqlc_gen_core::plugin::{GenerateRequest, GenerateResponse, File};
-//! use sqlc_gen_core::runtime::run;
+//! use sqlc_gen_core::runtime::{report, run};
+//! use std::process::ExitCode;
 //!
-//! fn main() -> Result<(), Box<dyn std::error::Error>> {
-//!     run(|request| {
+//! fn main() -> ExitCode {
+//!     report(run(|request| {
 //!         // Your code generation logic here
 //!         let files = vec![File {
 //!             name: "output.rs".to_string(),
@@ -28,15 +30,305 @@
 //!         }];
 //!         
 //!         Ok(GenerateResponse { files })
-//!     })
+//!     }))
 //! }
 //! ```
+//!
+//! # Error reporting
+//!
+//! [`run`], [`run_typed`] and [`run_plugin`] turn a panic of the processing
+//! code into [`Error::Panic`] instead of letting Rust print a raw panic
+//! message. [`report`] prints any error on stderr as shown below and returns
+//! the exit code of its [`Error::exit_code`]:
+//!
+//! ```text
+//! error: failed to parse schema/001_users.sql: sql parser error: Expected ...
+//!   stage: parse schema
+//!   at: schema/001_users.sql:3:15
+//!   sql: CREATE TABLE users (id INTEGER PRIMARY KEY,, name TEXT)
+//!   hint: check the statement against the SQL dialect of the configured engine
+//! ```
+//!
+//! | Exit code | Meaning                                                     |
+//! |-----------|-------------------------------------------------------------|
+//! | 0         | Success                                                     |
+//! | 1         | The processing code returned an error or an invalid response |
+//! | 2         | The request or the plugin options could not be decoded      |
+//! | 3         | A schema file could not be read or parsed                   |
+//! | 4         | Reading, writing or capturing a message failed              |
+//! | 101       | The processing code panicked                                |
+//!
+//! Setting [`BACKTRACE_ENV`] (`SQLC_GEN_BACKTRACE`) to a value other than `0`
+//! adds the backtrace of the panic to the report.
 
-use crate::plugin::{GenerateRequest, GenerateResponse};
+use crate::composite::CompositeAttributes;
+use crate::fs::{FileSystem, StdFileSystem};
+use crate::loader::SchemaLoader;
+use crate::log::{self, Level, Logger};
+use crate::options::DecodeOptions;
+use crate::output::normalize_paths;
+use crate::plugin::{Catalog, GenerateRequest, GenerateResponse, Query, Schema, Table};
 use crate::schema::CatalogBuilder;
 use prost::Message;
-use std::error::Error;
+use std::cell::RefCell;
+use std::error::Error as StdError;
+use std::fmt;
 use std::io::{Read, Write};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+/// Error produced while running a plugin.
+///
+/// Each variant corresponds to one stage of the plugin protocol, so callers
+/// can report actionable messages or map stages to distinct exit codes.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the request or writing the response failed
+    Io(std::io::Error),
+
+    /// The request is not a valid protobuf-encoded [`GenerateRequest`]
+    Decode(prost::DecodeError),
+
+    /// A file listed in `Settings.schema` could not be read
+    SchemaRead {
+        /// Path of the schema file
+        path: PathBuf,
+        /// Base directory relative entries were resolved against, `None` for
+        /// the working directory
+        base_dir: Option<PathBuf>,
+        /// Underlying I/O error
+        source: std::io::Error,
+    },
+
+    /// A `Settings.schema` entry resolves outside of the base directory
+    SchemaPath {
+        /// Schema entry as listed in the request
+        path: PathBuf,
+        /// Directory schema entries must stay within
+        base_dir: PathBuf,
+    },
+
+    /// A file listed in `Settings.schema` could not be parsed
+    SqlParse {
+        /// Path of the schema file
+        path: PathBuf,
+        /// Parse error, including the location and text of the failing statement
+        source: crate::schema::Error,
+    },
+
+    /// The plugin options could not be decoded
+    Options(crate::options::OptionsError),
+
+    /// The processing function or a plugin hook returned an error
+    Processor(Box<dyn StdError>),
+
+    /// The response contains file names that are not safe to write
+    ///
+    /// See [`crate::output::normalize_paths`] and [`RunOptions::validate_paths`].
+    InvalidPaths(crate::output::InvalidPaths),
+
+    /// The response could not be encoded
+    Encode(prost::EncodeError),
+
+    /// A captured request could not be read back by [`replay`]
+    Capture {
+        /// Path of the capture file
+        path: PathBuf,
+        /// Underlying I/O error
+        source: std::io::Error,
+    },
+
+    /// The processing function or a plugin hook panicked
+    Panic {
+        /// Panic message
+        message: String,
+        /// Source location of the panic, when known
+        location: Option<String>,
+        /// Backtrace of the panic, captured when [`BACKTRACE_ENV`] is set
+        backtrace: Option<String>,
+    },
+}
+
+impl Error {
+    /// Stage of the plugin protocol the error happened in
+    pub fn stage(&self) -> &'static str {
+        match self {
+            Self::Io(_) => "io",
+            Self::Decode(_) => "decode request",
+            Self::SchemaRead { .. } | Self::SchemaPath { .. } => "load schema",
+            Self::SqlParse { .. } => "parse schema",
+            Self::Options(_) => "decode options",
+            Self::Processor(_) | Self::Panic { .. } => "generate",
+            Self::InvalidPaths(_) => "check response",
+            Self::Encode(_) => "encode response",
+            Self::Capture { .. } => "capture",
+        }
+    }
+
+    /// Process exit code reported for the error, see the [module docs](self)
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Processor(_) | Self::InvalidPaths(_) => 1,
+            Self::Decode(_) | Self::Options(_) => 2,
+            Self::SchemaRead { .. } | Self::SchemaPath { .. } | Self::SqlParse { .. } => 3,
+            Self::Io(_) | Self::Encode(_) | Self::Capture { .. } => 4,
+            Self::Panic { .. } => 101,
+        }
+    }
+
+    /// Suggestion on how to fix the error, when there is a common cause
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::Decode(_) => Some(
+                "the plugin expects a GenerateRequest on stdin; run it through sqlc or replay a request captured with SQLC_GEN_CAPTURE",
+            ),
+            Self::SchemaRead { base_dir: None, .. } => Some(
+                "schema paths are resolved against the working directory; set SQLC_GEN_BASE_DIR to the directory of sqlc.yaml",
+            ),
+            Self::SchemaRead {
+                base_dir: Some(_), ..
+            } => Some("schema paths are resolved against the base directory; check that it is the directory of sqlc.yaml"),
+            Self::SchemaPath { .. } => {
+                Some("schema entries must stay inside the directory set by SQLC_GEN_BASE_DIR")
+            }
+            Self::SqlParse { .. } => {
+                Some("check the statement against the SQL dialect of the configured engine")
+            }
+            Self::Options(_) => Some("check the plugin options in sqlc.yaml"),
+            Self::InvalidPaths(_) => {
+                Some("generated file names must be relative paths inside the output directory")
+            }
+            Self::Panic {
+                backtrace: None, ..
+            } => Some("this is a bug in the plugin; set SQLC_GEN_BACKTRACE=1 to print a backtrace"),
+            _ => None,
+        }
+    }
+
+    /// Render the error for display on stderr
+    ///
+    /// The first line holds the error message. It is followed by the stage,
+    /// the file and location, the failing SQL statement, a hint and the
+    /// backtrace of a panic, each when available.
+    pub fn render(&self) -> String {
+        let mut output = match self {
+            Self::SqlParse { path, source } => {
+                format!("error: failed to parse {}: {source}\n", path.display())
+            }
+            _ => format!("error: {self}\n"),
+        };
+        output += &format!("  stage: {}\n", self.stage());
+
+        match self {
+            Self::SchemaRead { path, base_dir, .. } => {
+                output += &format!("  file: {}\n", path.display());
+                if let Some(base_dir) = base_dir {
+                    output += &format!("  base dir: {}\n", base_dir.display());
+                }
+            }
+            Self::SchemaPath { path, .. } | Self::Capture { path, .. } => {
+                output += &format!("  file: {}\n", path.display());
+            }
+            Self::SqlParse { path, source } => match source {
+                crate::schema::Error::Parse {
+                    line,
+                    column,
+                    statement,
+                    ..
+                } => {
+                    if *line > 0 {
+                        output += &format!("  at: {}:{line}:{column}\n", path.display());
+                    } else {
+                        output += &format!("  file: {}\n", path.display());
+                    }
+                    if !statement.is_empty() {
+                        output += &format!("  sql: {}\n", statement.trim());
+                    }
+                }
+                crate::schema::Error::UnknownDialect(_) => {
+                    output += &format!("  file: {}\n", path.display());
+                }
+            },
+            Self::Panic {
+                location: Some(location),
+                ..
+            } => {
+                output += &format!("  at: {location}\n");
+            }
+            _ => {}
+        }
+
+        if let Some(hint) = self.hint() {
+            output += &format!("  hint: {hint}\n");
+        }
+        if let Self::Panic {
+            backtrace: Some(backtrace),
+            ..
+        } = self
+        {
+            output += &format!("  backtrace:\n{backtrace}\n");
+        }
+
+        output
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "I/O error: {err}"),
+            Self::Decode(err) => write!(f, "failed to decode GenerateRequest: {err}"),
+            Self::SchemaRead { path, source, .. } => {
+                write!(f, "failed to read schema file {}: {source}", path.display())
+            }
+            Self::SchemaPath { path, base_dir } => write!(
+                f,
+                "schema path {} escapes base directory {}",
+                path.display(),
+                base_dir.display()
+            ),
+            Self::SqlParse { path, source } => match source {
+                crate::schema::Error::Parse { line, .. } if *line > 0 => {
+                    write!(f, "failed to parse {}:{line}: {source}", path.display())
+                }
+                _ => write!(f, "failed to parse {}: {source}", path.display()),
+            },
+            Self::Options(err) => write!(f, "{err}"),
+            Self::Processor(err) => write!(f, "{err}"),
+            Self::InvalidPaths(err) => write!(f, "{err}"),
+            Self::Encode(err) => write!(f, "failed to encode GenerateResponse: {err}"),
+            Self::Capture { path, source } => {
+                write!(
+                    f,
+                    "failed to access capture file {}: {source}",
+                    path.display()
+                )
+            }
+            Self::Panic { message, .. } => write!(f, "plugin panicked: {message}"),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Decode(err) => Some(err),
+            Self::SchemaRead { source, .. } => Some(source),
+            Self::SchemaPath { .. } => None,
+            Self::SqlParse { source, .. } => Some(source),
+            Self::Options(err) => Some(err),
+            Self::Processor(err) => Some(err.as_ref()),
+            Self::InvalidPaths(err) => Some(err),
+            Self::Encode(err) => Some(err),
+            Self::Capture { source, .. } => Some(source),
+            Self::Panic { .. } => None,
+        }
+    }
+}
 
 /// Runs a sqlc plugin with the standard stdin/stdout communication protocol.
 ///
@@ -51,12 +343,22 @@ use std::io::{Read, Write};
 ///
 /// # Errors
 ///
-/// Returns an error if:
-/// - Reading from stdin fails
-/// - Decoding the protobuf request fails
-/// - The process function returns an error
-/// - Encoding the response fails
-/// - Writing to stdout fails
+/// Returns an [`Error`] if:
+/// - Reading from stdin fails ([`Error::Io`])
+/// - Decoding the protobuf request fails ([`Error::Decode`])
+/// - A schema file cannot be read ([`Error::SchemaRead`]) or parsed ([`Error::SqlParse`])
+/// - The process function returns an error ([`Error::Processor`])
+/// - Encoding the response fails ([`Error::Encode`])
+/// - Writing to stdout fails ([`Error::Io`])
+/// - The process function panics ([`Error::Panic`])
+///
+/// Use [`report`] to print the error on stderr and exit with its code.
+///
+/// Schema paths are resolved with [`RunOptions::from_env`], so setting
+/// `SQLC_GEN_BASE_DIR` to the directory of `sqlc.yaml` makes the plugin
+/// independent of the directory sqlc is invoked from. Setting
+/// `SQLC_GEN_CAPTURE` to a file path saves the raw request (and response) for
+/// [`replay`].
 ///
 /// # Example
 ///
@@ -64,7 +366,7 @@ use std::io::{Read, Write};
 /// use sqlc_gen_core::plugin::{GenerateRequest, GenerateResponse, File};
 /// use sqlc_gen_core::runtime::run;
 ///
-/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// fn main() -> Result<(), sqlc_gen_core::runtime::Error> {
 ///     run(|request| {
 ///         // Access request data
 ///         let version = &request.sqlc_version;
@@ -80,13 +382,16 @@ use std::io::{Read, Write};
 ///     })
 /// }
 /// ```
-pub fn run<F>(process: F) -> Result<(), Box<dyn Error>>
+pub fn run<F>(process: F) -> Result<(), Error>
 where
-    F: FnOnce(GenerateRequest) -> Result<GenerateResponse, Box<dyn Error>>,
+    F: FnOnce(GenerateRequest) -> Result<GenerateResponse, Box<dyn StdError>>,
 {
     let stdin = std::io::stdin();
     let stdout = std::io::stdout();
-    run_with_io(stdin.lock(), stdout.lock(), process)
+    let options = RunOptions::from_env();
+    catch_panics(options.backtrace, || {
+        run_with_options(stdin.lock(), stdout.lock(), &options, process)
+    })
 }
 
 /// Runs a sqlc plugin with custom I/O streams.
@@ -109,12 +414,22 @@ where
 ///
 /// # Errors
 ///
-/// Returns an error if:
-/// - Reading from the input stream fails
-/// - Decoding the protobuf request fails
-/// - The process function returns an error
-/// - Encoding the response fails
-/// - Writing to the output stream fails
+/// Returns an [`Error`] if:
+/// - Reading from the input stream fails ([`Error::Io`])
+/// - Decoding the protobuf request fails ([`Error::Decode`])
+/// - A schema file cannot be read ([`Error::SchemaRead`]) or parsed ([`Error::SqlParse`])
+/// - The process function returns an error ([`Error::Processor`])
+/// - Encoding the response fails ([`Error::Encode`])
+/// - Writing to the output stream fails ([`Error::Io`])
+///
+/// # Schema files
+///
+/// When the request's `Settings.schema` is not empty, the listed files are
+/// parsed with [`CatalogBuilder`] and merged with the catalog sent by sqlc
+/// using [`CatalogBuilder::deep_merge_catalog`]: sqlc's columns are kept and the
+/// parsed constraints and indexes are added. Merge conflicts are logged as
+/// warnings, see [`crate::log`]. Entries may name files, directories or glob patterns of migration files;
+/// only the up sections of migrations are parsed, see [`crate::loader`].
 ///
 /// # Example
 ///
@@ -148,226 +463,2101 @@ where
 /// let response = GenerateResponse::decode(&output[..]).unwrap();
 /// assert_eq!(response.files.len(), 0);
 /// ```
-pub fn run_with_io<R, W, F>(mut reader: R, mut writer: W, process: F) -> Result<(), Box<dyn Error>>
+pub fn run_with_io<R, W, F>(reader: R, writer: W, process: F) -> Result<(), Error>
 where
     R: Read,
     W: Write,
-    F: FnOnce(GenerateRequest) -> Result<GenerateResponse, Box<dyn Error>>,
+    F: FnOnce(GenerateRequest) -> Result<GenerateResponse, Box<dyn StdError>>,
 {
-    let mut input = Vec::new();
-    reader.read_to_end(&mut input)?;
+    run_with_options(reader, writer, &RunOptions::default(), process)
+}
 
-    let mut request = GenerateRequest::decode(&input[..])?;
+/// Environment variable naming the directory schema paths are resolved against
+pub const BASE_DIR_ENV: &str = "SQLC_GEN_BASE_DIR";
 
-    if let Some(settings) = &request.settings {
-        if !settings.schema.is_empty() {
-            let mut builder = CatalogBuilder::new(settings.engine.as_str());
+/// Environment variable naming the file raw requests are captured to
+pub const CAPTURE_ENV: &str = "SQLC_GEN_CAPTURE";
 
-            for item in &settings.schema {
-                let schema = std::fs::read_to_string(item)?;
-                builder.parse_sql(&schema)?;
-            }
+/// Environment variable enabling backtraces in panic reports (any value but `0`)
+pub const BACKTRACE_ENV: &str = "SQLC_GEN_BACKTRACE";
 
-            if let Some(catalog) = request.catalog.take() {
-                builder.merge_catalog(catalog);
-            }
+/// Environment variable setting the level of diagnostics written to stderr
+pub const LOG_ENV: &str = "SQLC_GEN_LOG";
 
-            request.catalog = Some(builder.build());
-        }
-    }
+/// Options controlling how the runtime prepares a request.
+///
+/// [`run`], [`run_typed`] and [`run_plugin`] use [`RunOptions::from_env`],
+/// while the `*_with_io` variants use [`RunOptions::default`].
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Directory `Settings.schema` paths are resolved against
+    ///
+    /// sqlc resolves schema paths relative to its configuration file. When
+    /// set, schema entries are resolved against this directory and entries
+    /// escaping it are refused with [`Error::SchemaPath`]. When `None`, paths
+    /// are resolved against the current working directory.
+    pub base_dir: Option<PathBuf>,
 
-    let response = process(request)?;
-    let mut output = Vec::new();
-    response.encode(&mut output)?;
+    /// File system schema files are read from
+    ///
+    /// Defaults to [`StdFileSystem`]. Plugins running where file access is
+    /// restricted can use [`crate::fs::DisabledFileSystem`], and tests can
+    /// provide schemas with [`crate::fs::MemoryFileSystem`].
+    pub fs: Arc<dyn FileSystem>,
 
-    writer.write_all(&output)?;
-    Ok(())
-}
+    /// File the raw request is written to before it is processed
+    ///
+    /// The encoded response is written next to it, with `.response` appended
+    /// to the file name (see [`response_capture_path`]); the response of a
+    /// previous run is removed first, so that it never pairs with a request
+    /// that failed. A captured request can be processed again with [`replay`].
+    /// Capture files that cannot be written do not fail the run, but are
+    /// always reported as warnings through [`log`](Self::log), on stderr by
+    /// default, even when logging is disabled.
+    pub capture: Option<PathBuf>,
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::plugin::{File, GenerateRequest, GenerateResponse};
+    /// Check and normalize the file names of the response before encoding it
+    ///
+    /// Enabled by default: names are normalized with
+    /// [`crate::output::normalize_paths`] and absolute paths, `..` segments,
+    /// control characters and duplicates fail with [`Error::InvalidPaths`].
+    /// Plugins relying on sqlc to handle unusual names can opt out.
+    pub validate_paths: bool,
 
-    fn create_sample_request() -> GenerateRequest {
-        GenerateRequest {
-            settings: None,
-            catalog: None,
-            queries: vec![],
-            sqlc_version: "test".to_string(),
-            plugin_options: vec![],
-            global_options: vec![],
-        }
-    }
+    /// Capture a backtrace when the processing code panics
+    ///
+    /// Only used by the entry points catching panics ([`run`], [`run_typed`]
+    /// and [`run_plugin`]), which add it to [`Error::Panic`].
+    pub backtrace: bool,
 
-    fn create_sample_response() -> GenerateResponse {
-        GenerateResponse {
-            files: vec![File {
-                name: "test.rs".to_string(),
-                contents: b"// test content".to_vec(),
-            }],
+    /// Logger receiving the diagnostics of the runtime, disabled by default
+    ///
+    /// While a request is processed it is also the current logger of the
+    /// thread, so plugins can log through [`crate::log::event`].
+    pub log: Logger,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            base_dir: None,
+            fs: Arc::new(StdFileSystem),
+            capture: None,
+            validate_paths: true,
+            backtrace: false,
+            log: Logger::default(),
         }
     }
+}
 
-    #[test]
-    fn test_run_with_io_success() {
-        let mut input = Vec::new();
-        let mut output = Vec::new();
+impl RunOptions {
+    /// Create options from the environment
+    ///
+    /// The base directory is read from the [`BASE_DIR_ENV`] variable
+    /// (`SQLC_GEN_BASE_DIR`) and the capture file from the [`CAPTURE_ENV`]
+    /// variable (`SQLC_GEN_CAPTURE`); sqlc can forward them to process plugins
+    /// through the `env` list of the plugin configuration. Backtraces are
+    /// enabled when [`BACKTRACE_ENV`] (`SQLC_GEN_BACKTRACE`) is set to a value
+    /// other than `0`, and diagnostics are written to stderr up to the level
+    /// named by [`LOG_ENV`] (`SQLC_GEN_LOG`, e.g. `debug`). An unknown level
+    /// leaves logging disabled and is reported with a warning on stderr.
+    pub fn from_env() -> Self {
+        let log = env_logger(std::env::var(LOG_ENV).ok().as_deref(), Logger::default());
 
-        let request = create_sample_request();
-        request.encode(&mut input).unwrap();
+        Self {
+            base_dir: env_path(BASE_DIR_ENV),
+            capture: env_path(CAPTURE_ENV),
+            backtrace: std::env::var_os(BACKTRACE_ENV)
+                .is_some_and(|value| !value.is_empty() && value != "0"),
+            log,
+            ..Self::default()
+        }
+    }
 
-        let result = run_with_io(&input[..], &mut output, |req| {
-            assert_eq!(req.sqlc_version, "test");
-            Ok(create_sample_response())
-        });
-        assert!(result.is_ok(), "run_with_io should succeed");
+    /// Resolve schema paths against `base_dir`
+    pub fn with_base_dir(mut self, base_dir: impl Into<PathBuf>) -> Self {
+        self.base_dir = Some(base_dir.into());
+        self
+    }
 
-        let response = GenerateResponse::decode(&output[..]).unwrap();
-        assert_eq!(response.files.len(), 1);
-        assert_eq!(response.files[0].name, "test.rs");
-        assert_eq!(response.files[0].contents, b"// test content");
+    /// Read schema files from `fs` instead of the host file system
+    pub fn with_file_system(mut self, fs: impl FileSystem + 'static) -> Self {
+        self.fs = Arc::new(fs);
+        self
     }
 
-    #[test]
-    fn test_run_with_io_processor_error() {
-        let mut input = Vec::new();
-        let mut output = Vec::new();
+    /// Capture the raw request and response to `path`
+    pub fn with_capture(mut self, path: impl Into<PathBuf>) -> Self {
+        self.capture = Some(path.into());
+        self
+    }
 
-        let request = create_sample_request();
-        request.encode(&mut input).unwrap();
+    /// Enable or disable the validation of response file names
+    pub fn with_validate_paths(mut self, validate_paths: bool) -> Self {
+        self.validate_paths = validate_paths;
+        self
+    }
 
-        let result = run_with_io(&input[..], &mut output, |_req| {
-            Err("Processing failed".into())
-        });
-        assert!(
-            result.is_err(),
-            "run_with_io should fail when processor fails"
-        );
-        assert_eq!(result.unwrap_err().to_string(), "Processing failed");
+    /// Capture a backtrace when the processing code panics
+    pub fn with_backtrace(mut self, backtrace: bool) -> Self {
+        self.backtrace = backtrace;
+        self
     }
 
-    #[test]
-    fn test_run_with_io_invalid_input() {
-        let input = b"invalid protobuf data";
-        let mut output = Vec::new();
+    /// Send diagnostics to `log`
+    pub fn with_log(mut self, log: Logger) -> Self {
+        self.log = log;
+        self
+    }
 
-        let result = run_with_io(&input[..], &mut output, |_req| Ok(create_sample_response()));
-        assert!(
-            result.is_err(),
-            "run_with_io should fail with invalid input"
-        );
+    /// Loader for the schema files of a request
+    fn schema_loader(&self) -> SchemaLoader {
+        SchemaLoader {
+            base_dir: self.base_dir.clone(),
+            fs: Arc::clone(&self.fs),
+        }
     }
+}
 
-    #[test]
-    fn test_run_with_io_empty_input() {
-        let input: &[u8] = &[];
-        let mut output = Vec::new();
+/// Print an error on stderr and turn the result of a run into an exit code.
+///
+/// Errors are printed with [`Error::render`] and exit with
+/// [`Error::exit_code`], see the [module docs](self) for the format and the
+/// list of exit codes.
+///
+/// # Example
+///
+/// ```no_run
+/// use sqlc_gen_core::plugin::GenerateResponse;
+/// use sqlc_gen_core::runtime::{report, run};
+/// use std::process::ExitCode;
+///
+/// fn main() -> ExitCode {
+///     report(run(|_request| Ok(GenerateResponse { files: vec![] })))
+/// }
+/// ```
+pub fn report(result: Result<(), Error>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprint!("{}", err.render());
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
 
-        let result = run_with_io(input, &mut output, |_req| Ok(create_sample_response()));
-        assert!(
-            result.is_ok(),
-            "run_with_io should succeed with empty input (creates default request)"
-        );
+/// Run `f`, turning a panic into [`Error::Panic`]
+///
+/// A panic hook is installed the first time this runs and kept afterwards.
+/// While `f` runs, it records the location (and backtrace when
+/// `with_backtrace` is set) of panics of the current thread, so that they are
+/// reported once through the returned error rather than printed. Panics of
+/// other threads are handed to the hook installed before.
+fn catch_panics<T>(with_backtrace: bool, f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+    PREVIOUS_HOOK.get_or_init(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(panic_hook));
+        previous
+    });
 
-        let response = GenerateResponse::decode(&output[..]).unwrap();
-        assert_eq!(response.files.len(), 1);
-    }
+    let outer = CAUGHT_PANIC.with(|caught| {
+        caught.replace(Some(CaughtPanic {
+            with_backtrace,
+            ..Default::default()
+        }))
+    });
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f));
+    let caught = CAUGHT_PANIC
+        .with(|caught| caught.replace(outer))
+        .unwrap_or_default();
 
-    #[test]
-    fn test_run_with_io_empty_response() {
-        let mut input = Vec::new();
-        let mut output = Vec::new();
+    result.unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic payload".to_string());
 
-        let request = create_sample_request();
-        request.encode(&mut input).unwrap();
+        Err(Error::Panic {
+            message,
+            location: caught.location,
+            backtrace: caught.backtrace,
+        })
+    })
+}
 
-        // Processor returns empty response
-        let result = run_with_io(&input[..], &mut output, |_req| {
-            Ok(GenerateResponse { files: vec![] })
-        });
-        assert!(
-            result.is_ok(),
-            "run_with_io should succeed with empty response"
-        );
+/// Panic hook, as returned by [`std::panic::take_hook`]
+type PanicHook = dyn Fn(&std::panic::PanicHookInfo<'_>) + Sync + Send + 'static;
 
-        let response = GenerateResponse::decode(&output[..]).unwrap();
-        assert_eq!(response.files.len(), 0);
-    }
+/// Hook that was installed before [`catch_panics`] installed [`panic_hook`]
+static PREVIOUS_HOOK: OnceLock<Box<PanicHook>> = OnceLock::new();
 
-    #[test]
-    fn test_run_with_io_multiple_files() {
-        let mut input = Vec::new();
-        let mut output = Vec::new();
+thread_local! {
+    /// Details of the panic caught by [`catch_panics`], while it runs on this thread
+    static CAUGHT_PANIC: RefCell<Option<CaughtPanic>> = const { RefCell::new(None) };
+}
 
-        let request = create_sample_request();
-        request.encode(&mut input).unwrap();
+/// Panic details recorded by [`panic_hook`]
+#[derive(Debug, Default)]
+struct CaughtPanic {
+    /// Whether to capture a backtrace
+    with_backtrace: bool,
+    /// Source location of the panic
+    location: Option<String>,
+    /// Backtrace of the panic
+    backtrace: Option<String>,
+}
 
-        let result = run_with_io(&input[..], &mut output, |_req| {
-            Ok(GenerateResponse {
-                files: vec![
-                    File {
-                        name: "file1.rs".to_string(),
-                        contents: b"content1".to_vec(),
-                    },
-                    File {
-                        name: "file2.rs".to_string(),
-                        contents: b"content2".to_vec(),
-                    },
-                ],
-            })
-        });
-        assert!(result.is_ok(), "run_with_io should succeed");
+/// Record panics inside [`catch_panics`], and defer to the previous hook elsewhere
+fn panic_hook(info: &std::panic::PanicHookInfo<'_>) {
+    let recorded = CAUGHT_PANIC
+        .try_with(|caught| {
+            let Ok(mut caught) = caught.try_borrow_mut() else {
+                return false;
+            };
+            let Some(caught) = caught.as_mut() else {
+                return false;
+            };
+            caught.location = info.location().map(ToString::to_string);
+            caught.backtrace = caught
+                .with_backtrace
+                .then(|| std::backtrace::Backtrace::force_capture().to_string());
+            true
+        })
+        .unwrap_or(false);
 
-        let response = GenerateResponse::decode(&output[..]).unwrap();
-        assert_eq!(response.files.len(), 2);
-        assert_eq!(response.files[0].name, "file1.rs");
-        assert_eq!(response.files[1].name, "file2.rs");
+    if !recorded {
+        if let Some(previous) = PREVIOUS_HOOK.get() {
+            previous(info);
+        }
     }
+}
 
-    #[test]
-    fn test_run_with_io_preserves_request_data() {
-        let mut input = Vec::new();
-        let mut output = Vec::new();
+/// Read a non-empty path from an environment variable
+fn env_path(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
 
-        let request = GenerateRequest {
-            settings: None,
-            catalog: None,
-            queries: vec![],
-            sqlc_version: "1.2.3".to_string(),
-            plugin_options: b"test_plugin_options".to_vec(),
-            global_options: b"test_global_options".to_vec(),
-        };
-        request.encode(&mut input).unwrap();
+/// Enable the disabled logger `log` up to the level named by `value`
+///
+/// An unknown level is reported with a warning on `log`, which stays disabled.
+fn env_logger(value: Option<&str>, log: Logger) -> Logger {
+    let Some(value) = value.filter(|value| !value.trim().is_empty()) else {
+        return log;
+    };
 
-        let result = run_with_io(&input[..], &mut output, |req| {
-            assert_eq!(req.sqlc_version, "1.2.3");
-            assert_eq!(req.plugin_options, b"test_plugin_options");
-            assert_eq!(req.global_options, b"test_global_options");
-            Ok(create_sample_response())
-        });
-        assert!(result.is_ok());
+    match value.parse() {
+        Ok(level) => log.at_least(level),
+        Err(_) => {
+            log.at_least(Level::Warn)
+                .event(
+                    Level::Warn,
+                    "runtime",
+                    format!("ignoring unknown {LOG_ENV} level"),
+                )
+                .field("value", value)
+                .field("expected", "error|warn|info|debug|trace")
+                .emit();
+            log
+        }
     }
+}
 
-    #[test]
-    fn test_run_with_io_large_content() {
-        let mut input = Vec::new();
-        let mut output = Vec::new();
+/// Path the response is captured to when requests are captured to `path`
+///
+/// ```
+/// use sqlc_gen_core::runtime::response_capture_path;
+/// use std::path::Path;
+///
+/// assert_eq!(
+///     response_capture_path(Path::new("/tmp/request.bin")),
+///     Path::new("/tmp/request.bin.response")
+/// );
+/// ```
+pub fn response_capture_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".response");
+    PathBuf::from(name)
+}
 
-        let request = create_sample_request();
-        request.encode(&mut input).unwrap();
+/// Write captured bytes to `path`
+///
+/// Failures are logged to `log` even when it is disabled, since capturing was
+/// explicitly asked for.
+fn capture(log: &Logger, path: &Path, bytes: &[u8]) {
+    if let Err(err) = std::fs::write(path, bytes) {
+        log.at_least(Level::Warn)
+            .event(Level::Warn, "capture", "failed to write capture file")
+            .field("path", path.display())
+            .field("error", err)
+            .emit();
+    }
+}
 
-        let result = run_with_io(&input[..], &mut output, |_req| {
-            Ok(GenerateResponse {
-                files: vec![File {
-                    name: "large.rs".to_string(),
-                    contents: vec![b'x'; 1024 * 1024].clone(),
-                }],
-            })
-        });
+/// Remove the response captured by a previous run, logging a warning like
+/// [`capture`] on failure
+fn remove_capture(log: &Logger, path: &Path) {
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => log
+            .at_least(Level::Warn)
+            .event(Level::Warn, "capture", "failed to remove capture file")
+            .field("path", path.display())
+            .field("error", err)
+            .emit(),
+    }
+}
+
+/// Runs a sqlc plugin with custom I/O streams and runtime options.
+///
+/// This behaves like [`run_with_io`], but schema files are loaded according
+/// to `options`, see [`RunOptions`].
+///
+/// # Example
+///
+/// ```no_run
+/// use sqlc_gen_core::plugin::GenerateResponse;
+/// use sqlc_gen_core::runtime::{run_with_options, RunOptions};
+///
+/// fn main() -> Result<(), sqlc_gen_core::runtime::Error> {
+///     let options = RunOptions::default().with_base_dir("/path/to/sqlc/config");
+///     run_with_options(std::io::stdin(), std::io::stdout(), &options, |request| {
+///         Ok(GenerateResponse { files: vec![] })
+///     })
+/// }
+/// ```
+pub fn run_with_options<R, W, F>(
+    reader: R,
+    writer: W,
+    options: &RunOptions,
+    process: F,
+) -> Result<(), Error>
+where
+    R: Read,
+    W: Write,
+    F: FnOnce(GenerateRequest) -> Result<GenerateResponse, Box<dyn StdError>>,
+{
+    execute(reader, writer, options, |request, _| {
+        process(request).map_err(Error::Processor)
+    })
+}
+
+/// Read a request, hand it to `handler` and write the response
+fn execute<R, W, F>(reader: R, mut writer: W, options: &RunOptions, handler: F) -> Result<(), Error>
+where
+    R: Read,
+    W: Write,
+    F: FnOnce(GenerateRequest, CompositeAttributes) -> Result<GenerateResponse, Error>,
+{
+    options.log.scope(|| {
+        let start = Instant::now();
+        let input = read_input(reader)?;
+        log::event(Level::Debug, "runtime", "read request")
+            .field("bytes", input.len())
+            .emit();
+        if let Some(path) = &options.capture {
+            capture(&options.log, path, &input);
+            remove_capture(&options.log, &response_capture_path(path));
+        }
+
+        let response = process_input(&input, options, handler)?;
+        let output = encode_response(&response)?;
+        if let Some(path) = &options.capture {
+            capture(&options.log, &response_capture_path(path), &output);
+        }
+
+        writer.write_all(&output).map_err(Error::Io)?;
+        log::event(Level::Debug, "runtime", "wrote response")
+            .field("bytes", output.len())
+            .elapsed(start.elapsed())
+            .emit();
+        Ok(())
+    })
+}
+
+/// Read the raw request bytes
+fn read_input<R: Read>(mut reader: R) -> Result<Vec<u8>, Error> {
+    let mut input = Vec::new();
+    reader.read_to_end(&mut input).map_err(Error::Io)?;
+    Ok(input)
+}
+
+/// Decode and enrich a raw request, then hand it to `handler`
+///
+/// The handler receives the attributes of the parsed composite types, which
+/// are also the current attributes while it runs.
+fn process_input<F>(
+    input: &[u8],
+    options: &RunOptions,
+    handler: F,
+) -> Result<GenerateResponse, Error>
+where
+    F: FnOnce(GenerateRequest, CompositeAttributes) -> Result<GenerateResponse, Error>,
+{
+    options.log.scope(|| {
+        let request = GenerateRequest::decode(input).map_err(Error::Decode)?;
+        log::event(Level::Debug, "runtime", "decoded request")
+            .field("sqlc_version", &request.sqlc_version)
+            .field("queries", request.queries.len())
+            .emit();
+
+        let (request, attributes) = prepare_request(request, options)?;
+
+        let start = Instant::now();
+        let response = attributes.scope(|| handler(request, attributes.clone()))?;
+        log::event(Level::Debug, "generate", "done")
+            .field("files", response.files.len())
+            .elapsed(start.elapsed())
+            .emit();
+
+        finish_response(response, options)
+    })
+}
+
+/// Enrich the catalog of a decoded request from its schema files
+///
+/// Schema entries are expanded and read with [`SchemaLoader::load`], which
+/// also drops the down sections of migration files. When the file system of
+/// `options` is disabled, the catalog sent by sqlc is kept unchanged. The
+/// attributes of the
+/// parsed composite types are returned alongside the request, to be made
+/// current with [`CompositeAttributes::scope`] while the handler runs.
+pub(crate) fn prepare_request(
+    mut request: GenerateRequest,
+    options: &RunOptions,
+) -> Result<(GenerateRequest, CompositeAttributes), Error> {
+    let mut attributes = CompositeAttributes::default();
+
+    if let Some(settings) = &request.settings {
+        if !settings.schema.is_empty() && !options.fs.is_enabled() {
+            log::event(
+                Level::Debug,
+                "schema",
+                "file system disabled, keeping sqlc catalog",
+            )
+            .field("entries", settings.schema.len())
+            .emit();
+        } else if !settings.schema.is_empty() {
+            let mut builder = CatalogBuilder::new(settings.engine.as_str());
+
+            let start = Instant::now();
+            for file in options.schema_loader().load(&settings.schema)? {
+                log::event(Level::Debug, "schema", "read file")
+                    .field("path", file.path.display())
+                    .field("bytes", file.sql.len())
+                    .emit();
+                builder
+                    .parse_sql(&file.sql)
+                    .map_err(|source| Error::SqlParse {
+                        path: file.path,
+                        source,
+                    })?;
+            }
+            log::event(Level::Debug, "schema", "done")
+                .elapsed(start.elapsed())
+                .emit();
+
+            if let Some(catalog) = request.catalog.take() {
+                let report = log::timed("merge", || builder.deep_merge_catalog(catalog));
+                for conflict in &report.conflicts {
+                    log::event(Level::Warn, "merge", "conflict")
+                        .field("object", &conflict.object)
+                        .field("message", &conflict.message)
+                        .field("resolution", conflict.resolution)
+                        .emit();
+                }
+            }
+
+            attributes = builder.composite_attributes();
+            request.catalog = Some(builder.build());
+        }
+    }
+
+    Ok((request, attributes))
+}
+
+/// Check the file names of a response, unless disabled in `options`
+pub(crate) fn finish_response(
+    mut response: GenerateResponse,
+    options: &RunOptions,
+) -> Result<GenerateResponse, Error> {
+    if options.validate_paths {
+        normalize_paths(&mut response.files).map_err(Error::InvalidPaths)?;
+    }
+    Ok(response)
+}
+
+/// Encode a response
+fn encode_response(response: &GenerateResponse) -> Result<Vec<u8>, Error> {
+    let mut output = Vec::new();
+    response.encode(&mut output).map_err(Error::Encode)?;
+    Ok(output)
+}
+
+/// Processes a captured request again, outside of sqlc.
+///
+/// `path` is a request captured by setting [`CAPTURE_ENV`] (or
+/// [`RunOptions::capture`]) while sqlc ran the plugin. The request goes through
+/// the same schema enrichment as in [`run`], using [`RunOptions::from_env`]
+/// without capturing, and the response returned by `process` is handed back
+/// instead of being written to stdout. This lets the exact invocation be
+/// debugged or turned into a test.
+///
+/// # Errors
+///
+/// Returns [`Error::Capture`] if the file cannot be read, and otherwise the
+/// same errors as [`run`].
+///
+/// # Example
+///
+/// ```no_run
+/// use sqlc_gen_core::plugin::GenerateResponse;
+/// use sqlc_gen_core::runtime::replay;
+///
+/// let response = replay("/tmp/request.bin", |request| {
+///     // Set a breakpoint here
+///     Ok(GenerateResponse { files: vec![] })
+/// })
+/// .unwrap();
+/// assert!(response.files.is_empty());
+/// ```
+pub fn replay<P, F>(path: P, process: F) -> Result<GenerateResponse, Error>
+where
+    P: AsRef<Path>,
+    F: FnOnce(GenerateRequest) -> Result<GenerateResponse, Box<dyn StdError>>,
+{
+    let options = RunOptions {
+        capture: None,
+        ..RunOptions::from_env()
+    };
+    replay_with_options(path, &options, process)
+}
+
+/// Processes a captured request again with explicit runtime options.
+///
+/// This behaves like [`replay`], with schema files loaded according to
+/// `options`. [`RunOptions::capture`] is ignored, so replaying never
+/// overwrites the capture being replayed.
+pub fn replay_with_options<P, F>(
+    path: P,
+    options: &RunOptions,
+    process: F,
+) -> Result<GenerateResponse, Error>
+where
+    P: AsRef<Path>,
+    F: FnOnce(GenerateRequest) -> Result<GenerateResponse, Box<dyn StdError>>,
+{
+    let path = path.as_ref();
+    let input = std::fs::read(path).map_err(|source| Error::Capture {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    process_input(&input, options, |request, _| {
+        process(request).map_err(Error::Processor)
+    })
+}
+
+/// Runs a sqlc plugin whose options are decoded into a user-defined type.
+///
+/// This behaves like [`run`], but before invoking `process` it decodes the
+/// request's `plugin_options` (and `global_options` when `O` is
+/// [`crate::options::Options`]) with [`DecodeOptions`]. Empty options decode as
+/// `{}`, unknown keys are rejected and errors carry the JSON path of the
+/// offending value.
+///
+/// # Errors
+///
+/// Returns an error if [`run`] would, or [`Error::Options`] if the options
+/// cannot be decoded.
+///
+/// # Example
+///
+/// ```no_run
+/// use serde::Deserialize;
+/// use sqlc_gen_core::plugin::{File, GenerateResponse};
+/// use sqlc_gen_core::runtime::run_typed;
+///
+/// #[derive(Deserialize, Default)]
+/// #[serde(default)]
+/// struct Opts {
+///     package: String,
+/// }
+///
+/// fn main() -> Result<(), sqlc_gen_core::runtime::Error> {
+///     run_typed::<Opts, _>(|_request, opts| {
+///         let files = vec![File {
+///             name: format!("{}.rs", opts.package),
+///             contents: b"// Generated code".to_vec(),
+///         }];
+///
+///         Ok(GenerateResponse { files })
+///     })
+/// }
+/// ```
+pub fn run_typed<O, F>(process: F) -> Result<(), Error>
+where
+    O: DecodeOptions,
+    F: FnOnce(GenerateRequest, O) -> Result<GenerateResponse, Box<dyn StdError>>,
+{
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    let options = RunOptions::from_env();
+    catch_panics(options.backtrace, || {
+        execute(
+            stdin.lock(),
+            stdout.lock(),
+            &options,
+            typed_handler(process),
+        )
+    })
+}
+
+/// Runs a sqlc plugin with typed options and custom I/O streams.
+///
+/// See [`run_typed`] for how options are decoded and [`run_with_io`] for how
+/// the streams are used.
+///
+/// # Example
+///
+/// ```
+/// use serde::Deserialize;
+/// use sqlc_gen_core::plugin::{GenerateRequest, GenerateResponse};
+/// use sqlc_gen_core::runtime::run_typed_with_io;
+/// use prost::Message;
+///
+/// #[derive(Deserialize)]
+/// struct Opts {
+///     package: String,
+/// }
+///
+/// let request = GenerateRequest {
+///     plugin_options: br#"{"package": "db"}"#.to_vec(),
+///     ..Default::default()
+/// };
+///
+/// let mut input = Vec::new();
+/// request.encode(&mut input).unwrap();
+///
+/// let mut output = Vec::new();
+/// run_typed_with_io::<Opts, _, _, _>(&input[..], &mut output, |_req, opts| {
+///     assert_eq!(opts.package, "db");
+///     Ok(GenerateResponse { files: vec![] })
+/// }).unwrap();
+/// ```
+pub fn run_typed_with_io<O, R, W, F>(reader: R, writer: W, process: F) -> Result<(), Error>
+where
+    O: DecodeOptions,
+    R: Read,
+    W: Write,
+    F: FnOnce(GenerateRequest, O) -> Result<GenerateResponse, Box<dyn StdError>>,
+{
+    execute(
+        reader,
+        writer,
+        &RunOptions::default(),
+        typed_handler(process),
+    )
+}
+
+/// Adapt a typed processing function into a request handler
+fn typed_handler<O, F>(
+    process: F,
+) -> impl FnOnce(GenerateRequest, CompositeAttributes) -> Result<GenerateResponse, Error>
+where
+    O: DecodeOptions,
+    F: FnOnce(GenerateRequest, O) -> Result<GenerateResponse, Box<dyn StdError>>,
+{
+    |request, _| {
+        let options = O::decode_options(&request).map_err(Error::Options)?;
+        process(request, options).map_err(Error::Processor)
+    }
+}
+
+/// A sqlc plugin expressed as a set of lifecycle hooks.
+///
+/// Instead of handling the whole [`GenerateRequest`] in one closure, a plugin
+/// can implement the hooks it needs and let [`run_plugin`] call them in order:
+///
+/// 1. [`configure`](Plugin::configure) once with the request
+/// 2. [`prepare_catalog`](Plugin::prepare_catalog) once with the catalog, if
+///    any, then [`prepare_composites`](Plugin::prepare_composites) once with
+///    the attributes of its composite types
+/// 3. [`generate_query`](Plugin::generate_query) for every query
+/// 4. [`generate_model`](Plugin::generate_model) for every table of every schema
+///    accepted by [`include_schema`](Plugin::include_schema), which skips
+///    [`SYSTEM_SCHEMAS`] by default
+/// 5. [`finalize`](Plugin::finalize) once with the complete response
+///
+/// Every hook has a no-op default implementation. Generation hooks append
+/// their files to the shared [`GenerateResponse`], and `finalize` can rewrite
+/// or merge them before the response is sent back to sqlc.
+///
+/// # Example
+///
+/// ```no_run
+/// use sqlc_gen_core::plugin::{File, GenerateRequest, GenerateResponse, Query};
+/// use sqlc_gen_core::runtime::{run_plugin, Plugin};
+/// use std::error::Error;
+///
+/// struct QueryNames;
+///
+/// impl Plugin for QueryNames {
+///     fn generate_query(
+///         &mut self,
+///         _request: &GenerateRequest,
+///         query: &Query,
+///         response: &mut GenerateResponse,
+///     ) -> Result<(), Box<dyn Error>> {
+///         response.files.push(File {
+///             name: format!("{}.txt", query.name),
+///             contents: query.text.clone().into_bytes(),
+///         });
+///         Ok(())
+///     }
+/// }
+///
+/// fn main() -> Result<(), sqlc_gen_core::runtime::Error> {
+///     run_plugin(QueryNames)
+/// }
+/// ```
+pub trait Plugin {
+    /// Inspect the request before generation starts, e.g. to decode options
+    fn configure(&mut self, request: &GenerateRequest) -> Result<(), Box<dyn StdError>> {
+        let _ = request;
+        Ok(())
+    }
+
+    /// Adjust the catalog before any model or query is generated
+    fn prepare_catalog(&mut self, catalog: &mut Catalog) -> Result<(), Box<dyn StdError>> {
+        let _ = catalog;
+        Ok(())
+    }
+
+    /// Receive the attributes of the composite types parsed from the schema files
+    ///
+    /// The attributes are empty when no composite type was parsed. They are
+    /// also available from [`crate::composite::current`] while hooks run.
+    fn prepare_composites(
+        &mut self,
+        attributes: &CompositeAttributes,
+    ) -> Result<(), Box<dyn StdError>> {
+        let _ = attributes;
+        Ok(())
+    }
+
+    /// Whether [`generate_model`](Self::generate_model) is called for the tables of `schema`
+    ///
+    /// By default every schema is included except [`SYSTEM_SCHEMAS`], which
+    /// sqlc sends along with the user's schemas.
+    fn include_schema(&self, schema: &Schema) -> bool {
+        !SYSTEM_SCHEMAS.contains(&schema.name.as_str())
+    }
+
+    /// Generate code for a single table of the catalog
+    fn generate_model(
+        &mut self,
+        request: &GenerateRequest,
+        table: &Table,
+        response: &mut GenerateResponse,
+    ) -> Result<(), Box<dyn StdError>> {
+        let _ = (request, table, response);
+        Ok(())
+    }
+
+    /// Generate code for a single query
+    fn generate_query(
+        &mut self,
+        request: &GenerateRequest,
+        query: &Query,
+        response: &mut GenerateResponse,
+    ) -> Result<(), Box<dyn StdError>> {
+        let _ = (request, query, response);
+        Ok(())
+    }
+
+    /// Post-process the response once every model and query has been generated
+    fn finalize(
+        &mut self,
+        request: &GenerateRequest,
+        response: &mut GenerateResponse,
+    ) -> Result<(), Box<dyn StdError>> {
+        let _ = (request, response);
+        Ok(())
+    }
+}
+
+/// Lets a plugin be driven by reference, so its state can be inspected afterwards.
+impl<P: Plugin + ?Sized> Plugin for &mut P {
+    fn configure(&mut self, request: &GenerateRequest) -> Result<(), Box<dyn StdError>> {
+        (**self).configure(request)
+    }
+
+    fn prepare_catalog(&mut self, catalog: &mut Catalog) -> Result<(), Box<dyn StdError>> {
+        (**self).prepare_catalog(catalog)
+    }
+
+    fn prepare_composites(
+        &mut self,
+        attributes: &CompositeAttributes,
+    ) -> Result<(), Box<dyn StdError>> {
+        (**self).prepare_composites(attributes)
+    }
+
+    fn include_schema(&self, schema: &Schema) -> bool {
+        (**self).include_schema(schema)
+    }
+
+    fn generate_model(
+        &mut self,
+        request: &GenerateRequest,
+        table: &Table,
+        response: &mut GenerateResponse,
+    ) -> Result<(), Box<dyn StdError>> {
+        (**self).generate_model(request, table, response)
+    }
+
+    fn generate_query(
+        &mut self,
+        request: &GenerateRequest,
+        query: &Query,
+        response: &mut GenerateResponse,
+    ) -> Result<(), Box<dyn StdError>> {
+        (**self).generate_query(request, query, response)
+    }
+
+    fn finalize(
+        &mut self,
+        request: &GenerateRequest,
+        response: &mut GenerateResponse,
+    ) -> Result<(), Box<dyn StdError>> {
+        (**self).finalize(request, response)
+    }
+}
+
+/// Runs a [`Plugin`] with the standard stdin/stdout communication protocol.
+///
+/// # Errors
+///
+/// Returns an error if [`run`] would, or [`Error::Processor`] if any of the
+/// plugin hooks fails.
+pub fn run_plugin<P: Plugin>(plugin: P) -> Result<(), Error> {
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    let options = RunOptions::from_env();
+    catch_panics(options.backtrace, || {
+        execute(
+            stdin.lock(),
+            stdout.lock(),
+            &options,
+            |request, attributes| drive(plugin, request, &attributes).map_err(Error::Processor),
+        )
+    })
+}
+
+/// Runs a [`Plugin`] with custom I/O streams.
+///
+/// See [`Plugin`] for the order in which hooks are called and [`run_with_io`]
+/// for how the streams are used.
+pub fn run_plugin_with_io<R, W, P>(reader: R, writer: W, plugin: P) -> Result<(), Error>
+where
+    R: Read,
+    W: Write,
+    P: Plugin,
+{
+    execute(
+        reader,
+        writer,
+        &RunOptions::default(),
+        |request, attributes| drive(plugin, request, &attributes).map_err(Error::Processor),
+    )
+}
+
+/// Schemas of sqlc's PostgreSQL catalog that describe the database itself
+pub const SYSTEM_SCHEMAS: &[&str] = &["pg_catalog", "information_schema"];
+
+/// Call the hooks of `plugin` in lifecycle order
+fn drive<P: Plugin>(
+    mut plugin: P,
+    mut request: GenerateRequest,
+    attributes: &CompositeAttributes,
+) -> Result<GenerateResponse, Box<dyn StdError>> {
+    plugin.configure(&request)?;
+
+    if let Some(catalog) = request.catalog.as_mut() {
+        plugin.prepare_catalog(catalog)?;
+    }
+    plugin.prepare_composites(attributes)?;
+
+    let mut response = GenerateResponse::default();
+
+    for query in &request.queries {
+        plugin.generate_query(&request, query, &mut response)?;
+    }
+
+    if let Some(catalog) = &request.catalog {
+        for schema in &catalog.schemas {
+            if !plugin.include_schema(schema) {
+                continue;
+            }
+            for table in &schema.tables {
+                plugin.generate_model(&request, table, &mut response)?;
+            }
+        }
+    }
+
+    plugin.finalize(&request, &mut response)?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+    use crate::plugin::{File, GenerateRequest, GenerateResponse, Settings};
+
+    fn create_sample_request() -> GenerateRequest {
+        GenerateRequest {
+            settings: None,
+            catalog: None,
+            queries: vec![],
+            sqlc_version: "test".to_string(),
+            plugin_options: vec![],
+            global_options: vec![],
+        }
+    }
+
+    fn create_sample_response() -> GenerateResponse {
+        GenerateResponse {
+            files: vec![File {
+                name: "test.rs".to_string(),
+                contents: b"// test content".to_vec(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_run_with_io_success() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let request = create_sample_request();
+        request.encode(&mut input).unwrap();
+
+        let result = run_with_io(&input[..], &mut output, |req| {
+            assert_eq!(req.sqlc_version, "test");
+            Ok(create_sample_response())
+        });
+        assert!(result.is_ok(), "run_with_io should succeed");
+
+        let response = GenerateResponse::decode(&output[..]).unwrap();
+        assert_eq!(response.files.len(), 1);
+        assert_eq!(response.files[0].name, "test.rs");
+        assert_eq!(response.files[0].contents, b"// test content");
+    }
+
+    #[test]
+    fn test_run_with_io_processor_error() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let request = create_sample_request();
+        request.encode(&mut input).unwrap();
+
+        let result = run_with_io(&input[..], &mut output, |_req| {
+            Err("Processing failed".into())
+        });
+        assert!(
+            result.is_err(),
+            "run_with_io should fail when processor fails"
+        );
+        let err = result.unwrap_err();
+        assert!(matches!(err, Error::Processor(_)));
+        assert_eq!(err.to_string(), "Processing failed");
+    }
+
+    #[test]
+    fn test_run_with_io_invalid_input() {
+        let input = b"invalid protobuf data";
+        let mut output = Vec::new();
+
+        let result = run_with_io(&input[..], &mut output, |_req| Ok(create_sample_response()));
+        assert!(
+            result.is_err(),
+            "run_with_io should fail with invalid input"
+        );
+        assert!(matches!(result.unwrap_err(), Error::Decode(_)));
+    }
+
+    #[test]
+    fn test_run_with_io_empty_input() {
+        let input: &[u8] = &[];
+        let mut output = Vec::new();
+
+        let result = run_with_io(input, &mut output, |_req| Ok(create_sample_response()));
+        assert!(
+            result.is_ok(),
+            "run_with_io should succeed with empty input (creates default request)"
+        );
+
+        let response = GenerateResponse::decode(&output[..]).unwrap();
+        assert_eq!(response.files.len(), 1);
+    }
+
+    #[test]
+    fn test_run_with_io_empty_response() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let request = create_sample_request();
+        request.encode(&mut input).unwrap();
+
+        // Processor returns empty response
+        let result = run_with_io(&input[..], &mut output, |_req| {
+            Ok(GenerateResponse { files: vec![] })
+        });
+        assert!(
+            result.is_ok(),
+            "run_with_io should succeed with empty response"
+        );
+
+        let response = GenerateResponse::decode(&output[..]).unwrap();
+        assert_eq!(response.files.len(), 0);
+    }
+
+    #[test]
+    fn test_run_with_io_multiple_files() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let request = create_sample_request();
+        request.encode(&mut input).unwrap();
+
+        let result = run_with_io(&input[..], &mut output, |_req| {
+            Ok(GenerateResponse {
+                files: vec![
+                    File {
+                        name: "file1.rs".to_string(),
+                        contents: b"content1".to_vec(),
+                    },
+                    File {
+                        name: "file2.rs".to_string(),
+                        contents: b"content2".to_vec(),
+                    },
+                ],
+            })
+        });
+        assert!(result.is_ok(), "run_with_io should succeed");
+
+        let response = GenerateResponse::decode(&output[..]).unwrap();
+        assert_eq!(response.files.len(), 2);
+        assert_eq!(response.files[0].name, "file1.rs");
+        assert_eq!(response.files[1].name, "file2.rs");
+    }
+
+    #[test]
+    fn test_run_with_io_normalizes_paths() {
+        let input = create_sample_request().encode_to_vec();
+        let mut output = Vec::new();
+
+        run_with_io(&input[..], &mut output, |_req| {
+            Ok(GenerateResponse {
+                files: vec![File {
+                    name: "./db\\models.rs".to_string(),
+                    contents: vec![],
+                }],
+            })
+        })
+        .unwrap();
+
+        let response = GenerateResponse::decode(&output[..]).unwrap();
+        assert_eq!(response.files[0].name, "db/models.rs");
+    }
+
+    #[test]
+    fn test_run_with_io_rejects_invalid_paths() {
+        let input = create_sample_request().encode_to_vec();
+        let mut output = Vec::new();
+        let files = || {
+            vec![
+                File {
+                    name: "../escape.rs".to_string(),
+                    contents: vec![],
+                },
+                File {
+                    name: "/abs.rs".to_string(),
+                    contents: vec![],
+                },
+            ]
+        };
+
+        let err = run_with_io(&input[..], &mut output, |_req| {
+            Ok(GenerateResponse { files: files() })
+        })
+        .unwrap_err();
+        assert!(matches!(&err, Error::InvalidPaths(paths) if paths.0.len() == 2));
+        assert!(err.to_string().contains("\"../escape.rs\""));
+        assert!(output.is_empty());
+
+        let options = RunOptions::default().with_validate_paths(false);
+        run_with_options(&input[..], &mut output, &options, |_req| {
+            Ok(GenerateResponse { files: files() })
+        })
+        .unwrap();
+        let response = GenerateResponse::decode(&output[..]).unwrap();
+        assert_eq!(response.files[0].name, "../escape.rs");
+    }
+
+    #[test]
+    fn test_run_with_io_preserves_request_data() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let request = GenerateRequest {
+            settings: None,
+            catalog: None,
+            queries: vec![],
+            sqlc_version: "1.2.3".to_string(),
+            plugin_options: b"test_plugin_options".to_vec(),
+            global_options: b"test_global_options".to_vec(),
+        };
+        request.encode(&mut input).unwrap();
+
+        let result = run_with_io(&input[..], &mut output, |req| {
+            assert_eq!(req.sqlc_version, "1.2.3");
+            assert_eq!(req.plugin_options, b"test_plugin_options");
+            assert_eq!(req.global_options, b"test_global_options");
+            Ok(create_sample_response())
+        });
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_run_with_io_large_content() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let request = create_sample_request();
+        request.encode(&mut input).unwrap();
+
+        let result = run_with_io(&input[..], &mut output, |_req| {
+            Ok(GenerateResponse {
+                files: vec![File {
+                    name: "large.rs".to_string(),
+                    contents: vec![b'x'; 1024 * 1024].clone(),
+                }],
+            })
+        });
         assert!(result.is_ok(), "run_with_io should handle large content");
 
         let response = GenerateResponse::decode(&output[..]).unwrap();
-        assert_eq!(response.files[0].contents.len(), 1024 * 1024);
+        assert_eq!(response.files[0].contents.len(), 1024 * 1024);
+    }
+
+    #[derive(Debug, Default, serde::Deserialize)]
+    #[serde(default)]
+    struct TestOptions {
+        package: String,
+        emit_tests: bool,
+    }
+
+    #[test]
+    fn test_run_typed_with_io_decodes_options() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let request = GenerateRequest {
+            plugin_options: br#"{"package": "db"}"#.to_vec(),
+            ..create_sample_request()
+        };
+        request.encode(&mut input).unwrap();
+
+        let result =
+            run_typed_with_io::<TestOptions, _, _, _>(&input[..], &mut output, |req, opts| {
+                assert_eq!(req.sqlc_version, "test");
+                assert_eq!(opts.package, "db");
+                assert!(!opts.emit_tests);
+                Ok(create_sample_response())
+            });
+        assert!(result.is_ok(), "run_typed_with_io should succeed");
+
+        let response = GenerateResponse::decode(&output[..]).unwrap();
+        assert_eq!(response.files.len(), 1);
+    }
+
+    #[test]
+    fn test_run_typed_with_io_global_options() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let request = GenerateRequest {
+            global_options: br#"{"emit_tests": true}"#.to_vec(),
+            ..create_sample_request()
+        };
+        request.encode(&mut input).unwrap();
+
+        let result = run_typed_with_io::<Options<TestOptions, TestOptions>, _, _, _>(
+            &input[..],
+            &mut output,
+            |_req, opts| {
+                assert!(!opts.plugin.emit_tests);
+                assert!(opts.global.emit_tests);
+                Ok(create_sample_response())
+            },
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_run_typed_with_io_rejects_unknown_option() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let request = GenerateRequest {
+            plugin_options: br#"{"pakage": "db"}"#.to_vec(),
+            ..create_sample_request()
+        };
+        request.encode(&mut input).unwrap();
+
+        let result =
+            run_typed_with_io::<TestOptions, _, _, _>(&input[..], &mut output, |_req, _opts| {
+                panic!("process should not be called with invalid options")
+            });
+        let err = result.unwrap_err();
+        assert!(matches!(err, Error::Options(_)));
+        assert_eq!(
+            err.to_string(),
+            "invalid plugin_options at `pakage`: unknown key `pakage`"
+        );
+        assert!(output.is_empty());
+    }
+
+    /// Records every hook call so tests can check the lifecycle order
+    #[derive(Default)]
+    struct RecordingPlugin {
+        calls: Vec<String>,
+    }
+
+    impl Plugin for RecordingPlugin {
+        fn configure(&mut self, request: &GenerateRequest) -> Result<(), Box<dyn StdError>> {
+            self.calls
+                .push(format!("configure {}", request.sqlc_version));
+            Ok(())
+        }
+
+        fn prepare_catalog(&mut self, catalog: &mut Catalog) -> Result<(), Box<dyn StdError>> {
+            self.calls.push("prepare_catalog".to_string());
+            catalog.default_schema = "public".to_string();
+            Ok(())
+        }
+
+        fn prepare_composites(
+            &mut self,
+            attributes: &CompositeAttributes,
+        ) -> Result<(), Box<dyn StdError>> {
+            self.calls
+                .push(format!("prepare_composites {}", attributes.len()));
+            Ok(())
+        }
+
+        fn generate_model(
+            &mut self,
+            request: &GenerateRequest,
+            table: &Table,
+            response: &mut GenerateResponse,
+        ) -> Result<(), Box<dyn StdError>> {
+            let catalog = request.catalog.as_ref().unwrap();
+            let name = &table.rel.as_ref().unwrap().name;
+            self.calls.push(format!(
+                "generate_model {}.{}",
+                catalog.default_schema, name
+            ));
+            response.files.push(File {
+                name: format!("{name}.rs"),
+                contents: vec![],
+            });
+            Ok(())
+        }
+
+        fn generate_query(
+            &mut self,
+            _request: &GenerateRequest,
+            query: &Query,
+            _response: &mut GenerateResponse,
+        ) -> Result<(), Box<dyn StdError>> {
+            self.calls.push(format!("generate_query {}", query.name));
+            Ok(())
+        }
+
+        fn finalize(
+            &mut self,
+            _request: &GenerateRequest,
+            response: &mut GenerateResponse,
+        ) -> Result<(), Box<dyn StdError>> {
+            self.calls
+                .push(format!("finalize {}", response.files.len()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_run_plugin_with_io_hook_order() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let mut builder = CatalogBuilder::new("postgresql");
+        builder
+            .parse_sql(
+                "CREATE TABLE users (id int); CREATE TABLE posts (id int);
+                 CREATE TABLE pg_catalog.pg_class (oid oid);
+                 CREATE TABLE information_schema.tables (table_name text);",
+            )
+            .unwrap();
+
+        let request = GenerateRequest {
+            catalog: Some(builder.build()),
+            queries: vec![
+                Query {
+                    name: "GetUser".to_string(),
+                    ..Default::default()
+                },
+                Query {
+                    name: "ListPosts".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..create_sample_request()
+        };
+        request.encode(&mut input).unwrap();
+
+        let mut plugin = RecordingPlugin::default();
+        run_plugin_with_io(&input[..], &mut output, &mut plugin).unwrap();
+
+        assert_eq!(
+            plugin.calls,
+            vec![
+                "configure test",
+                "prepare_catalog",
+                "prepare_composites 0",
+                "generate_query GetUser",
+                "generate_query ListPosts",
+                "generate_model public.users",
+                "generate_model public.posts",
+                "finalize 2",
+            ]
+        );
+
+        let response = GenerateResponse::decode(&output[..]).unwrap();
+        assert_eq!(response.files.len(), 2);
+        assert_eq!(response.files[0].name, "users.rs");
+    }
+
+    #[test]
+    fn test_run_plugin_with_io_include_schema() {
+        #[derive(Default)]
+        struct AuthModels {
+            models: Vec<String>,
+        }
+
+        impl Plugin for AuthModels {
+            fn include_schema(&self, schema: &Schema) -> bool {
+                schema.name == "auth"
+            }
+
+            fn generate_model(
+                &mut self,
+                _request: &GenerateRequest,
+                table: &Table,
+                _response: &mut GenerateResponse,
+            ) -> Result<(), Box<dyn StdError>> {
+                self.models.push(table.qualified_name());
+                Ok(())
+            }
+        }
+
+        let mut builder = CatalogBuilder::new("postgresql");
+        builder
+            .parse_sql("CREATE TABLE users (id int); CREATE TABLE auth.accounts (id int);")
+            .unwrap();
+
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+        GenerateRequest {
+            catalog: Some(builder.build()),
+            ..create_sample_request()
+        }
+        .encode(&mut input)
+        .unwrap();
+
+        let mut plugin = AuthModels::default();
+        run_plugin_with_io(&input[..], &mut output, &mut plugin).unwrap();
+        assert_eq!(plugin.models, ["auth.accounts"]);
+    }
+
+    #[test]
+    fn test_run_plugin_with_io_without_catalog() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        create_sample_request().encode(&mut input).unwrap();
+
+        let mut plugin = RecordingPlugin::default();
+        run_plugin_with_io(&input[..], &mut output, &mut plugin).unwrap();
+
+        assert_eq!(
+            plugin.calls,
+            vec!["configure test", "prepare_composites 0", "finalize 0"]
+        );
+    }
+
+    #[test]
+    fn test_run_plugin_with_io_default_hooks() {
+        struct Noop;
+        impl Plugin for Noop {}
+
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        create_sample_request().encode(&mut input).unwrap();
+        run_plugin_with_io(&input[..], &mut output, Noop).unwrap();
+
+        let response = GenerateResponse::decode(&output[..]).unwrap();
+        assert!(response.files.is_empty());
+    }
+
+    #[test]
+    fn test_run_plugin_with_io_hook_error_stops_generation() {
+        struct FailingConfigure {
+            generated: bool,
+        }
+
+        impl Plugin for FailingConfigure {
+            fn configure(&mut self, _request: &GenerateRequest) -> Result<(), Box<dyn StdError>> {
+                Err("bad configuration".into())
+            }
+
+            fn finalize(
+                &mut self,
+                _request: &GenerateRequest,
+                _response: &mut GenerateResponse,
+            ) -> Result<(), Box<dyn StdError>> {
+                self.generated = true;
+                Ok(())
+            }
+        }
+
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        create_sample_request().encode(&mut input).unwrap();
+
+        let mut plugin = FailingConfigure { generated: false };
+        let result = run_plugin_with_io(&input[..], &mut output, &mut plugin);
+
+        assert_eq!(result.unwrap_err().to_string(), "bad configuration");
+        assert!(!plugin.generated);
+        assert!(output.is_empty());
+    }
+
+    fn request_with_schema(schema: Vec<String>) -> GenerateRequest {
+        GenerateRequest {
+            settings: Some(Settings {
+                engine: "postgresql".to_string(),
+                schema,
+                ..Default::default()
+            }),
+            ..create_sample_request()
+        }
+    }
+
+    #[test]
+    fn test_run_with_io_schema_read_error() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let missing = std::env::temp_dir().join("sqlc-gen-core-missing-schema.sql");
+        request_with_schema(vec![missing.display().to_string()])
+            .encode(&mut input)
+            .unwrap();
+
+        let result = run_with_io(&input[..], &mut output, |_req| Ok(create_sample_response()));
+        match result.unwrap_err() {
+            Error::SchemaRead {
+                path,
+                base_dir,
+                source,
+            } => {
+                assert_eq!(path, missing);
+                assert_eq!(base_dir, None);
+                assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+            }
+            other => panic!("expected schema read error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_run_with_io_sql_parse_error() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let path = std::env::temp_dir().join(format!(
+            "sqlc-gen-core-invalid-schema-{}.sql",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            "CREATE TABLE users (id int);\nCREATE TABLE posts (id int,,);\n",
+        )
+        .unwrap();
+
+        request_with_schema(vec![path.display().to_string()])
+            .encode(&mut input)
+            .unwrap();
+
+        let result = run_with_io(&input[..], &mut output, |_req| Ok(create_sample_response()));
+        std::fs::remove_file(&path).unwrap();
+
+        let err = result.unwrap_err();
+        let message = err.to_string();
+        match err {
+            Error::SqlParse {
+                path: err_path,
+                source: crate::schema::Error::Parse { line, .. },
+            } => {
+                assert_eq!(err_path, path);
+                assert_eq!(line, 2);
+            }
+            other => panic!("expected SQL parse error, got {other:?}"),
+        }
+        assert!(message.contains(":2: "));
+        assert!(!message.contains('\n'), "{message}");
+    }
+
+    #[test]
+    fn test_error_render() {
+        let source = crate::schema::CatalogBuilder::new("postgresql")
+            .parse_sql("CREATE TABLE users (id int);\nCREATE TABLE posts (id int,,);")
+            .unwrap_err();
+        let err = Error::SqlParse {
+            path: PathBuf::from("schema.sql"),
+            source,
+        };
+
+        let rendered = err.render();
+        let lines: Vec<_> = rendered.lines().collect();
+        assert!(lines[0].starts_with("error: failed to parse schema.sql: "));
+        assert_eq!(lines[1], "  stage: parse schema");
+        assert!(lines[2].starts_with("  at: schema.sql:2:"));
+        assert_eq!(lines[3], "  sql: CREATE TABLE posts (id int,,)");
+        assert!(lines[4].starts_with("  hint: "));
+        assert_eq!(err.exit_code(), 3);
+
+        let err = Error::Processor("no queries".into());
+        assert_eq!(err.render(), "error: no queries\n  stage: generate\n");
+        assert_eq!(err.exit_code(), 1);
+    }
+
+    #[test]
+    fn test_error_render_schema_read() {
+        let not_found = || std::io::Error::from(std::io::ErrorKind::NotFound);
+        let err = Error::SchemaRead {
+            path: PathBuf::from("schema.sql"),
+            base_dir: None,
+            source: not_found(),
+        };
+        assert!(err.hint().unwrap().contains("working directory"));
+
+        let err = Error::SchemaRead {
+            path: PathBuf::from("/srv/app/schema.sql"),
+            base_dir: Some(PathBuf::from("/srv/app")),
+            source: not_found(),
+        };
+        let rendered = err.render();
+        let lines: Vec<_> = rendered.lines().collect();
+        assert_eq!(lines[2], "  file: /srv/app/schema.sql");
+        assert_eq!(lines[3], "  base dir: /srv/app");
+        assert!(!lines[4].contains("working directory"), "{rendered}");
+        assert!(!lines[4].contains("SQLC_GEN_BASE_DIR"), "{rendered}");
+    }
+
+    #[test]
+    fn test_catch_panics() {
+        let err =
+            catch_panics(false, || -> Result<(), Error> { panic!("boom {}", 42) }).unwrap_err();
+        let Error::Panic {
+            message,
+            location,
+            backtrace,
+        } = &err
+        else {
+            panic!("expected panic error, got {err:?}");
+        };
+        assert_eq!(message, "boom 42");
+        assert!(location.as_deref().unwrap().contains("runtime.rs"));
+        assert!(backtrace.is_none());
+        assert_eq!(err.exit_code(), 101);
+        assert!(err.render().contains("hint: this is a bug in the plugin"));
+
+        let err = catch_panics(true, || -> Result<(), Error> { panic!("boom") }).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Panic {
+                backtrace: Some(_),
+                ..
+            }
+        ));
+        assert!(err.render().contains("  backtrace:\n"));
+
+        assert_eq!(catch_panics(false, || Ok(7)).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_catch_panics_on_several_threads() {
+        let threads: Vec<_> = (0..8)
+            .map(|n| {
+                std::thread::spawn(move || {
+                    let err = catch_panics(false, || -> Result<(), Error> {
+                        let inner =
+                            catch_panics(false, || -> Result<(), Error> { panic!("inner") });
+                        assert!(matches!(inner, Err(Error::Panic { .. })));
+                        panic!("thread {n}")
+                    })
+                    .unwrap_err();
+                    match err {
+                        Error::Panic {
+                            message, location, ..
+                        } => (message, location),
+                        err => panic!("expected panic error, got {err:?}"),
+                    }
+                })
+            })
+            .collect();
+
+        for (n, thread) in threads.into_iter().enumerate() {
+            let (message, location) = thread.join().unwrap();
+            assert_eq!(message, format!("thread {n}"));
+            assert!(location.is_some());
+        }
+    }
+
+    #[test]
+    fn test_error_source() {
+        let err = Error::Processor("boom".into());
+        assert_eq!(err.source().unwrap().to_string(), "boom");
+
+        let err = Error::Io(std::io::Error::other("closed"));
+        assert_eq!(err.to_string(), "I/O error: closed");
+        assert!(err.source().is_some());
+    }
+
+    #[test]
+    fn test_run_with_io_schema_directory() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("001_users.sql"),
+            "CREATE TABLE users (id int);",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("002_posts.sql"),
+            "CREATE TABLE posts (id int, user_id int);",
+        )
+        .unwrap();
+
+        request_with_schema(vec![dir.path().display().to_string()])
+            .encode(&mut input)
+            .unwrap();
+
+        run_with_io(&input[..], &mut output, |req| {
+            let catalog = req.catalog.unwrap();
+            let tables: Vec<_> = catalog
+                .schemas
+                .iter()
+                .flat_map(|s| &s.tables)
+                .map(|t| t.rel.as_ref().unwrap().name.clone())
+                .collect();
+            assert_eq!(tables, vec!["users", "posts"]);
+            Ok(create_sample_response())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_run_with_io_schema_migrations() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("1_users.up.sql"),
+            "CREATE TABLE users (id int);",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("1_users.down.sql"), "DROP TABLE users;").unwrap();
+        std::fs::write(
+            dir.path().join("2_posts.sql"),
+            "-- +goose Up\nCREATE TABLE posts (id int);\n-- +goose Down\nDROP TABLE posts;\n",
+        )
+        .unwrap();
+
+        request_with_schema(vec![dir.path().display().to_string()])
+            .encode(&mut input)
+            .unwrap();
+
+        run_with_io(&input[..], &mut output, |req| {
+            let schema = &req.catalog.unwrap().schemas[0];
+            assert_eq!(schema.tables.len(), 2);
+            Ok(create_sample_response())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_run_with_options_base_dir() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("db")).unwrap();
+        std::fs::write(
+            dir.path().join("db/schema.sql"),
+            "CREATE TABLE users (id int);",
+        )
+        .unwrap();
+
+        request_with_schema(vec!["db/schema.sql".to_string()])
+            .encode(&mut input)
+            .unwrap();
+
+        let options = RunOptions::default().with_base_dir(dir.path());
+        run_with_options(&input[..], &mut output, &options, |req| {
+            assert_eq!(req.catalog.unwrap().schemas[0].tables.len(), 1);
+            Ok(create_sample_response())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_run_with_options_refuses_escaping_schema_path() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let dir = tempfile::tempdir().unwrap();
+        request_with_schema(vec!["../schema.sql".to_string()])
+            .encode(&mut input)
+            .unwrap();
+
+        let options = RunOptions::default().with_base_dir(dir.path());
+        let result = run_with_options(&input[..], &mut output, &options, |_req| {
+            Ok(create_sample_response())
+        });
+
+        let err = result.unwrap_err();
+        assert!(matches!(err, Error::SchemaPath { .. }));
+        assert!(err
+            .to_string()
+            .starts_with("schema path ../schema.sql escapes"));
+    }
+
+    #[test]
+    fn test_run_with_options_memory_file_system() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let fs = crate::fs::MemoryFileSystem::new()
+            .with_file("/project/db/1_users.sql", "CREATE TABLE users (id int);")
+            .with_file("/project/db/2_posts.sql", "CREATE TABLE posts (id int);");
+
+        request_with_schema(vec!["db".to_string()])
+            .encode(&mut input)
+            .unwrap();
+
+        let options = RunOptions::default()
+            .with_base_dir("/project")
+            .with_file_system(fs);
+        run_with_options(&input[..], &mut output, &options, |req| {
+            let schema = &req.catalog.unwrap().schemas[0];
+            let names: Vec<_> = schema
+                .tables
+                .iter()
+                .map(|t| t.rel.as_ref().unwrap().name.as_str())
+                .collect();
+            assert_eq!(names.len(), 2);
+            assert!(names.contains(&"users") && names.contains(&"posts"));
+            Ok(create_sample_response())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_run_with_options_logs_diagnostics() {
+        let input = request_with_schema(vec!["schema.sql".to_string()]).encode_to_vec();
+        let mut output = Vec::new();
+
+        let fs = crate::fs::MemoryFileSystem::new().with_file(
+            "schema.sql",
+            "CREATE TABLE users (id int);\nCREATE VIEW v AS SELECT id FROM users;",
+        );
+        let buffer = crate::log::tests::Buffer::default();
+        let options = RunOptions::default()
+            .with_file_system(fs)
+            .with_log(Logger::to_writer(Level::Debug, buffer.clone()));
+
+        run_with_options(&input[..], &mut output, &options, |_req| {
+            log::event(Level::Info, "plugin", "hello").emit();
+            Ok(create_sample_response())
+        })
+        .unwrap();
+
+        let logs = buffer.contents();
+        for expected in [
+            "[runtime] read request bytes=",
+            "[schema] read file path=schema.sql bytes=",
+            "[schema] parsed statement sql=\"CREATE TABLE users\"",
+            "[schema] skipped statement sql=\"CREATE VIEW v AS\"",
+            "sqlc-gen info [plugin] hello\n",
+            "[generate] done files=1 elapsed=",
+            "[runtime] wrote response bytes=",
+        ] {
+            assert!(logs.contains(expected), "missing {expected:?} in:\n{logs}");
+        }
+
+        assert!(!log::enabled(Level::Info));
+    }
+
+    #[test]
+    fn test_run_with_options_sets_composite_attributes() {
+        let input = request_with_schema(vec!["schema.sql".to_string()]).encode_to_vec();
+        let mut output = Vec::new();
+
+        let fs = crate::fs::MemoryFileSystem::new().with_file(
+            "schema.sql",
+            "CREATE TYPE address AS (street text, city text);\nCREATE TABLE users (home address);",
+        );
+        let options = RunOptions::default().with_file_system(fs);
+
+        run_with_options(&input[..], &mut output, &options, |req| {
+            let catalog = req.catalog.as_ref().unwrap();
+            let home = &catalog.schemas[0].tables[0].columns[0];
+            let attributes = crate::composite::current();
+            assert_eq!(attributes.resolve(home).map(<[_]>::len), Some(2));
+            Ok(create_sample_response())
+        })
+        .unwrap();
+
+        assert!(crate::composite::current().is_empty());
+    }
+
+    #[test]
+    fn test_run_plugin_with_io_passes_composite_attributes() {
+        let dir = tempfile::tempdir().unwrap();
+        let schema = dir.path().join("schema.sql");
+        std::fs::write(
+            &schema,
+            "CREATE TYPE address AS (street text, city text);\nCREATE TABLE users (home address);",
+        )
+        .unwrap();
+
+        let input = request_with_schema(vec![schema.display().to_string()]).encode_to_vec();
+        let mut output = Vec::new();
+        let mut plugin = RecordingPlugin::default();
+        run_plugin_with_io(&input[..], &mut output, &mut plugin).unwrap();
+
+        assert!(plugin.calls.contains(&"prepare_composites 1".to_string()));
+    }
+
+    #[test]
+    fn test_run_with_options_disabled_file_system() {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+
+        let catalog = Catalog {
+            default_schema: "public".to_string(),
+            schemas: vec![crate::plugin::Schema {
+                name: "public".to_string(),
+                tables: vec![Table {
+                    rel: Some(crate::plugin::Identifier {
+                        catalog: String::new(),
+                        schema: "public".to_string(),
+                        name: "users".to_string(),
+                    }),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        GenerateRequest {
+            catalog: Some(catalog.clone()),
+            ..request_with_schema(vec!["schema.sql".to_string()])
+        }
+        .encode(&mut input)
+        .unwrap();
+
+        let options = RunOptions::default().with_file_system(crate::fs::DisabledFileSystem);
+        let expected = Some(catalog);
+        run_with_options(&input[..], &mut output, &options, |req| {
+            // Schema entries are skipped and sqlc's catalog is kept
+            assert_eq!(req.catalog, expected);
+            Ok(create_sample_response())
+        })
+        .unwrap();
+
+        // Requests without schema entries never touch the file system
+        let mut input = Vec::new();
+        create_sample_request().encode(&mut input).unwrap();
+        run_with_options(&input[..], &mut output, &options, |_req| {
+            Ok(create_sample_response())
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_capture_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("request.bin");
+        std::fs::write(
+            dir.path().join("schema.sql"),
+            "CREATE TABLE users (id int);",
+        )
+        .unwrap();
+
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+        request_with_schema(vec!["schema.sql".to_string()])
+            .encode(&mut input)
+            .unwrap();
+
+        let options = RunOptions::default()
+            .with_base_dir(dir.path())
+            .with_capture(&path);
+        run_with_options(&input[..], &mut output, &options, |_req| {
+            Ok(create_sample_response())
+        })
+        .unwrap();
+
+        // The raw request is captured, before schema enrichment
+        assert_eq!(std::fs::read(&path).unwrap(), input);
+        assert_eq!(std::fs::read(response_capture_path(&path)).unwrap(), output);
+
+        let response = replay_with_options(&path, &options, |req| {
+            assert_eq!(req.catalog.unwrap().schemas[0].tables.len(), 1);
+            Ok(create_sample_response())
+        })
+        .unwrap();
+        assert_eq!(response, create_sample_response());
+    }
+
+    #[test]
+    fn test_capture_write_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+        create_sample_request().encode(&mut input).unwrap();
+
+        let buffer = crate::log::tests::Buffer::default();
+        let options = RunOptions::default()
+            .with_capture(dir.path().join("missing/request.bin"))
+            .with_log(Logger::to_writer(Level::Warn, buffer.clone()));
+        run_with_options(&input[..], &mut output, &options, |_req| {
+            Ok(create_sample_response())
+        })
+        .unwrap();
+
+        let response = GenerateResponse::decode(&output[..]).unwrap();
+        assert_eq!(response, create_sample_response());
+        let logs = buffer.contents();
+        assert!(
+            logs.contains("[capture] failed to write capture file path="),
+            "{logs}"
+        );
+    }
+
+    #[test]
+    fn test_capture_write_error_with_logging_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+        create_sample_request().encode(&mut input).unwrap();
+
+        let buffer = crate::log::tests::Buffer::default();
+        let mut log = Logger::to_writer(Level::Error, buffer.clone());
+        log.level = None;
+        let options = RunOptions::default()
+            .with_capture(dir.path().join("missing/request.bin"))
+            .with_log(log);
+        run_with_options(&input[..], &mut output, &options, |_req| {
+            Ok(create_sample_response())
+        })
+        .unwrap();
+
+        let logs = buffer.contents();
+        assert!(
+            logs.starts_with("sqlc-gen warn [capture] failed to write capture file path="),
+            "{logs}"
+        );
+        assert_eq!(logs.lines().count(), 2, "{logs}");
+    }
+
+    #[test]
+    fn test_env_logger() {
+        let disabled = |buffer: &crate::log::tests::Buffer| {
+            let mut log = Logger::to_writer(Level::Error, buffer.clone());
+            log.level = None;
+            log
+        };
+
+        let buffer = crate::log::tests::Buffer::default();
+        assert_eq!(env_logger(None, disabled(&buffer)).level, None);
+        assert_eq!(env_logger(Some(""), disabled(&buffer)).level, None);
+        assert_eq!(
+            env_logger(Some("debug"), disabled(&buffer)).level,
+            Some(Level::Debug)
+        );
+        assert_eq!(buffer.contents(), "");
+
+        assert_eq!(env_logger(Some("verbose"), disabled(&buffer)).level, None);
+        assert_eq!(
+            buffer.contents(),
+            "sqlc-gen warn [runtime] ignoring unknown SQLC_GEN_LOG level value=verbose expected=error|warn|info|debug|trace\n"
+        );
+    }
+
+    #[test]
+    fn test_capture_removes_stale_response() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("request.bin");
+        let response_path = response_capture_path(&path);
+        std::fs::write(&response_path, b"previous response").unwrap();
+
+        let input = create_sample_request().encode_to_vec();
+        let mut output = Vec::new();
+        let options = RunOptions::default().with_capture(&path);
+        let err = run_with_options(&input[..], &mut output, &options, |_req| {
+            Err("generation failed".into())
+        })
+        .unwrap_err();
+
+        assert!(matches!(err, Error::Processor(_)));
+        assert_eq!(std::fs::read(&path).unwrap(), input);
+        assert!(!response_path.exists());
+    }
+
+    #[test]
+    fn test_replay_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = replay(dir.path().join("request.bin"), |_req| {
+            Ok(create_sample_response())
+        })
+        .unwrap_err();
+
+        assert!(matches!(err, Error::Capture { .. }));
+        assert!(err.to_string().starts_with("failed to access capture file"));
     }
 }
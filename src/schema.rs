@@ -1,16 +1,87 @@
 //! SQL schema parsing and constraint extraction.
 //!
 //! This module provides functionality to parse SQL schema files and extract
-//! constraint information (primary keys, foreign keys, indexes)
-
-use crate::plugin::{Column, ForeignKey, Identifier, Index, PrimaryKey, Schema, Table};
+//! constraint information (primary keys, foreign keys, indexes) and enum types
+//! (`CREATE TYPE ... AS ENUM`, updated by `ALTER TYPE`, and MySQL's inline
+//! `ENUM(...)` column types, named `<table>_<column>` like sqlc does) and
+//! composite types (`CREATE TYPE ... AS (...)`, whose attributes are kept in
+//! [`CompositeAttributes`]). Schemas are registered by `CREATE SCHEMA` and
+//! commented by `COMMENT ON SCHEMA`; unqualified objects are placed into the
+//! default schema of the engine (see [`engine_default_schema`]).
+//!
+//! Names are stored the way the engine stores them: without quotes, and for
+//! PostgreSQL and MySQL with unquoted identifiers folded to lower case, so
+//! that `CREATE TABLE Users` and sqlc's catalog both name the table `users`
+//! while `"Users"` keeps its case.
+
+use crate::composite::CompositeAttributes;
+use crate::log::{self, Level};
+use crate::merge::{MergePolicies, MergeReport};
+use crate::plugin::{
+    Column, CompositeType, Enum, ForeignKey, Identifier, Index, PrimaryKey, Schema, Table,
+};
 use sqlparser::ast::{
-    ColumnOption, CreateIndex, CreateTable, ObjectName, Statement, TableConstraint,
+    AlterType, AlterTypeAddValue, AlterTypeAddValuePosition, AlterTypeOperation, AlterTypeRename,
+    AlterTypeRenameValue, ColumnOption, CommentObject, CreateIndex, CreateTable, DataType,
+    EnumMember, Expr, Ident, IndexColumn, ObjectName, SchemaName, Statement, TableConstraint, Use,
+    UserDefinedTypeCompositeAttributeDef, UserDefinedTypeRepresentation,
 };
 use sqlparser::dialect::dialect_from_str;
-use sqlparser::parser::Parser;
+use sqlparser::parser::{Parser, ParserError};
 use std::collections::HashMap;
-use std::error::Error;
+use std::fmt;
+
+/// Error produced while parsing SQL schema definitions.
+#[derive(Debug)]
+pub enum Error {
+    /// The builder's dialect is not supported by `sqlparser`
+    UnknownDialect(String),
+
+    /// The SQL could not be parsed
+    Parse {
+        /// Line of the error, starting from 1 (0 when unknown)
+        line: u64,
+        /// Column of the error, starting from 1 (0 when unknown)
+        column: u64,
+        /// Text of the statement containing the error (empty when unknown)
+        statement: String,
+        /// Underlying parser error
+        source: ParserError,
+    },
+}
+
+impl Error {
+    /// Build a parse error, locating the failing statement within `sql`
+    pub(crate) fn parse(sql: &str, source: ParserError) -> Self {
+        let (line, column) = error_location(&source).unwrap_or((0, 0));
+        let statement = statement_at(sql, line, column).unwrap_or_default();
+
+        Self::Parse {
+            line,
+            column,
+            statement,
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownDialect(dialect) => write!(f, "Unknown dialect: {dialect}"),
+            Self::Parse { source, .. } => write!(f, "{source}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::UnknownDialect(_) => None,
+            Self::Parse { source, .. } => Some(source),
+        }
+    }
+}
 
 /// Builder for creating a `plugin::Catalog` from SQL schema definitions.
 ///
@@ -56,10 +127,50 @@ pub struct CatalogBuilder {
 
     /// Map of schema names to schema definitions
     ///
-    /// The key is the schema name (empty string for default/unnamed schema),
-    /// and the value contains all tables within that schema.
+    /// The key is the schema name (unqualified objects are placed in the
+    /// [`default_schema`](Self::default_schema), which is the empty string
+    /// for engines without one), and the value contains all tables within
+    /// that schema.
     /// Access this directly to iterate over all schemas or look up specific ones.
     pub schemas: HashMap<String, Schema>,
+
+    /// Name of the schema unqualified names refer to
+    ///
+    /// Initialized from the engine by [`new`](Self::new) and updated by a
+    /// `USE` statement. Copied to `Catalog.default_schema` by
+    /// [`build`](Self::build).
+    pub default_schema: String,
+
+    /// Name of the built catalog, copied to `Catalog.name`
+    pub name: String,
+
+    /// Comment of the built catalog, copied to `Catalog.comment`
+    pub comment: String,
+
+    /// Order of the schemas in the built catalog
+    pub schema_order: SchemaOrder,
+
+    /// Schema names in the order they were first declared
+    declared: Vec<String>,
+
+    /// Attributes of the parsed composite types
+    composites: CompositeAttributes,
+}
+
+/// Order of the schemas in a catalog built by [`CatalogBuilder::build`].
+///
+/// Tables, enums, composite types and indexes always keep their source order
+/// within a schema, so the same input always produces the same catalog.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchemaOrder {
+    /// The default schema first, then the other schemas by name
+    #[default]
+    DefaultFirst,
+
+    /// Schemas in the order they were first declared, by parsed statements or
+    /// merged catalogs; schemas inserted directly into
+    /// [`CatalogBuilder::schemas`] come last, by name
+    Declaration,
 }
 
 impl Default for CatalogBuilder {
@@ -67,26 +178,132 @@ impl Default for CatalogBuilder {
         Self {
             dialect: "generic".to_string(),
             schemas: HashMap::new(),
+            default_schema: String::new(),
+            name: String::new(),
+            comment: String::new(),
+            schema_order: SchemaOrder::default(),
+            declared: Vec::new(),
+            composites: CompositeAttributes::default(),
         }
     }
 }
 
 impl CatalogBuilder {
     /// Create a new empty builder with the specified SQL dialect
+    ///
+    /// The [`default_schema`](Self::default_schema) is the one of the engine
+    /// named by `dialect`, see [`engine_default_schema`].
     pub fn new(dialect: &str) -> Self {
         Self {
             dialect: dialect.to_string(),
-            schemas: HashMap::new(),
+            default_schema: engine_default_schema(dialect).to_string(),
+            ..Self::default()
         }
     }
 
+    /// Set the schema unqualified names refer to, such as the database name
+    /// for MySQL
+    pub fn with_default_schema(mut self, schema: impl Into<String>) -> Self {
+        self.default_schema = schema.into();
+        self
+    }
+
+    /// Set the name of the built catalog
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Set the comment of the built catalog
+    pub fn with_comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = comment.into();
+        self
+    }
+
+    /// Set the order of the schemas in the built catalog
+    pub fn with_schema_order(mut self, order: SchemaOrder) -> Self {
+        self.schema_order = order;
+        self
+    }
+
+    /// Attributes of the composite types parsed so far
+    ///
+    /// `plugin::CompositeType` has no room for attributes, so they are kept
+    /// apart from the built catalog. See [`crate::composite`].
+    pub fn composite_attributes(&self) -> CompositeAttributes {
+        self.composites
+            .clone()
+            .with_default_schema(self.default_schema.clone())
+    }
+
     /// Build the `plugin::Catalog` from the parsed schema information.
-    pub fn build(self) -> crate::plugin::Catalog {
+    ///
+    /// Schemas are ordered according to [`schema_order`](Self::schema_order).
+    pub fn build(mut self) -> crate::plugin::Catalog {
+        let mut names: Vec<String> = self.schemas.keys().cloned().collect();
+        names.sort();
+
+        let mut ordered = Vec::with_capacity(names.len());
+        match self.schema_order {
+            SchemaOrder::DefaultFirst => {
+                if let Some(schema) = self.schemas.remove(&self.default_schema) {
+                    ordered.push(schema);
+                }
+            }
+            SchemaOrder::Declaration => {
+                for name in &self.declared {
+                    if let Some(schema) = self.schemas.remove(name) {
+                        ordered.push(schema);
+                    }
+                }
+            }
+        }
+        ordered.extend(names.iter().filter_map(|name| self.schemas.remove(name)));
+
         crate::plugin::Catalog {
-            name: "".to_string(),
-            default_schema: "".to_string(),
-            comment: "".to_string(),
-            schemas: self.schemas.into_values().collect(),
+            name: self.name,
+            default_schema: self.default_schema,
+            comment: self.comment,
+            schemas: ordered,
+        }
+    }
+
+    /// Schema and object name of a possibly qualified name
+    ///
+    /// Unqualified names refer to the default schema.
+    fn qualify(&self, name: &ObjectName) -> (String, String) {
+        let (schema_name, object_name) = parse_qualified_name(&self.dialect, name);
+        if schema_name.is_empty() {
+            (self.default_schema.clone(), object_name)
+        } else {
+            (schema_name, object_name)
+        }
+    }
+
+    /// Schema named `name`, declared and created on first use
+    fn schema_entry(&mut self, name: &str) -> &mut Schema {
+        self.declare_schema(name);
+        self.schemas
+            .entry(name.to_string())
+            .or_insert_with(|| Schema {
+                name: name.to_string(),
+                ..Default::default()
+            })
+    }
+
+    /// Record the first declaration of a schema, for [`SchemaOrder::Declaration`]
+    pub(crate) fn declare_schema(&mut self, name: &str) {
+        if !self.declared.iter().any(|declared| declared == name) {
+            self.declared.push(name.to_string());
+        }
+    }
+
+    /// Record that the declared schema `from` is now named `to`
+    pub(crate) fn rename_declared_schema(&mut self, from: &str, to: &str) {
+        if self.declared.iter().any(|declared| declared == to) {
+            self.declared.retain(|declared| declared != from);
+        } else if let Some(declared) = self.declared.iter_mut().find(|d| *d == from) {
+            *declared = to.to_string();
         }
     }
 
@@ -95,91 +312,111 @@ impl CatalogBuilder {
     /// If a schema from the `other` catalog already exists in the builder, its
     /// contents (tables, enums, etc.) will be merged into the existing schema.
     /// If an item (table, enum, etc.) with the same name already exists within
-    /// a schema, it will be ignored to prevent duplicates.
+    /// a schema, it will be ignored to prevent duplicates. The returned report
+    /// lists the ignored items that differ from the existing ones.
+    ///
+    /// This is [`merge_catalog_with`](Self::merge_catalog_with) with the
+    /// default [`MergePolicies`]; use it directly to choose another policy.
     ///
     /// # Arguments
     ///
     /// * `other` - A `plugin::Catalog` to merge into the builder.
-    pub fn merge_catalog(&mut self, other: crate::plugin::Catalog) {
-        for other_schema in other.schemas {
-            let builder_schema = self
-                .schemas
-                .entry(other_schema.name.clone())
-                .or_insert_with(|| Schema {
-                    name: other_schema.name.clone(),
-                    ..Default::default()
-                });
-
-            let existing_tables: std::collections::HashSet<String> = builder_schema
-                .tables
-                .iter()
-                .filter_map(|t| t.rel.as_ref().map(|r| r.name.clone()))
-                .collect();
-            for table in other_schema.tables {
-                if let Some(rel) = &table.rel {
-                    if !existing_tables.contains(&rel.name) {
-                        builder_schema.tables.push(table);
-                    }
-                }
-            }
-
-            let existing_enums: std::collections::HashSet<String> = builder_schema
-                .enums
-                .iter()
-                .map(|e| e.name.clone())
-                .collect();
-            for r#enum in other_schema.enums {
-                if !existing_enums.contains(&r#enum.name) {
-                    builder_schema.enums.push(r#enum);
-                }
-            }
-
-            let existing_composites: std::collections::HashSet<String> = builder_schema
-                .composite_types
-                .iter()
-                .map(|c| c.name.clone())
-                .collect();
-            for composite in other_schema.composite_types {
-                if !existing_composites.contains(&composite.name) {
-                    builder_schema.composite_types.push(composite);
-                }
-            }
-        }
+    pub fn merge_catalog(&mut self, other: crate::plugin::Catalog) -> MergeReport {
+        self.merge_catalog_with(other, &MergePolicies::default())
+            .expect("keeping existing objects never fails")
     }
 
-    /// Parse SQL schema from a string and return a Schema
-    pub fn parse_sql(&mut self, sql: &str) -> Result<(), Box<dyn Error>> {
-        let dialect =
-            dialect_from_str(&self.dialect).ok_or(format!("Unknown dialect: {}", self.dialect))?;
-        let statements = Parser::parse_sql(dialect.as_ref(), sql)?;
+    /// Parse SQL schema from a string and add its definitions to the builder
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnknownDialect`] if the builder's dialect is not
+    /// supported, or [`Error::Parse`] with the location and text of the
+    /// failing statement if the SQL cannot be parsed.
+    pub fn parse_sql(&mut self, sql: &str) -> Result<(), Error> {
+        let dialect = dialect_from_str(&self.dialect)
+            .ok_or_else(|| Error::UnknownDialect(self.dialect.clone()))?;
+        let statements =
+            Parser::parse_sql(dialect.as_ref(), sql).map_err(|err| Error::parse(sql, err))?;
 
         for statement in statements {
+            if log::enabled(Level::Debug) {
+                let handled = matches!(
+                    statement,
+                    Statement::CreateTable(_)
+                        | Statement::CreateIndex(_)
+                        | Statement::AlterTable { .. }
+                        | Statement::CreateType {
+                            representation: UserDefinedTypeRepresentation::Enum { .. }
+                                | UserDefinedTypeRepresentation::Composite { .. },
+                            ..
+                        }
+                        | Statement::AlterType(_)
+                        | Statement::CreateSchema { .. }
+                        | Statement::CreateDatabase { .. }
+                        | Statement::Comment {
+                            object_type: CommentObject::Schema,
+                            ..
+                        }
+                        | Statement::Use(_)
+                );
+                let message = if handled {
+                    "parsed statement"
+                } else {
+                    "skipped statement"
+                };
+                log::event(Level::Debug, "schema", message)
+                    .field("sql", statement_summary(&statement))
+                    .emit();
+            }
+
             match statement {
                 Statement::CreateTable(table) => {
-                    let table_def = Table::from_create_table(&table);
-                    let schema_name = table_def
-                        .rel
-                        .as_ref()
-                        .map(|r| r.schema.clone())
-                        .unwrap_or_default();
-
-                    let schema =
-                        self.schemas
-                            .entry(schema_name.clone())
-                            .or_insert_with(|| Schema {
-                                name: schema_name.clone(),
-                                comment: String::new(),
-                                tables: Vec::new(),
-                                enums: Vec::new(),
-                                composite_types: Vec::new(),
-                            });
+                    let mut table_def = Table::from_create_table(&table, &self.dialect);
+                    let (schema_name, table_name) = self.qualify(&table.name);
+                    if let Some(rel) = &mut table_def.rel {
+                        rel.schema = schema_name.clone();
+                        rel.name = table_name.clone();
+                    }
 
+                    // MySQL declares enums inline, as column types
+                    let enums: Vec<Enum> = if self.dialect.eq_ignore_ascii_case("mysql") {
+                        table
+                            .columns
+                            .iter()
+                            .zip(&mut table_def.columns)
+                            .filter_map(|(column_def, column)| {
+                                let r#enum =
+                                    Enum::from_column_def(&table_name, column_def, &self.dialect)?;
+                                if let Some(r#type) = &mut column.r#type {
+                                    r#type.name = r#enum.name.clone();
+                                }
+                                Some(r#enum)
+                            })
+                            .collect()
+                    } else {
+                        Vec::new()
+                    };
+
+                    let schema = self.schema_entry(&schema_name);
                     schema.tables.push(table_def);
+                    for r#enum in enums {
+                        match schema.enums.iter_mut().find(|e| e.name == r#enum.name) {
+                            Some(existing) => *existing = r#enum,
+                            None => schema.enums.push(r#enum),
+                        }
+                    }
                 }
                 Statement::CreateIndex(index) => {
-                    let (schema_name, table_name) = parse_qualified_name(&index.table_name);
+                    let (schema_name, table_name) = self.qualify(&index.table_name);
 
                     if let Some(schema) = self.schemas.get_mut(&schema_name) {
+                        let mut index_def =
+                            Index::from_create_index(&index, &table_name, &self.dialect);
+                        if index.name.is_none() {
+                            index_def.name = unused_relation_name(schema, &index_def.name);
+                        }
+
                         if let Some(table) = schema.tables.iter_mut().find(|t| {
                             if let Some(rel) = &t.rel {
                                 rel.name == table_name
@@ -187,7 +424,6 @@ impl CatalogBuilder {
                                 false
                             }
                         }) {
-                            let index_def = Index::from_create_index(&index);
                             table.indexes.push(index_def);
                         }
                     }
@@ -195,7 +431,7 @@ impl CatalogBuilder {
                 Statement::AlterTable {
                     name, operations, ..
                 } => {
-                    let (schema_name, table_name) = parse_qualified_name(&name);
+                    let (schema_name, table_name) = self.qualify(&name);
 
                     if let Some(schema) = self.schemas.get_mut(&schema_name) {
                         if let Some(table) = schema.tables.iter_mut().find(|t| {
@@ -211,12 +447,103 @@ impl CatalogBuilder {
                                     ..
                                 } = operation
                                 {
-                                    table.add_constraint(constraint);
+                                    table.add_constraint(constraint, &self.dialect);
                                 }
                             }
                         }
                     }
                 }
+                Statement::CreateType {
+                    name,
+                    representation: UserDefinedTypeRepresentation::Enum { labels },
+                } => {
+                    let (schema_name, type_name) = self.qualify(&name);
+                    let r#enum = Enum::from_labels(type_name, &labels);
+
+                    let schema = self.schema_entry(&schema_name);
+                    match schema.enums.iter_mut().find(|e| e.name == r#enum.name) {
+                        Some(existing) => *existing = r#enum,
+                        None => schema.enums.push(r#enum),
+                    }
+                }
+                Statement::CreateType {
+                    name,
+                    representation: UserDefinedTypeRepresentation::Composite { attributes },
+                } => {
+                    let (schema_name, type_name) = self.qualify(&name);
+                    let columns = attributes
+                        .iter()
+                        .map(|attribute| Column::from_attribute_def(attribute, &self.dialect))
+                        .collect();
+                    self.composites.insert(&schema_name, &type_name, columns);
+
+                    let schema = self.schema_entry(&schema_name);
+                    if !schema.composite_types.iter().any(|c| c.name == type_name) {
+                        schema.composite_types.push(CompositeType {
+                            name: type_name,
+                            comment: String::new(),
+                        });
+                    }
+                }
+                Statement::AlterType(AlterType { name, operation }) => {
+                    let (schema_name, type_name) = self.qualify(&name);
+                    let Some(schema) = self.schemas.get_mut(&schema_name) else {
+                        continue;
+                    };
+
+                    if let Some(r#enum) = schema.enums.iter_mut().find(|e| e.name == type_name) {
+                        r#enum.alter(operation, &self.dialect);
+                    } else if let AlterTypeOperation::Rename(AlterTypeRename { new_name }) =
+                        operation
+                    {
+                        if let Some(composite) = schema
+                            .composite_types
+                            .iter_mut()
+                            .find(|c| c.name == type_name)
+                        {
+                            composite.name = ident_name(&self.dialect, &new_name);
+                            self.composites
+                                .rename(&schema_name, &type_name, &composite.name);
+                        }
+                    }
+                }
+                Statement::CreateSchema {
+                    schema_name: SchemaName::Simple(name) | SchemaName::NamedAuthorization(name, _),
+                    ..
+                } => {
+                    let name = object_name(&self.dialect, &name);
+                    self.schema_entry(&name);
+                }
+                Statement::CreateDatabase { db_name, .. }
+                    if self.dialect.eq_ignore_ascii_case("mysql") =>
+                {
+                    // Databases are schemas in MySQL
+                    let name = object_name(&self.dialect, &db_name);
+                    self.schema_entry(&name);
+                }
+                Statement::CreateSchema {
+                    schema_name: SchemaName::UnnamedAuthorization(user),
+                    ..
+                } => {
+                    // The schema is named after the user
+                    let name = ident_name(&self.dialect, &user);
+                    self.schema_entry(&name);
+                }
+                Statement::Comment {
+                    object_type: CommentObject::Schema,
+                    object_name: name,
+                    comment,
+                    ..
+                } => {
+                    let name = object_name(&self.dialect, &name);
+                    if let Some(schema) = self.schemas.get_mut(&name) {
+                        schema.comment = comment.unwrap_or_default();
+                    }
+                }
+                Statement::Use(Use::Object(name) | Use::Database(name) | Use::Schema(name)) => {
+                    // MySQL selects the database unqualified names refer to
+                    self.default_schema = object_name(&self.dialect, &name);
+                }
                 _ => {
                     // Ignore other statements (CREATE VIEW, INSERT, etc.)
                 }
@@ -227,6 +554,42 @@ impl CatalogBuilder {
     }
 }
 
+/// Schema unqualified names refer to in `engine`, as sqlc names it
+///
+/// This is `public` for PostgreSQL and MySQL, whose sqlc catalog also names
+/// its default schema `public`, and `main` for SQLite. Other engines have no
+/// default schema and get the empty string. For MySQL, a `USE` statement or
+/// [`CatalogBuilder::with_default_schema`] replaces it with the name of the
+/// current database.
+///
+/// # Example
+///
+/// ```
+/// use sqlc_gen_core::schema::engine_default_schema;
+///
+/// assert_eq!(engine_default_schema("postgresql"), "public");
+/// assert_eq!(engine_default_schema("sqlite"), "main");
+/// assert_eq!(engine_default_schema("mysql"), "public");
+/// assert_eq!(engine_default_schema("generic"), "");
+/// ```
+pub fn engine_default_schema(engine: &str) -> &'static str {
+    match engine.to_ascii_lowercase().as_str() {
+        "postgresql" | "postgres" | "mysql" => "public",
+        "sqlite" => "main",
+        _ => "",
+    }
+}
+
+/// Leading keywords and name of a statement, for diagnostics
+fn statement_summary(statement: &Statement) -> String {
+    let sql = statement.to_string();
+    let head = sql.split('(').next().unwrap_or_default();
+    head.split_whitespace()
+        .take(4)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 impl Table {
     #[cfg(test)]
     fn new_for_test(name: &str, schema: Option<&str>) -> Self {
@@ -245,13 +608,8 @@ impl Table {
     }
 
     /// Create a Table from a CREATE TABLE statement
-    pub(crate) fn from_create_table(create_table: &CreateTable) -> Self {
-        let name = create_table.name.0.last().unwrap().to_string();
-        let schema_name = if create_table.name.0.len() > 1 {
-            create_table.name.0[0].to_string()
-        } else {
-            String::new()
-        };
+    pub(crate) fn from_create_table(create_table: &CreateTable, dialect: &str) -> Self {
+        let (schema_name, name) = parse_qualified_name(dialect, &create_table.name);
 
         let mut table = Self {
             rel: Some(Identifier {
@@ -263,7 +621,7 @@ impl Table {
             columns: create_table
                 .columns
                 .iter()
-                .map(Column::from_column_def)
+                .map(|column| Column::from_column_def(column, dialect))
                 .collect(),
             primary_key: None,
             foreign_keys: Vec::new(),
@@ -272,14 +630,19 @@ impl Table {
 
         // Extract inline column constraints (e.g., column_name PRIMARY KEY)
         for column in &create_table.columns {
+            let column_name = ident_name(dialect, &column.name);
             for option in &column.options {
                 // Try to create a primary key constraint
-                if let Some(pk) = PrimaryKey::from_column_option(column.name.to_string(), option) {
+                if let Some(pk) =
+                    PrimaryKey::from_column_option(column_name.clone(), option, dialect)
+                {
                     table.primary_key = Some(pk);
                 }
 
                 // Try to create a foreign key constraint
-                if let Some(fk) = ForeignKey::from_column_option(column.name.to_string(), option) {
+                if let Some(fk) =
+                    ForeignKey::from_column_option(column_name.clone(), option, dialect)
+                {
                     table.foreign_keys.push(fk);
                 }
             }
@@ -287,24 +650,24 @@ impl Table {
 
         // Extract table-level constraints
         for constraint in &create_table.constraints {
-            table.add_constraint(constraint.clone());
+            table.add_constraint(constraint.clone(), dialect);
         }
 
         table
     }
 
     /// Add a constraint to the table from a TableConstraint
-    pub(crate) fn add_constraint(&mut self, constraint: TableConstraint) {
+    pub(crate) fn add_constraint(&mut self, constraint: TableConstraint, dialect: &str) {
         match constraint {
             pk @ TableConstraint::PrimaryKey { .. } => {
-                self.primary_key = Some(PrimaryKey::from_table_constraint(pk));
+                self.primary_key = Some(PrimaryKey::from_table_constraint(pk, dialect));
             }
             fk @ TableConstraint::ForeignKey { .. } => {
                 self.foreign_keys
-                    .push(ForeignKey::from_table_constraint(fk));
+                    .push(ForeignKey::from_table_constraint(fk, dialect));
             }
             uq @ TableConstraint::Unique { name: Some(_), .. } => {
-                self.indexes.push(Index::from_table_constraint(uq));
+                self.indexes.push(Index::from_table_constraint(uq, dialect));
             }
             _ => {
                 // Ignore unnamed unique constraints and other constraint types
@@ -338,7 +701,7 @@ impl Table {
 
 impl Column {
     /// Create column from its definition
-    pub(crate) fn from_column_def(column: &sqlparser::ast::ColumnDef) -> Self {
+    pub(crate) fn from_column_def(column: &sqlparser::ast::ColumnDef, dialect: &str) -> Self {
         // Check if column is nullable (NOT NULL or PRIMARY KEY constraint)
         let has_not_null = column
             .options
@@ -356,9 +719,10 @@ impl Column {
         });
 
         let not_null = has_not_null || is_primary_key;
+        let name = ident_name(dialect, &column.name);
 
         Self {
-            name: column.name.to_string(),
+            name: name.clone(),
             not_null,
             is_array: false,
             comment: String::new(),
@@ -375,41 +739,157 @@ impl Column {
             }),
             is_sqlc_slice: false,
             embed_table: None,
-            original_name: column.name.to_string(),
+            original_name: name,
             unsigned: false,
             array_dims: 0,
         }
     }
+
+    /// Create a column from an attribute of a composite type
+    pub(crate) fn from_attribute_def(
+        attribute: &UserDefinedTypeCompositeAttributeDef,
+        dialect: &str,
+    ) -> Self {
+        let name = ident_name(dialect, &attribute.name);
+
+        Self {
+            name: name.clone(),
+            r#type: Some(Identifier {
+                catalog: String::new(),
+                schema: String::new(),
+                name: attribute.data_type.to_string(),
+            }),
+            original_name: name,
+            ..Default::default()
+        }
+    }
+}
+
+impl Enum {
+    /// Create an Enum from the labels of a `CREATE TYPE ... AS ENUM` statement
+    pub(crate) fn from_labels(name: String, labels: &[Ident]) -> Self {
+        Self {
+            name,
+            vals: labels.iter().map(|label| label.value.clone()).collect(),
+            comment: String::new(),
+        }
+    }
+
+    /// Create an Enum from a MySQL `ENUM(...)` column, named `<table>_<column>`
+    ///
+    /// Returns `None` when the column has another type.
+    pub(crate) fn from_column_def(
+        table_name: &str,
+        column: &sqlparser::ast::ColumnDef,
+        dialect: &str,
+    ) -> Option<Self> {
+        let DataType::Enum(members, _) = &column.data_type else {
+            return None;
+        };
+
+        Some(Self {
+            name: format!("{table_name}_{}", ident_name(dialect, &column.name)),
+            vals: members
+                .iter()
+                .map(|member| match member {
+                    EnumMember::Name(name) | EnumMember::NamedValue(name, _) => name.clone(),
+                })
+                .collect(),
+            comment: String::new(),
+        })
+    }
+
+    /// Apply an `ALTER TYPE` operation
+    ///
+    /// `ADD VALUE` inserts the value before or after an existing value, or at
+    /// the end, and is ignored if the value already exists. `RENAME VALUE`
+    /// and `RENAME TO` rename a value and the type itself.
+    pub(crate) fn alter(&mut self, operation: AlterTypeOperation, dialect: &str) {
+        match operation {
+            AlterTypeOperation::AddValue(AlterTypeAddValue {
+                value, position, ..
+            }) => {
+                if self.vals.contains(&value.value) {
+                    return;
+                }
+
+                let index = match &position {
+                    Some(AlterTypeAddValuePosition::Before(other)) => self.position(&other.value),
+                    Some(AlterTypeAddValuePosition::After(other)) => {
+                        self.position(&other.value).map(|index| index + 1)
+                    }
+                    None => None,
+                };
+                self.vals
+                    .insert(index.unwrap_or(self.vals.len()), value.value);
+            }
+            AlterTypeOperation::RenameValue(AlterTypeRenameValue { from, to }) => {
+                if let Some(index) = self.position(&from.value) {
+                    self.vals[index] = to.value;
+                }
+            }
+            AlterTypeOperation::Rename(AlterTypeRename { new_name }) => {
+                self.name = ident_name(dialect, &new_name);
+            }
+        }
+    }
+
+    /// Index of a value
+    fn position(&self, value: &str) -> Option<usize> {
+        self.vals.iter().position(|val| val == value)
+    }
 }
 
 impl Index {
     /// Create an Index from a CREATE INDEX statement
-    fn from_create_index(create_index: &CreateIndex) -> Self {
-        create_index
-            .name
-            .as_ref()
-            .map(|name| Self {
-                name: name.to_string(),
-                columns: create_index
+    ///
+    /// Unnamed indexes are named like PostgreSQL names them,
+    /// `<table>_<columns>_idx`, where an expression column stands for the name
+    /// of its function (`users_lower_idx`) or for `expr`. The parser numbers
+    /// the name when it is already taken; unlike PostgreSQL, long names are not
+    /// truncated.
+    fn from_create_index(create_index: &CreateIndex, table_name: &str, dialect: &str) -> Self {
+        let columns: Vec<String> = create_index
+            .columns
+            .iter()
+            .map(|column| index_column_name(dialect, column))
+            .collect();
+
+        let name = match &create_index.name {
+            Some(name) => object_name(dialect, name),
+            None => {
+                let parts: Vec<String> = create_index
                     .columns
                     .iter()
-                    .map(|col| col.column.to_string())
-                    .collect(),
-                unique: create_index.unique,
-            })
-            .unwrap()
+                    .map(|column| {
+                        index_expr_name(dialect, &column.column.expr)
+                            .unwrap_or_else(|| "expr".to_string())
+                    })
+                    .collect();
+                format!("{table_name}_{}_idx", parts.join("_"))
+            }
+        };
+
+        Self {
+            name,
+            columns,
+            unique: create_index.unique,
+        }
     }
 
     /// Create an Index from a TableConstraint::Unique
-    pub(crate) fn from_table_constraint(constraint: TableConstraint) -> Self {
+    pub(crate) fn from_table_constraint(constraint: TableConstraint, dialect: &str) -> Self {
         match constraint {
             TableConstraint::Unique {
                 name: Some(index_name),
                 columns,
                 ..
             } => Self {
-                name: index_name.to_string(),
-                columns: columns.iter().map(|c| c.to_string()).collect(),
+                name: ident_name(dialect, &index_name),
+                columns: columns
+                    .iter()
+                    .map(|c| index_column_name(dialect, c))
+                    .collect(),
                 unique: true,
             },
             TableConstraint::Unique { name: None, .. } => {
@@ -432,11 +912,14 @@ impl Index {
 
 impl PrimaryKey {
     /// Create a PrimaryKey from a TableConstraint::PrimaryKey
-    pub(crate) fn from_table_constraint(constraint: TableConstraint) -> Self {
+    pub(crate) fn from_table_constraint(constraint: TableConstraint, dialect: &str) -> Self {
         match constraint {
             TableConstraint::PrimaryKey { name, columns, .. } => Self {
-                columns: columns.iter().map(|c| c.to_string()).collect(),
-                name: name.map(|n| n.to_string()).unwrap_or_default(),
+                columns: columns
+                    .iter()
+                    .map(|c| index_column_name(dialect, c))
+                    .collect(),
+                name: name.map(|n| ident_name(dialect, &n)).unwrap_or_default(),
             },
             _ => panic!("Expected TableConstraint::PrimaryKey, got {constraint:?}"),
         }
@@ -446,6 +929,7 @@ impl PrimaryKey {
     pub(crate) fn from_column_option(
         column_name: String,
         option: &sqlparser::ast::ColumnOptionDef,
+        dialect: &str,
     ) -> Option<Self> {
         match &option.option {
             ColumnOption::Unique {
@@ -455,7 +939,7 @@ impl PrimaryKey {
                 name: option
                     .name
                     .as_ref()
-                    .map(|n| n.to_string())
+                    .map(|n| ident_name(dialect, n))
                     .unwrap_or_default(),
             }),
             _ => None,
@@ -470,7 +954,7 @@ impl PrimaryKey {
 
 impl ForeignKey {
     /// Create a ForeignKey from a TableConstraint::ForeignKey
-    pub(crate) fn from_table_constraint(constraint: TableConstraint) -> Self {
+    pub(crate) fn from_table_constraint(constraint: TableConstraint, dialect: &str) -> Self {
         match constraint {
             TableConstraint::ForeignKey {
                 name,
@@ -481,10 +965,13 @@ impl ForeignKey {
                 on_update,
                 ..
             } => Self {
-                columns: columns.iter().map(|c| c.to_string()).collect(),
-                referenced_table: foreign_table.to_string(),
-                referenced_columns: referred_columns.iter().map(|c| c.to_string()).collect(),
-                name: name.map(|n| n.to_string()).unwrap_or_default(),
+                columns: columns.iter().map(|c| ident_name(dialect, c)).collect(),
+                referenced_table: object_name(dialect, &foreign_table),
+                referenced_columns: referred_columns
+                    .iter()
+                    .map(|c| ident_name(dialect, c))
+                    .collect(),
+                name: name.map(|n| ident_name(dialect, &n)).unwrap_or_default(),
                 on_delete: on_delete
                     .as_ref()
                     .map(|a| a.to_string())
@@ -502,6 +989,7 @@ impl ForeignKey {
     pub(crate) fn from_column_option(
         column_name: String,
         option: &sqlparser::ast::ColumnOptionDef,
+        dialect: &str,
     ) -> Option<Self> {
         match &option.option {
             ColumnOption::ForeignKey {
@@ -512,12 +1000,15 @@ impl ForeignKey {
                 ..
             } => Some(Self {
                 columns: vec![column_name],
-                referenced_table: foreign_table.to_string(),
-                referenced_columns: referred_columns.iter().map(|c| c.to_string()).collect(),
+                referenced_table: object_name(dialect, foreign_table),
+                referenced_columns: referred_columns
+                    .iter()
+                    .map(|c| ident_name(dialect, c))
+                    .collect(),
                 name: option
                     .name
                     .as_ref()
-                    .map(|n| n.to_string())
+                    .map(|n| ident_name(dialect, n))
                     .unwrap_or_default(),
                 on_delete: on_delete
                     .as_ref()
@@ -545,16 +1036,127 @@ impl ForeignKey {
 
 /// Parse a qualified name into (schema_name, table_name)
 ///
-/// Returns the schema name (empty string for default schema) and the table name.
+/// Returns the schema name (empty string for default schema) and the table name,
+/// normalized with [`ident_name`].
 /// For example: "public.users" -> ("public", "users"), "users" -> ("", "users")
-fn parse_qualified_name(name: &ObjectName) -> (String, String) {
-    if name.0.len() > 1 {
-        (name.0[0].to_string(), name.0[1].to_string())
+fn parse_qualified_name(dialect: &str, name: &ObjectName) -> (String, String) {
+    let part = |index: usize| match name.0[index].as_ident() {
+        Some(ident) => ident_name(dialect, ident),
+        None => name.0[index].to_string(),
+    };
+
+    match name.0.len() {
+        0 => (String::new(), String::new()),
+        1 => (String::new(), part(0)),
+        len => (part(len - 2), part(len - 1)),
+    }
+}
+
+/// Normalized name of a possibly qualified object, parts joined with `.`
+fn object_name(dialect: &str, name: &ObjectName) -> String {
+    name.0
+        .iter()
+        .map(|part| match part.as_ident() {
+            Some(ident) => ident_name(dialect, ident),
+            None => part.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Name of an identifier as the engine stores it
+///
+/// The quotes are dropped. PostgreSQL folds unquoted identifiers to lower
+/// case, and sqlc does the same for MySQL; other engines keep them as written.
+fn ident_name(dialect: &str, ident: &Ident) -> String {
+    let folds_case = matches!(
+        dialect.to_ascii_lowercase().as_str(),
+        "postgresql" | "postgres" | "mysql"
+    );
+    if folds_case && ident.quote_style.is_none() {
+        ident.value.to_lowercase()
     } else {
-        (String::new(), name.0[0].to_string())
+        ident.value.clone()
     }
 }
 
+/// Name PostgreSQL derives from an index expression, if any
+///
+/// This is the column of a column reference and the function of a function
+/// call, looking through casts and parentheses.
+fn index_expr_name(dialect: &str, expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Identifier(ident) => Some(ident_name(dialect, ident)),
+        Expr::CompoundIdentifier(idents) => idents.last().map(|ident| ident_name(dialect, ident)),
+        Expr::Function(function) => {
+            let (_, name) = parse_qualified_name(dialect, &function.name);
+            Some(name)
+        }
+        Expr::Cast { expr, .. } | Expr::Nested(expr) => index_expr_name(dialect, expr),
+        _ => None,
+    }
+}
+
+/// `name`, or `name` followed by the first number making it unused in `schema`
+///
+/// Tables and indexes share a namespace, as in PostgreSQL.
+fn unused_relation_name(schema: &Schema, name: &str) -> String {
+    let taken = |candidate: &str| {
+        schema.tables.iter().any(|table| {
+            table.rel.as_ref().is_some_and(|rel| rel.name == candidate)
+                || table.indexes.iter().any(|index| index.name == candidate)
+        })
+    };
+
+    let mut candidate = name.to_string();
+    let mut number = 0;
+    while taken(&candidate) {
+        number += 1;
+        candidate = format!("{name}{number}");
+    }
+    candidate
+}
+
+/// Name of an index column, normalized when it is a plain column reference
+fn index_column_name(dialect: &str, column: &IndexColumn) -> String {
+    match &column.column.expr {
+        Expr::Identifier(ident) => ident_name(dialect, ident),
+        _ => column.column.to_string(),
+    }
+}
+
+/// Extract the `Line: N, Column: M` location sqlparser appends to its messages
+fn error_location(error: &ParserError) -> Option<(u64, u64)> {
+    let message = match error {
+        ParserError::TokenizerError(message) | ParserError::ParserError(message) => message,
+        ParserError::RecursionLimitExceeded => return None,
+    };
+
+    let rest = &message[message.rfind("Line: ")? + "Line: ".len()..];
+    let (line, rest) = rest.split_once(", Column: ")?;
+    let column: String = rest.chars().take_while(char::is_ascii_digit).collect();
+
+    Some((line.parse().ok()?, column.parse().ok()?))
+}
+
+/// Return the `;` delimited statement of `sql` that contains the given location
+fn statement_at(sql: &str, line: u64, column: u64) -> Option<String> {
+    let line_start: usize = sql
+        .split_inclusive('\n')
+        .take(usize::try_from(line).ok()?.checked_sub(1)?)
+        .map(str::len)
+        .sum();
+    let offset = sql[line_start..]
+        .char_indices()
+        .nth(usize::try_from(column).ok()?.saturating_sub(1))
+        .map_or(sql.len(), |(i, _)| line_start + i);
+
+    let start = sql[..offset].rfind(';').map_or(0, |i| i + 1);
+    let end = sql[offset..].find(';').map_or(sql.len(), |i| offset + i);
+
+    Some(sql[start..end].trim().to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -567,7 +1169,18 @@ mod tests {
     fn test_builder_new() {
         let builder = CatalogBuilder::new("postgresql");
         assert_eq!(builder.dialect, "postgresql");
+        assert_eq!(builder.default_schema, "public");
         assert!(builder.schemas.is_empty());
+
+        assert_eq!(CatalogBuilder::new("sqlite").default_schema, "main");
+        assert_eq!(CatalogBuilder::new("mysql").default_schema, "public");
+        assert_eq!(CatalogBuilder::new("generic").default_schema, "");
+        assert_eq!(
+            CatalogBuilder::new("mysql")
+                .with_default_schema("app")
+                .default_schema,
+            "app"
+        );
     }
 
     #[test]
@@ -668,6 +1281,55 @@ mod tests {
         assert_eq!(table.primary_key.as_ref().unwrap().columns, vec!["id"]);
     }
 
+    #[test]
+    fn test_builder_parse_create_schema() {
+        let sql = r#"
+            CREATE SCHEMA app;
+            CREATE SCHEMA IF NOT EXISTS audit AUTHORIZATION admin;
+            COMMENT ON SCHEMA app IS 'Application data';
+            CREATE TABLE users (id INT PRIMARY KEY);
+            CREATE INDEX users_id_idx ON users (id);
+            CREATE TABLE app.posts (id INT);
+        "#;
+
+        let mut builder = CatalogBuilder::new("postgresql");
+        builder.parse_sql(sql).unwrap();
+
+        let catalog = builder.build();
+        assert_eq!(catalog.default_schema, "public");
+
+        let names: Vec<_> = catalog.schemas.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["public", "app", "audit"]);
+
+        let users = &catalog.schemas[0].tables[0];
+        assert_eq!(users.rel.as_ref().unwrap().schema, "public");
+        assert_eq!(users.indexes[0].name, "users_id_idx");
+
+        assert_eq!(catalog.schemas[1].comment, "Application data");
+        assert_eq!(catalog.schemas[1].tables.len(), 1);
+        assert!(catalog.schemas[2].tables.is_empty());
+    }
+
+    #[test]
+    fn test_builder_parse_mysql_database() {
+        let sql = r#"
+            CREATE DATABASE shop;
+            USE shop;
+            CREATE TABLE orders (id INT);
+        "#;
+
+        let mut builder = CatalogBuilder::new("mysql");
+        builder.parse_sql(sql).unwrap();
+
+        let catalog = builder.build();
+        assert_eq!(catalog.default_schema, "shop");
+        assert_eq!(catalog.schemas.len(), 1);
+        assert_eq!(
+            catalog.schemas[0].tables[0].rel.as_ref().unwrap().schema,
+            "shop"
+        );
+    }
+
     #[test]
     fn test_builder_clone() {
         let sql = "CREATE TABLE users (id INTEGER PRIMARY KEY)";
@@ -692,6 +1354,119 @@ mod tests {
         assert_eq!(catalog.schemas[0].tables.len(), 1);
     }
 
+    #[test]
+    fn test_builder_build_name_and_comment() {
+        let catalog = CatalogBuilder::new("postgresql")
+            .with_name("app")
+            .with_comment("Application catalog")
+            .build();
+        assert_eq!(catalog.name, "app");
+        assert_eq!(catalog.comment, "Application catalog");
+
+        let mut builder = CatalogBuilder::new("mysql");
+        builder.parse_sql("CREATE TABLE orders (id INT)").unwrap();
+        let catalog = builder.build();
+        assert!(catalog.name.is_empty());
+        assert_eq!(catalog.default_schema, "public");
+        assert_eq!(
+            catalog.schemas[0].tables[0].rel.as_ref().unwrap().schema,
+            "public"
+        );
+    }
+
+    #[test]
+    fn test_builder_build_schema_order() {
+        let sql = "CREATE TABLE zeta.a (id int);
+                   CREATE TABLE public.b (id int);
+                   CREATE TABLE alpha.c (id int);
+                   CREATE TABLE public.a (id int);";
+
+        let mut builder = CatalogBuilder::new("postgresql");
+        builder.parse_sql(sql).unwrap();
+
+        let names = |catalog: &crate::plugin::Catalog| -> Vec<String> {
+            catalog.schemas.iter().map(|s| s.name.clone()).collect()
+        };
+
+        let catalog = builder.clone().build();
+        assert_eq!(names(&catalog), vec!["public", "alpha", "zeta"]);
+        let tables: Vec<_> = catalog.schemas[0]
+            .tables
+            .iter()
+            .map(|t| t.rel.as_ref().unwrap().name.as_str())
+            .collect();
+        assert_eq!(tables, vec!["b", "a"]);
+
+        let catalog = builder
+            .clone()
+            .with_schema_order(SchemaOrder::Declaration)
+            .build();
+        assert_eq!(names(&catalog), vec!["zeta", "public", "alpha"]);
+    }
+
+    #[test]
+    fn test_builder_build_is_deterministic() {
+        // Every builder hashes its schemas with its own random seed, so
+        // separately built catalogs differ unless the order is well defined
+        let sql: String = (0..32)
+            .rev()
+            .map(|n| format!("CREATE TABLE s{n}.t{n} (id int); CREATE TYPE s{n}.e AS ENUM ('a');"))
+            .collect();
+        let build = |order: SchemaOrder| {
+            let mut builder = CatalogBuilder::new("postgresql").with_schema_order(order);
+            builder.parse_sql(&sql).unwrap();
+            builder.build()
+        };
+
+        for order in [SchemaOrder::DefaultFirst, SchemaOrder::Declaration] {
+            let catalog = build(order);
+            for _ in 0..5 {
+                assert_eq!(build(order), catalog);
+            }
+        }
+
+        let catalog = build(SchemaOrder::DefaultFirst);
+        assert_eq!(catalog.schemas[0].name, "s0");
+        assert_eq!(catalog.schemas[31].name, "s9");
+        let catalog = build(SchemaOrder::Declaration);
+        assert_eq!(catalog.schemas[0].name, "s31");
+    }
+
+    #[test]
+    fn test_builder_build_declaration_order_after_merge() {
+        let mut builder =
+            CatalogBuilder::new("postgresql").with_schema_order(SchemaOrder::Declaration);
+        builder
+            .parse_sql("CREATE TABLE users (id int); CREATE TABLE b.t (id int)")
+            .unwrap();
+        builder.schemas.insert(
+            "extra".to_string(),
+            Schema {
+                name: "extra".to_string(),
+                ..Default::default()
+            },
+        );
+
+        builder.merge_catalog(crate::plugin::Catalog {
+            default_schema: "public".to_string(),
+            schemas: vec![
+                Schema {
+                    name: "a".to_string(),
+                    ..Default::default()
+                },
+                Schema {
+                    name: "public".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        });
+
+        let catalog = builder.build();
+        let names: Vec<_> = catalog.schemas.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["public", "b", "a", "extra"]);
+    }
+
     #[test]
     fn test_builder_merge_catalog_disjoint_schemas() {
         let sql = "CREATE TABLE public.users (id int)";
@@ -960,28 +1735,10 @@ mod tests {
 
     #[test]
     fn test_column_clone() {
-        let column = Column {
-            name: "test".to_string(),
-            not_null: false,
-            is_array: false,
-            comment: String::new(),
-            length: 0,
-            is_named_param: false,
-            is_func_call: false,
-            scope: String::new(),
-            table: None,
-            table_alias: String::new(),
-            r#type: Some(Identifier {
-                catalog: String::new(),
-                schema: String::new(),
-                name: "INTEGER".to_string(),
-            }),
-            is_sqlc_slice: false,
-            embed_table: None,
-            original_name: "test".to_string(),
-            unsigned: false,
-            array_dims: 0,
-        };
+        let column = Column::builder("test")
+            .ty("INTEGER")
+            .original_name("test")
+            .build();
 
         let cloned = column.clone();
         assert_eq!(column, cloned);
@@ -1045,6 +1802,35 @@ mod tests {
         assert_eq!(table.indexes[0].columns, vec!["first_name", "last_name"]);
     }
 
+    #[test]
+    fn test_index_without_name() {
+        let sql = r#"
+            CREATE TABLE users (email VARCHAR(255), first_name VARCHAR(255));
+            CREATE INDEX ON users (email, first_name);
+            CREATE UNIQUE INDEX ON users (lower(email));
+            CREATE INDEX ON users (email, first_name);
+            CREATE INDEX ON users ((email || first_name));
+        "#;
+
+        let mut builder = CatalogBuilder::new("postgresql");
+        builder.parse_sql(sql).unwrap();
+
+        let schema = builder.schemas.get("public").unwrap();
+        let table = &schema.tables[0];
+
+        let names: Vec<_> = table.indexes.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "users_email_first_name_idx",
+                "users_lower_idx",
+                "users_email_first_name_idx1",
+                "users_expr_idx",
+            ]
+        );
+        assert!(table.indexes[1].unique);
+    }
+
     #[test]
     fn test_index_contains() {
         let index = Index {
@@ -1114,6 +1900,130 @@ mod tests {
         assert_eq!(index, cloned);
     }
 
+    // ============================================================================
+    // Enum Tests
+    // ============================================================================
+
+    #[test]
+    fn test_enum_from_create_type() {
+        let sql = r#"
+            CREATE TYPE mood AS ENUM ('sad', 'ok', 'happy');
+            CREATE TYPE app.status AS ENUM ('active', 'disabled');
+        "#;
+
+        let mut builder = CatalogBuilder::new("postgresql");
+        builder.parse_sql(sql).unwrap();
+
+        let mood = &builder.schemas.get("public").unwrap().enums[0];
+        assert_eq!(mood.name, "mood");
+        assert_eq!(mood.vals, vec!["sad", "ok", "happy"]);
+
+        let status = &builder.schemas.get("app").unwrap().enums[0];
+        assert_eq!(status.name, "status");
+        assert_eq!(status.vals, vec!["active", "disabled"]);
+    }
+
+    #[test]
+    fn test_enum_alter_type_add_value() {
+        let sql = r#"
+            CREATE TYPE mood AS ENUM ('sad', 'happy');
+            ALTER TYPE mood ADD VALUE 'ecstatic';
+            ALTER TYPE mood ADD VALUE 'ok' BEFORE 'happy';
+            ALTER TYPE mood ADD VALUE 'angry' AFTER 'sad';
+            ALTER TYPE mood ADD VALUE IF NOT EXISTS 'ok';
+        "#;
+
+        let mut builder = CatalogBuilder::new("postgresql");
+        builder.parse_sql(sql).unwrap();
+
+        let mood = &builder.schemas.get("public").unwrap().enums[0];
+        assert_eq!(mood.vals, vec!["sad", "angry", "ok", "happy", "ecstatic"]);
+    }
+
+    #[test]
+    fn test_enum_alter_type_rename() {
+        let sql = r#"
+            CREATE TYPE app.mood AS ENUM ('sad', 'happy');
+            ALTER TYPE app.mood RENAME VALUE 'sad' TO 'blue';
+            ALTER TYPE app.mood RENAME TO feeling;
+            ALTER TYPE app.unknown RENAME VALUE 'a' TO 'b';
+        "#;
+
+        let mut builder = CatalogBuilder::new("postgresql");
+        builder.parse_sql(sql).unwrap();
+
+        let schema = builder.schemas.get("app").unwrap();
+        assert_eq!(schema.enums.len(), 1);
+        assert_eq!(schema.enums[0].name, "feeling");
+        assert_eq!(schema.enums[0].vals, vec!["blue", "happy"]);
+    }
+
+    #[test]
+    fn test_enum_from_mysql_column() {
+        let sql = r#"
+            CREATE TABLE users (
+                id INT PRIMARY KEY,
+                status ENUM('active', 'disabled') NOT NULL,
+                role ENUM('admin', 'member')
+            );
+        "#;
+
+        let mut builder = CatalogBuilder::new("mysql");
+        builder.parse_sql(sql).unwrap();
+
+        let schema = builder.schemas.get("public").unwrap();
+        let names: Vec<_> = schema.enums.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["users_status", "users_role"]);
+        assert_eq!(schema.enums[0].vals, vec!["active", "disabled"]);
+
+        let columns = &schema.tables[0].columns;
+        assert_eq!(columns[0].r#type.as_ref().unwrap().name, "INT");
+        assert_eq!(columns[1].r#type.as_ref().unwrap().name, "users_status");
+        assert!(columns[1].not_null);
+        assert_eq!(columns[2].r#type.as_ref().unwrap().name, "users_role");
+    }
+
+    // ============================================================================
+    // Composite Type Tests
+    // ============================================================================
+
+    #[test]
+    fn test_composite_type_from_create_type() {
+        let sql = r#"
+            CREATE TYPE address AS (street TEXT, city TEXT);
+            CREATE TYPE app.point AS (x INT, y INT);
+            ALTER TYPE app.point RENAME TO coordinates;
+            CREATE TABLE users (id INT NOT NULL, home address, at app.coordinates);
+        "#;
+
+        let mut builder = CatalogBuilder::new("postgresql");
+        builder.parse_sql(sql).unwrap();
+
+        let schema = builder.schemas.get("public").unwrap();
+        assert_eq!(schema.composite_types[0].name, "address");
+        assert_eq!(
+            builder.schemas.get("app").unwrap().composite_types[0].name,
+            "coordinates"
+        );
+
+        let attributes = builder.composite_attributes();
+        let columns = &schema.tables[0].columns;
+        let fields: Vec<_> = attributes
+            .resolve(&columns[2])
+            .unwrap()
+            .iter()
+            .map(|field| {
+                (
+                    field.name.as_str(),
+                    field.r#type.as_ref().unwrap().name.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(fields, vec![("x", "INT"), ("y", "INT")]);
+        assert_eq!(attributes.resolve(&columns[1]).unwrap().len(), 2);
+        assert!(attributes.resolve(&columns[0]).is_none());
+    }
+
     // ============================================================================
     // PrimaryKey Tests
     // ============================================================================
@@ -1401,7 +2311,7 @@ mod tests {
         let mut builder = CatalogBuilder::new("postgresql");
         builder.parse_sql(sql).unwrap();
 
-        let schema = builder.schemas.get("").unwrap();
+        let schema = builder.schemas.get("public").unwrap();
         assert_eq!(schema.tables.len(), 2);
 
         let users_table = &schema
@@ -1436,4 +2346,66 @@ mod tests {
         assert!(builder.schemas.contains_key("public"));
         assert!(builder.schemas.contains_key("auth"));
     }
+
+    // ============================================================================
+    // Error Tests
+    // ============================================================================
+
+    #[test]
+    fn test_parse_sql_unknown_dialect() {
+        let mut builder = CatalogBuilder::new("nosuchdb");
+        let err = builder
+            .parse_sql("CREATE TABLE users (id int)")
+            .unwrap_err();
+
+        assert!(matches!(err, Error::UnknownDialect(ref d) if d == "nosuchdb"));
+        assert_eq!(err.to_string(), "Unknown dialect: nosuchdb");
+    }
+
+    #[test]
+    fn test_parse_sql_error_location_and_statement() {
+        let sql = "CREATE TABLE users (id int);\nCREATE TABLE posts (id int,, title text);\n";
+
+        let mut builder = CatalogBuilder::new("postgresql");
+        let err = builder.parse_sql(sql).unwrap_err();
+
+        match err {
+            Error::Parse {
+                line,
+                column,
+                statement,
+                ..
+            } => {
+                assert_eq!(line, 2);
+                assert!(column > 0);
+                assert_eq!(statement, "CREATE TABLE posts (id int,, title text)");
+            }
+            other => panic!("expected parse error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_sql_tokenizer_error() {
+        let mut builder = CatalogBuilder::new("postgresql");
+        let err = builder
+            .parse_sql("CREATE TABLE users (name text DEFAULT 'unterminated)")
+            .unwrap_err();
+
+        assert!(matches!(err, Error::Parse { line: 1, .. }));
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn test_statement_at_first_statement() {
+        let sql = "CREATE TABLE a (id int); CREATE TABLE b (id int)";
+        assert_eq!(
+            statement_at(sql, 1, 3).as_deref(),
+            Some("CREATE TABLE a (id int)")
+        );
+        assert_eq!(
+            statement_at(sql, 1, 30).as_deref(),
+            Some("CREATE TABLE b (id int)")
+        );
+        assert_eq!(statement_at(sql, 0, 0), None);
+    }
 }
@@ -0,0 +1,589 @@
+//! Golden-file tests for plugins.
+//!
+//! A fixture is a directory describing one plugin invocation, together with
+//! the files the plugin is expected to generate:
+//!
+//! ```text
+//! tests/fixtures/users/
+//! ├── schema.sql      schema file (or a `schema/` directory of migrations)
+//! ├── queries.json    JSON array of `plugin::Query` messages (optional)
+//! ├── options.json    plugin options, sent as `plugin_options` (optional)
+//! └── expected/       golden copies of the generated files
+//! ```
+//!
+//! [`Fixture::check`] builds the [`GenerateRequest`], runs it through the same
+//! schema enrichment as the runtime, calls the plugin, checks the generated
+//! file names as the runtime does and compares every generated file to its
+//! golden copy, reporting a line diff on mismatch.
+//! Setting the [`BLESS_ENV`] variable (`SQLC_GEN_BLESS=1`) writes the
+//! generated files to `expected/` instead, removing golden files that are no
+//! longer generated.
+//!
+//! `queries.json` uses the proto3 JSON mapping of the `json` feature, so a
+//! request written by sqlc's JSON plugin can be pasted in directly.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sqlc_gen_core::plugin::{File, GenerateResponse};
+//! use sqlc_gen_core::testing::Fixture;
+//!
+//! #[test]
+//! fn users() {
+//!     Fixture::new("tests/fixtures/users").assert(|request| {
+//!         let names: Vec<_> = request.queries.iter().map(|q| q.name.clone()).collect();
+//!         Ok(GenerateResponse {
+//!             files: vec![File {
+//!                 name: "queries.txt".to_string(),
+//!                 contents: names.join("\n").into_bytes(),
+//!             }],
+//!         })
+//!     });
+//! }
+//! ```
+
+use crate::plugin::{GenerateRequest, GenerateResponse, Query, Settings};
+use crate::runtime::{finish_response, prepare_request, RunOptions};
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that makes [`Fixture::check`] update golden files
+pub const BLESS_ENV: &str = "SQLC_GEN_BLESS";
+
+/// Error produced while checking a fixture.
+#[derive(Debug)]
+pub enum Error {
+    /// A fixture file could not be read or a golden file could not be written
+    Io {
+        /// Path of the file
+        path: PathBuf,
+        /// Underlying I/O error
+        source: io::Error,
+    },
+
+    /// `queries.json` is not a valid JSON array of queries
+    Queries {
+        /// Path of the queries file
+        path: PathBuf,
+        /// Underlying JSON error
+        source: serde_json::Error,
+    },
+
+    /// Preparing the request or running the plugin failed
+    Runtime(crate::runtime::Error),
+
+    /// The generated files differ from the golden files
+    Mismatch(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Queries { path, source } => {
+                write!(f, "invalid queries in {}: {source}", path.display())
+            }
+            Self::Runtime(err) => write!(f, "{err}"),
+            Self::Mismatch(report) => write!(f, "{report}"),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Queries { source, .. } => Some(source),
+            Self::Runtime(err) => Some(err),
+            Self::Mismatch(_) => None,
+        }
+    }
+}
+
+impl From<crate::runtime::Error> for Error {
+    fn from(err: crate::runtime::Error) -> Self {
+        Self::Runtime(err)
+    }
+}
+
+/// A fixture directory describing one plugin invocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    /// Fixture directory
+    pub dir: PathBuf,
+
+    /// Database engine set in the request settings
+    pub engine: String,
+
+    /// Write the generated files as the new golden files instead of comparing
+    pub bless: bool,
+}
+
+impl Fixture {
+    /// Create a fixture for `dir`, using the `postgresql` engine
+    ///
+    /// Blessing is enabled when [`BLESS_ENV`] is set to a value other than
+    /// `0` or `false`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let bless = std::env::var(BLESS_ENV)
+            .map(|value| !matches!(value.as_str(), "" | "0" | "false"))
+            .unwrap_or(false);
+
+        Self {
+            dir: dir.into(),
+            engine: "postgresql".to_string(),
+            bless,
+        }
+    }
+
+    /// Set the database engine of the request
+    pub fn with_engine(mut self, engine: impl Into<String>) -> Self {
+        self.engine = engine.into();
+        self
+    }
+
+    /// Enable or disable blessing regardless of the environment
+    pub fn with_bless(mut self, bless: bool) -> Self {
+        self.bless = bless;
+        self
+    }
+
+    /// Directory holding the golden files
+    pub fn expected_dir(&self) -> PathBuf {
+        self.dir.join("expected")
+    }
+
+    /// Build the request described by the fixture, before schema enrichment
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `queries.json` or `options.json` cannot be read, or
+    /// if `queries.json` is invalid.
+    pub fn request(&self) -> Result<GenerateRequest, Error> {
+        let schema = ["schema.sql", "schema"]
+            .into_iter()
+            .filter(|entry| self.dir.join(entry).exists())
+            .map(str::to_string)
+            .collect();
+
+        let queries_path = self.dir.join("queries.json");
+        let queries: Vec<Query> = match read_optional(&queries_path)? {
+            Some(bytes) => serde_json::from_slice(&bytes).map_err(|source| Error::Queries {
+                path: queries_path,
+                source,
+            })?,
+            None => Vec::new(),
+        };
+
+        Ok(GenerateRequest {
+            settings: Some(Settings {
+                engine: self.engine.clone(),
+                schema,
+                ..Default::default()
+            }),
+            queries,
+            sqlc_version: "test".to_string(),
+            plugin_options: read_optional(&self.dir.join("options.json"))?.unwrap_or_default(),
+            ..Default::default()
+        })
+    }
+
+    /// Run `process` on the fixture and compare its output to the golden files
+    ///
+    /// When [`bless`](Self::bless) is set, the golden files are updated instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Mismatch`] with a readable report if the generated
+    /// files differ from the golden files, or an error if the fixture cannot
+    /// be read, the plugin fails or it generates file names that are not safe
+    /// to write below `expected/`.
+    pub fn check<F>(&self, process: F) -> Result<(), Error>
+    where
+        F: FnOnce(GenerateRequest) -> Result<GenerateResponse, Box<dyn StdError>>,
+    {
+        let options = RunOptions::default().with_base_dir(&self.dir);
+        let (request, attributes) = prepare_request(self.request()?, &options)?;
+        let response = attributes
+            .scope(|| process(request))
+            .map_err(crate::runtime::Error::Processor)?;
+
+        let response = finish_response(response, &options)?;
+
+        let generated: BTreeMap<PathBuf, Vec<u8>> = response
+            .files
+            .into_iter()
+            .map(|file| (PathBuf::from(file.name), file.contents))
+            .collect();
+
+        if self.bless {
+            self.bless_files(&generated)
+        } else {
+            self.compare_files(&generated)
+        }
+    }
+
+    /// Like [`check`](Self::check), but panics with the report on failure
+    ///
+    /// # Panics
+    ///
+    /// Panics if the generated files differ from the golden files or if the
+    /// fixture cannot be checked.
+    pub fn assert<F>(&self, process: F)
+    where
+        F: FnOnce(GenerateRequest) -> Result<GenerateResponse, Box<dyn StdError>>,
+    {
+        if let Err(err) = self.check(process) {
+            panic!("fixture {} failed:\n{err}", self.dir.display());
+        }
+    }
+
+    /// Replace the golden files with the generated files
+    fn bless_files(&self, generated: &BTreeMap<PathBuf, Vec<u8>>) -> Result<(), Error> {
+        let expected_dir = self.expected_dir();
+
+        for name in golden_files(&expected_dir)? {
+            if !generated.contains_key(&name) {
+                let path = expected_dir.join(&name);
+                std::fs::remove_file(&path).map_err(|source| Error::Io { path, source })?;
+            }
+        }
+
+        for (name, contents) in generated {
+            let path = expected_dir.join(name);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|source| Error::Io {
+                    path: parent.to_path_buf(),
+                    source,
+                })?;
+            }
+            std::fs::write(&path, contents).map_err(|source| Error::Io { path, source })?;
+        }
+
+        Ok(())
+    }
+
+    /// Compare the generated files to the golden files
+    fn compare_files(&self, generated: &BTreeMap<PathBuf, Vec<u8>>) -> Result<(), Error> {
+        let expected_dir = self.expected_dir();
+        let mut report = String::new();
+
+        for (name, contents) in generated {
+            let path = expected_dir.join(name);
+            match read_optional(&path)? {
+                Some(expected) if expected == *contents => {}
+                Some(expected) => {
+                    report += &format!("--- {} (golden)\n+++ generated\n", path.display());
+                    report += &diff(
+                        &String::from_utf8_lossy(&expected),
+                        &String::from_utf8_lossy(contents),
+                    );
+                }
+                None => report += &format!("missing golden file {}\n", path.display()),
+            }
+        }
+
+        for name in golden_files(&expected_dir)? {
+            if !generated.contains_key(&name) {
+                report += &format!(
+                    "golden file {} was not generated\n",
+                    expected_dir.join(name).display()
+                );
+            }
+        }
+
+        if report.is_empty() {
+            Ok(())
+        } else {
+            report += &format!("\nset {BLESS_ENV}=1 to update the golden files\n");
+            Err(Error::Mismatch(report))
+        }
+    }
+}
+
+/// Read a fixture file, returning `None` if it does not exist
+fn read_optional(path: &Path) -> Result<Option<Vec<u8>>, Error> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(Error::Io {
+            path: path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+/// List the golden files below `dir`, relative to it
+fn golden_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(relative) = pending.pop() {
+        let path = dir.join(&relative);
+        let entries = match std::fs::read_dir(&path) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(source) => return Err(Error::Io { path, source }),
+        };
+
+        for entry in entries {
+            let entry = entry.map_err(|source| Error::Io {
+                path: path.clone(),
+                source,
+            })?;
+            let name = relative.join(entry.file_name());
+            if entry.path().is_dir() {
+                pending.push(name);
+            } else {
+                files.push(name);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Number of unchanged lines shown around each change
+const CONTEXT: usize = 2;
+
+/// Render a line diff of `expected` and `actual`
+///
+/// Removed lines are prefixed with `-`, added lines with `+` and unchanged
+/// lines with a space; long unchanged runs are collapsed.
+fn diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+
+    // Longest common subsequence lengths of the suffixes
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+
+    let changed: Vec<usize> = (0..lines.len()).filter(|&n| lines[n].0 != ' ').collect();
+    let near_change = |n: usize| {
+        changed
+            .iter()
+            .any(|&c| c.saturating_sub(CONTEXT) <= n && n <= c + CONTEXT)
+    };
+
+    let mut output = String::new();
+    let mut skipped = false;
+    for (n, (tag, line)) in lines.iter().enumerate() {
+        if near_change(n) {
+            output += &format!("{tag}{line}\n");
+            skipped = false;
+        } else if !skipped {
+            output += " ...\n";
+            skipped = true;
+        }
+    }
+
+    if expected.ends_with('\n') != actual.ends_with('\n') {
+        output += "\\ trailing newline differs\n";
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::File;
+
+    /// Plugin listing the tables of the catalog and the queries of the request
+    fn list(request: GenerateRequest) -> Result<GenerateResponse, Box<dyn StdError>> {
+        let catalog = request.catalog.ok_or("missing catalog")?;
+        let tables: Vec<_> = catalog.schemas[0]
+            .tables
+            .iter()
+            .map(|t| t.rel.as_ref().unwrap().name.clone())
+            .collect();
+        let queries: Vec<_> = request.queries.iter().map(|q| q.name.clone()).collect();
+
+        Ok(GenerateResponse {
+            files: vec![
+                File {
+                    name: "tables.txt".to_string(),
+                    contents: format!("{}\n", tables.join("\n")).into_bytes(),
+                },
+                File {
+                    name: "queries/names.txt".to_string(),
+                    contents: format!("{}\n", queries.join("\n")).into_bytes(),
+                },
+                File {
+                    name: "options.json".to_string(),
+                    contents: request.plugin_options,
+                },
+            ],
+        })
+    }
+
+    fn fixture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("schema.sql"),
+            "CREATE TABLE users (id int);\nCREATE TABLE posts (id int);",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("queries.json"),
+            r#"[{"name": "GetUser", "cmd": ":one"}, {"name": "ListPosts", "cmd": ":many"}]"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("options.json"), r#"{"package":"db"}"#).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_request_from_fixture() {
+        let dir = fixture();
+        let request = Fixture::new(dir.path()).request().unwrap();
+
+        let settings = request.settings.unwrap();
+        assert_eq!(settings.engine, "postgresql");
+        assert_eq!(settings.schema, vec!["schema.sql"]);
+        assert_eq!(request.queries.len(), 2);
+        assert_eq!(request.queries[1].cmd, ":many");
+        assert_eq!(request.plugin_options, br#"{"package":"db"}"#);
+    }
+
+    #[test]
+    fn test_bless_then_check() {
+        let dir = fixture();
+        let fixture = Fixture::new(dir.path());
+
+        fixture.clone().with_bless(true).check(list).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("expected/queries/names.txt")).unwrap(),
+            "GetUser\nListPosts\n"
+        );
+
+        fixture.with_bless(false).check(list).unwrap();
+    }
+
+    #[test]
+    fn test_check_reports_diff() {
+        let dir = fixture();
+        std::fs::create_dir_all(dir.path().join("expected/queries")).unwrap();
+        std::fs::write(dir.path().join("expected/tables.txt"), "users\naccounts\n").unwrap();
+        std::fs::write(dir.path().join("expected/stale.txt"), "").unwrap();
+
+        let err = Fixture::new(dir.path())
+            .with_bless(false)
+            .check(list)
+            .unwrap_err();
+
+        let Error::Mismatch(report) = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert!(report.contains(" users\n-accounts\n+posts\n"));
+        assert!(report.contains("missing golden file"));
+        assert!(report.contains("stale.txt was not generated"));
+        assert!(report.contains("SQLC_GEN_BLESS=1"));
+    }
+
+    #[test]
+    fn test_bless_removes_stale_files() {
+        let dir = fixture();
+        std::fs::create_dir_all(dir.path().join("expected")).unwrap();
+        std::fs::write(dir.path().join("expected/stale.txt"), "").unwrap();
+
+        Fixture::new(dir.path())
+            .with_bless(true)
+            .check(list)
+            .unwrap();
+        assert!(!dir.path().join("expected/stale.txt").exists());
+    }
+
+    #[test]
+    fn test_bless_refuses_unsafe_names() {
+        let dir = fixture();
+        let escape = |_| {
+            Ok(GenerateResponse {
+                files: vec![File {
+                    name: "../escaped.txt".to_string(),
+                    contents: b"oops".to_vec(),
+                }],
+            })
+        };
+
+        let err = Fixture::new(dir.path())
+            .with_bless(true)
+            .check(escape)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Runtime(crate::runtime::Error::InvalidPaths(_))
+        ));
+        assert!(!dir.path().join("escaped.txt").exists());
+
+        let err = Fixture::new(dir.path())
+            .with_bless(false)
+            .check(escape)
+            .unwrap_err();
+        assert!(matches!(err, Error::Runtime(_)));
+    }
+
+    #[test]
+    fn test_check_normalizes_names() {
+        let dir = fixture();
+        std::fs::create_dir_all(dir.path().join("expected/db")).unwrap();
+        std::fs::write(dir.path().join("expected/db/models.txt"), "users\n").unwrap();
+
+        Fixture::new(dir.path())
+            .with_bless(false)
+            .check(|_| {
+                Ok(GenerateResponse {
+                    files: vec![File {
+                        name: "./db\\models.txt".to_string(),
+                        contents: b"users\n".to_vec(),
+                    }],
+                })
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_invalid_queries() {
+        let dir = fixture();
+        std::fs::write(dir.path().join("queries.json"), r#"{"name": "GetUser"}"#).unwrap();
+
+        let err = Fixture::new(dir.path()).request().unwrap_err();
+        assert!(matches!(err, Error::Queries { .. }));
+    }
+
+    #[test]
+    fn test_diff_collapses_unchanged_lines() {
+        let expected = "a\nb\nc\nd\ne\nf\ng\n";
+        let actual = "a\nb\nc\nd\ne\nF\ng\n";
+
+        assert_eq!(diff(expected, actual), " ...\n d\n e\n-f\n+F\n g\n");
+    }
+}